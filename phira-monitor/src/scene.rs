@@ -68,7 +68,8 @@ pub struct PlayerView {
 
 impl PlayerView {
     pub fn new(info: UserInfo, chart: Chart, emitter: ParticleEmitter) -> Self {
-        let judge = Judge::new(&chart);
+        // judgements come from the remote player, local key settings don't matter
+        let judge = Judge::new(&chart, &prpr::config::Config::default());
         Self {
             id: info.id,
             name: info.name,
@@ -174,7 +174,7 @@ impl Page for OffsetPage {
             } else {
                 if self.cali_last {
                     let g = ui.to_global(ct);
-                    self.emitter.emit_at(vec2(g.0, g.1), 0., self.color);
+                    self.emitter.emit_at(vec2(g.0, g.1), 0., self.color, false);
                     let _ = self.cali_hit.play(PlaySfxParams::default());
                 }
                 self.cali_last = false;
@@ -236,17 +236,33 @@ impl Page for ResPackPage {
             if let Some(pack) = &item.loaded {
                 let width = 0.16;
                 let mut r = Rect::new(cr.x + 0.07, cr.y + 0.1, width, 0.);
+                // only the first frame of animated notes is shown
+                let frames = pack.info.note_frames as f32;
+                let draw_frame = |ui: &mut Ui, r: Rect, tex: Texture2D| {
+                    let r = ui.rect_to_global(r);
+                    draw_texture_ex(
+                        tex,
+                        r.x,
+                        r.y,
+                        c,
+                        DrawTextureParams {
+                            source: Some(Rect::new(0., 0., tex.width() / frames, tex.height())),
+                            dest_size: Some(vec2(r.w, r.h)),
+                            ..Default::default()
+                        },
+                    );
+                };
                 let mut draw = |mut r: Rect, tex: Texture2D, mh: Texture2D| {
                     let y = r.y;
-                    r.h = tex.height() / tex.width() * r.w;
+                    r.h = tex.height() / tex.width() * frames * r.w;
                     r.y = y - r.h / 2.;
-                    ui.fill_rect(r, (tex, r, ScaleType::Fit, c));
+                    draw_frame(ui, r, tex);
                     r.x += r.w * 1.8;
                     r.w *= mh.width() / tex.width();
                     r.x -= r.w / 2.;
-                    r.h = mh.height() / mh.width() * r.w;
+                    r.h = mh.height() / mh.width() * frames * r.w;
                     r.y = y - r.h / 2.;
-                    ui.fill_rect(r, (mh, r, ScaleType::Fit, c));
+                    draw_frame(ui, r, mh);
                 };
                 let sp = (cr.h - 0.4) / 2.;
                 draw(r, *pack.note_style.click, *pack.note_style_mh.click);
@@ -304,7 +320,7 @@ impl Page for ResPackPage {
                             source: Some({
                                 if pack.info.hold_repeat {
                                     let hold_body = style.hold_body.as_ref().unwrap();
-                                    let w = hold_body.width();
+                                    let w = hold_body.width() / frames;
                                     Rect::new(0., 0., w, r2.h / width / 2. * w)
                                 } else {
                                     conv(style.hold_body_rect(), &style.hold)
@@ -339,12 +355,12 @@ impl Page for ResPackPage {
                 let p = (t - inter * rnd) / 0.9;
                 if p <= 1. {
                     let y = st + (line - st) * p;
-                    let h = tex.height() / tex.width() * width;
+                    let h = tex.height() / tex.width() * frames * width;
                     let r = Rect::new(cx - width / 2., y - h / 2., width, h);
-                    ui.fill_rect(r, (tex, r, ScaleType::Fit, c));
+                    draw_frame(ui, r, tex);
                 } else if irnd != self.last_round {
                     if let Some(emitter) = &mut self.emitter {
-                        emitter.emit_at(vec2(cx, line), 0., pack.info.fx_perfect());
+                        emitter.emit_at(vec2(cx, line), 0., pack.info.fx_perfect(), false);
                     }
                     if let Some(sfxs) = &mut self.sfxs {
                         let _ = sfxs[(irnd % 3) as usize].play(PlaySfxParams::default());
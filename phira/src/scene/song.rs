@@ -22,7 +22,7 @@ use phira_mp_common::{ClientCommand, CompactPos, JudgeEvent, TouchFrame};
 use prpr::{
     config::Mods,
     core::Tweenable,
-    ext::{poll_future, semi_black, semi_white, unzip_into, JoinToString, LocalTask, RectExt, SafeTexture, ScaleType},
+    ext::{decode_music, poll_future, semi_black, semi_white, unzip_into, JoinToString, LocalTask, RectExt, SafeTexture, ScaleType},
     fs,
     info::ChartInfo,
     judge::{icon_index, Judge},
@@ -342,7 +342,7 @@ impl SongScene {
                         let mut fs = fs_from_path(&path)?;
                         let info = fs::load_info(fs.as_mut()).await?;
                         with_effects(
-                            AudioClip::decode(fs.load_file(&info.music).await?)?,
+                            decode_music(fs.load_file(&info.music).await?)?,
                             Some((info.preview_start, info.preview_end.unwrap_or(info.preview_start + 15.))),
                         )
                     } else {
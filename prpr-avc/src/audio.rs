@@ -0,0 +1,50 @@
+use crate::{AVCodecContext, AVFormatContext, AVFrame, AVPacket, MediaSource, SwrContext};
+use anyhow::{Context, Result};
+
+/// Decoded audio as interleaved `f32` samples.
+pub struct AudioData {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: u32,
+}
+
+/// Decodes the first audio stream of `source` into `channels` channels, resampled to `sample_rate` if given.
+///
+/// Works on any container FFmpeg can read, including videos with an audio track.
+pub fn decode_audio(source: Box<dyn MediaSource>, channels: u32, sample_rate: Option<u32>) -> Result<AudioData> {
+    let mut format_ctx = AVFormatContext::new()?;
+    format_ctx.open_source(source)?;
+    format_ctx.find_stream_info()?;
+
+    let audio_stream = format_ctx.streams().into_iter().find(|it| it.is_audio()).context("no audio")?;
+    let decoder = audio_stream.find_decoder()?;
+    let mut codec_ctx = AVCodecContext::new(decoder, audio_stream.codec_params(), None)?;
+    let sample_rate = sample_rate.unwrap_or(codec_ctx.sample_rate() as u32);
+    let mut swr = SwrContext::new(&codec_ctx, channels as _, sample_rate as _)?;
+
+    let mut packet = AVPacket::new()?;
+    let mut frame = AVFrame::new()?;
+    let mut samples = Vec::new();
+    let audio_index = audio_stream.index();
+    while format_ctx.read_frame(&mut packet)? {
+        if packet.stream_index() == audio_index {
+            codec_ctx.send_packet(Some(&packet))?;
+            while codec_ctx.receive_frame(&mut frame)? {
+                swr.convert(Some(&frame), &mut samples)?;
+            }
+        }
+        packet.unref();
+    }
+    // decoders may hold back frames (e.g. AAC's priming delay) until drained
+    codec_ctx.send_packet(None)?;
+    while codec_ctx.receive_frame(&mut frame)? {
+        swr.convert(Some(&frame), &mut samples)?;
+    }
+    swr.convert(None, &mut samples)?;
+
+    Ok(AudioData {
+        samples,
+        sample_rate,
+        channels,
+    })
+}
@@ -1,19 +1,84 @@
-use crate::{ffi, handle, AVError, AVPacket, AVResult, AVStreamRef, OwnedPtr};
-use anyhow::{Context, Result};
-use std::{ffi::CString, ptr::null_mut};
+use crate::{ffi, handle, AVCodecContext, AVError, AVIOContext, AVPacket, AVResult, AVStreamRef, MediaSource, OwnedPtr};
+use anyhow::{bail, Context, Result};
+use std::{
+    ffi::CString,
+    ptr::{null, null_mut},
+};
 
-#[repr(transparent)]
-pub struct AVFormatContext(OwnedPtr<ffi::AVFormatContext>);
+pub struct AVFormatContext(OwnedPtr<ffi::AVFormatContext>, Option<AVIOContext>);
 impl AVFormatContext {
     pub fn new() -> Result<Self> {
         unsafe {
             Ok(Self(
                 OwnedPtr::new(ffi::avformat_alloc_context())
                     .context("failed to allocate format context")?,
+                None,
             ))
         }
     }
 
+    /// Creates a muxer writing to `path`, with the container guessed from its extension.
+    pub fn new_output(path: &str) -> Result<Self> {
+        unsafe {
+            let path = CString::new(path)?;
+            let mut ptr = null_mut();
+            handle(ffi::avformat_alloc_output_context2(&mut ptr, null(), null(), path.as_ptr()))?;
+            let mut this = Self(OwnedPtr::new(ptr).context("failed to allocate format context")?, None);
+            if (*this.0.as_ref().oformat).flags as u32 & ffi::AVFMT_NOFILE == 0 {
+                handle(ffi::avio_open(&mut this.0.as_mut().pb, path.as_ptr(), ffi::AVIO_FLAG_WRITE as _))?;
+            }
+            Ok(this)
+        }
+    }
+
+    /// Whether encoders must put their headers in the stream parameters rather than in every keyframe.
+    pub fn needs_global_header(&self) -> bool {
+        unsafe { (*self.0.as_ref().oformat).flags as u32 & ffi::AVFMT_GLOBALHEADER != 0 }
+    }
+
+    /// Adds a stream carrying the output of `codec`.
+    pub fn new_stream(&mut self, codec: &AVCodecContext) -> Result<AVStreamRef> {
+        unsafe {
+            let stream = ffi::avformat_new_stream(self.0 .0, null());
+            if stream.is_null() {
+                bail!("failed to create stream");
+            }
+            handle(ffi::avcodec_parameters_from_context((*stream).codecpar, codec.as_ref()))?;
+            (*stream).time_base = codec.as_ref().time_base;
+            Ok(AVStreamRef(stream))
+        }
+    }
+
+    pub fn write_header(&mut self) -> AVResult<()> {
+        unsafe { handle(ffi::avformat_write_header(self.0 .0, null_mut())) }
+    }
+
+    /// Writes an encoded packet, taking its contents.
+    pub fn write_packet(&mut self, packet: &mut AVPacket) -> AVResult<()> {
+        unsafe { handle(ffi::av_interleaved_write_frame(self.0 .0, packet.0 .0)) }
+    }
+
+    pub fn write_trailer(&mut self) -> AVResult<()> {
+        unsafe { handle(ffi::av_write_trailer(self.0 .0)) }
+    }
+
+    /// Opens media read from `source` instead of a file.
+    pub fn open_source(&mut self, source: Box<dyn MediaSource>) -> Result<()> {
+        let io = AVIOContext::new(source)?;
+        unsafe {
+            self.0.as_mut().pb = io.as_ptr();
+            // kept alive until the format context is freed
+            self.1 = Some(io);
+            handle(ffi::avformat_open_input(
+                self.0.as_self_mut(),
+                null(),
+                null_mut(),
+                null_mut(),
+            ))?;
+        }
+        Ok(())
+    }
+
     pub fn open_input(&mut self, url: &str) -> AVResult<()> {
         unsafe {
             let url = CString::new(url).unwrap();
@@ -38,10 +103,28 @@ impl AVFormatContext {
         }
     }
 
+    /// Duration of the whole file in seconds, if known.
+    pub fn duration(&self) -> Option<f64> {
+        let duration = unsafe { self.0.as_ref().duration };
+        (duration != ffi::AV_NOPTS_VALUE).then(|| duration as f64 / ffi::AV_TIME_BASE as f64)
+    }
+
+    /// Seeks `stream` to the last keyframe at or before `timestamp`, in the stream's time base.
+    pub fn seek_frame(&mut self, stream: i32, timestamp: i64) -> AVResult<()> {
+        unsafe {
+            handle(ffi::av_seek_frame(
+                self.0 .0,
+                stream,
+                timestamp,
+                ffi::AVSEEK_FLAG_BACKWARD as _,
+            ))
+        }
+    }
+
     pub fn read_frame(&mut self, frame: &mut AVPacket) -> AVResult<bool> {
         unsafe {
             match handle(ffi::av_read_frame(self.0 .0, frame.0 .0)) {
-                Err(AVError { code: -541478725, .. }) => return Ok(false),
+                Err(AVError { code: ffi::AVERROR_EOF, .. }) => return Ok(false),
                 x => {
                     x?;
                     Ok(true)
@@ -56,6 +139,20 @@ unsafe impl Send for AVFormatContext {}
 impl Drop for AVFormatContext {
     fn drop(&mut self) {
         unsafe {
+            // FFmpeg frees the context and nulls it when opening the input fails
+            if self.0 .0.is_null() {
+                return;
+            }
+            let this = self.0.as_mut();
+            if !this.iformat.is_null() {
+                // leaves custom I/O contexts alone, those are freed by `self.1`
+                ffi::avformat_close_input(self.0.as_self_mut());
+                return;
+            }
+            // closes the file opened by `new_output`
+            if !this.oformat.is_null() && (*this.oformat).flags as u32 & ffi::AVFMT_NOFILE == 0 {
+                ffi::avio_closep(&mut this.pb);
+            }
             ffi::avformat_free_context(self.0 .0);
         }
     }
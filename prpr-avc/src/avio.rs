@@ -0,0 +1,93 @@
+use crate::{ffi, OwnedPtr};
+use anyhow::{bail, Result};
+use std::{
+    ffi::c_void,
+    io::{Read, Seek, SeekFrom},
+    os::raw::c_int,
+};
+
+const BUFFER_SIZE: usize = 32 * 1024;
+
+/// Anything FFmpeg can read media from.
+pub trait MediaSource: Read + Seek + Send {}
+impl<T: Read + Seek + Send> MediaSource for T {}
+
+/// An I/O context reading through Rust callbacks instead of a file path.
+pub struct AVIOContext {
+    ptr: OwnedPtr<ffi::AVIOContext>,
+    source: *mut Box<dyn MediaSource>,
+}
+
+impl AVIOContext {
+    pub fn new(source: Box<dyn MediaSource>) -> Result<Self> {
+        unsafe {
+            let mut buffer = ffi::av_malloc(BUFFER_SIZE) as *mut u8;
+            if buffer.is_null() {
+                bail!("failed to allocate buffer");
+            }
+            let source = Box::into_raw(Box::new(source));
+            let ptr = OwnedPtr::new(ffi::avio_alloc_context(
+                buffer,
+                BUFFER_SIZE as _,
+                0,
+                source as *mut c_void,
+                Some(read_packet),
+                None,
+                Some(seek),
+            ));
+            let Some(ptr) = ptr else {
+                ffi::av_freep(&mut buffer as *mut *mut u8 as *mut c_void);
+                drop(Box::from_raw(source));
+                bail!("failed to allocate I/O context");
+            };
+            Ok(Self { ptr, source })
+        }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut ffi::AVIOContext {
+        self.ptr.0
+    }
+}
+
+unsafe extern "C" fn read_packet(opaque: *mut c_void, buf: *mut u8, size: c_int) -> c_int {
+    let source = &mut *(opaque as *mut Box<dyn MediaSource>);
+    match source.read(std::slice::from_raw_parts_mut(buf, size as usize)) {
+        Ok(0) => ffi::AVERROR_EOF,
+        Ok(n) => n as c_int,
+        Err(_) => ffi::AVERROR_EIO,
+    }
+}
+
+unsafe extern "C" fn seek(opaque: *mut c_void, offset: i64, whence: c_int) -> i64 {
+    let source = &mut *(opaque as *mut Box<dyn MediaSource>);
+    let whence = whence as u32 & !ffi::AVSEEK_FORCE;
+    let res = if whence == ffi::AVSEEK_SIZE {
+        (|| -> std::io::Result<u64> {
+            let pos = source.stream_position()?;
+            let len = source.seek(SeekFrom::End(0))?;
+            source.seek(SeekFrom::Start(pos))?;
+            Ok(len)
+        })()
+    } else {
+        source.seek(match whence {
+            0 => SeekFrom::Start(offset as u64),
+            1 => SeekFrom::Current(offset),
+            2 => SeekFrom::End(offset),
+            _ => return ffi::AVERROR_EIO as i64,
+        })
+    };
+    res.map_or(ffi::AVERROR_EIO as i64, |it| it as i64)
+}
+
+unsafe impl Send for AVIOContext {}
+
+impl Drop for AVIOContext {
+    fn drop(&mut self) {
+        unsafe {
+            // FFmpeg may have replaced the buffer we gave it
+            ffi::av_freep(&mut self.ptr.as_mut().buffer as *mut *mut u8 as *mut c_void);
+            ffi::avio_context_free(self.ptr.as_self_mut());
+            drop(Box::from_raw(self.source));
+        }
+    }
+}
@@ -1,9 +1,10 @@
 use crate::{
-    ffi, handle, AVError, AVFrame, AVPacket, AVPixelFormat, AVResult, OwnedPtr, StreamFormat,
+    ffi, handle, AVError, AVFrame, AVPacket, AVPixelFormat, AVRational, AVResult, OwnedPtr,
+    StreamFormat,
 };
 use anyhow::{bail, Context, Result};
 use std::{
-    ptr::null_mut,
+    ptr::{null, null_mut},
     sync::{
         atomic::{AtomicI32, Ordering},
         Mutex,
@@ -39,6 +40,17 @@ impl AVCodecRef {
             }
         }
     }
+
+    pub fn find_encoder(id: ffi::AVCodecID) -> Result<Self> {
+        unsafe {
+            let ptr = ffi::avcodec_find_encoder(id);
+            if ptr.is_null() {
+                bail!("cannot find encoder with id {id}");
+            } else {
+                Ok(Self(ptr))
+            }
+        }
+    }
 }
 
 static EXPECTED_PIX_FMT_EDIT: Mutex<()> = Mutex::new(());
@@ -84,6 +96,34 @@ impl AVCodecContext {
         }
     }
 
+    /// Opens an encoder after `setup` filled in its parameters.
+    pub(crate) fn new_encoder(codec: AVCodecRef, setup: impl FnOnce(&mut ffi::AVCodecContext)) -> Result<Self> {
+        unsafe {
+            let mut ptr = OwnedPtr::new(ffi::avcodec_alloc_context3(codec.0))
+                .context("failed to create context")?;
+            setup(ptr.as_mut());
+            handle(ffi::avcodec_open2(ptr.0, codec.0, null_mut()))?;
+            Ok(Self(ptr))
+        }
+    }
+
+    pub(crate) fn as_ref(&self) -> &ffi::AVCodecContext {
+        unsafe { self.0.as_ref() }
+    }
+
+    pub fn time_base(&self) -> AVRational {
+        self.as_ref().time_base.into()
+    }
+
+    pub fn sample_rate(&self) -> i32 {
+        self.as_ref().sample_rate
+    }
+
+    /// Number of samples each audio frame must hold, `None` if any size is accepted.
+    pub fn frame_size(&self) -> Option<i32> {
+        Some(self.as_ref().frame_size).filter(|it| *it > 0)
+    }
+
     pub fn stream_format(&self) -> StreamFormat {
         unsafe {
             let this = self.0.as_ref();
@@ -95,14 +135,39 @@ impl AVCodecContext {
         }
     }
 
-    pub fn send_packet(&mut self, packet: &AVPacket) -> AVResult<()> {
-        unsafe { handle(ffi::avcodec_send_packet(self.0 .0, packet.0 .0)) }
+    /// Sends a packet to the decoder, `None` to start draining it.
+    pub fn send_packet(&mut self, packet: Option<&AVPacket>) -> AVResult<()> {
+        unsafe { handle(ffi::avcodec_send_packet(self.0 .0, packet.map_or(null(), |it| it.0 .0))) }
+    }
+
+    /// Drops every buffered frame, needed after seeking.
+    pub fn flush(&mut self) {
+        unsafe { ffi::avcodec_flush_buffers(self.0 .0) }
+    }
+
+    /// Sends a frame to the encoder, `None` to start flushing it.
+    pub fn send_frame(&mut self, frame: Option<&AVFrame>) -> AVResult<()> {
+        unsafe { handle(ffi::avcodec_send_frame(self.0 .0, frame.map_or(null(), |it| it.0 .0))) }
+    }
+
+    /// Returns `false` if the encoder needs more input, or has been fully flushed.
+    pub fn receive_packet(&mut self, packet: &mut AVPacket) -> AVResult<bool> {
+        unsafe {
+            match handle(ffi::avcodec_receive_packet(self.0 .0, packet.0 .0)) {
+                Err(AVError { code, .. }) if code == -EAGAIN || code == ffi::AVERROR_EOF => return Ok(false),
+                x => {
+                    x?;
+                    Ok(true)
+                }
+            }
+        }
     }
 
+    /// Returns `false` if the decoder needs more input, or has been fully drained.
     pub fn receive_frame(&mut self, frame: &mut AVFrame) -> AVResult<bool> {
         unsafe {
             match handle(ffi::avcodec_receive_frame(self.0 .0, frame.0 .0)) {
-                Err(AVError { code, .. }) if code == -EAGAIN => return Ok(false),
+                Err(AVError { code, .. }) if code == -EAGAIN || code == ffi::AVERROR_EOF => return Ok(false),
                 x => {
                     x?;
                     Ok(true)
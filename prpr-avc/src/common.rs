@@ -40,7 +40,7 @@ impl std::error::Error for AVError {}
 
 pub type AVResult<T> = Result<T, AVError>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct AVRational {
     pub num: i32,
     pub den: i32,
@@ -56,6 +56,15 @@ impl AVRational {
     }
 }
 
+impl From<AVRational> for ffi::AVRational {
+    fn from(value: AVRational) -> Self {
+        Self {
+            num: value.num as _,
+            den: value.den as _,
+        }
+    }
+}
+
 impl From<ffi::AVRational> for AVRational {
     fn from(value: ffi::AVRational) -> Self {
         Self {
@@ -77,6 +86,7 @@ pub struct AVPixelFormat(pub ffi::AVPixelFormat);
 impl AVPixelFormat {
     pub const YUV420P: AVPixelFormat = AVPixelFormat(0);
     pub const RGB24: AVPixelFormat = AVPixelFormat(2);
+    pub const RGBA: AVPixelFormat = AVPixelFormat(26);
 }
 
 #[derive(Debug, Clone)]
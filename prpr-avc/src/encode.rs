@@ -0,0 +1,236 @@
+use crate::{ffi, AVCodecContext, AVCodecRef, AVFormatContext, AVFrame, AVPacket, AVPixelFormat, AVRational, AVStreamRef, StreamFormat, SwsContext};
+use anyhow::{ensure, Context, Result};
+
+#[derive(Debug, Clone, Copy)]
+pub enum VideoCodec {
+    H264,
+    /// Lossless, MKV only.
+    Ffv1,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AudioCodec {
+    Aac,
+    /// 16-bit PCM, MKV only.
+    Pcm,
+}
+
+#[derive(Debug, Clone)]
+pub struct VideoParams {
+    pub codec: VideoCodec,
+    pub width: i32,
+    pub height: i32,
+    pub frame_rate: i32,
+    pub bit_rate: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AudioParams {
+    pub codec: AudioCodec,
+    pub sample_rate: i32,
+    pub channels: i32,
+    pub bit_rate: Option<i64>,
+}
+
+struct OutputStream {
+    codec_ctx: AVCodecContext,
+    stream: AVStreamRef,
+    frame: AVFrame,
+    /// Timestamp of the next frame, in the codec's time base.
+    pts: i64,
+}
+
+impl OutputStream {
+    fn new(format_ctx: &mut AVFormatContext, codec_ctx: AVCodecContext, frame: AVFrame) -> Result<Self> {
+        let stream = format_ctx.new_stream(&codec_ctx)?;
+        Ok(Self {
+            codec_ctx,
+            stream,
+            frame,
+            pts: 0,
+        })
+    }
+
+    /// Encodes the current frame, or flushes the encoder if `flush` is set.
+    fn send(&mut self, format_ctx: &mut AVFormatContext, packet: &mut AVPacket, flush: bool) -> Result<()> {
+        self.codec_ctx.send_frame((!flush).then_some(&self.frame))?;
+        while self.codec_ctx.receive_packet(packet)? {
+            packet.rescale_ts(self.codec_ctx.time_base(), self.stream.time_base());
+            packet.set_stream_index(self.stream.index());
+            format_ctx.write_packet(packet)?;
+        }
+        Ok(())
+    }
+}
+
+/// Encodes RGBA frames and interleaved `f32` samples into a video file.
+///
+/// The container is picked from the output path's extension (`.mp4`, `.mkv`, ...).
+pub struct Encoder {
+    format_ctx: AVFormatContext,
+    packet: AVPacket,
+
+    video: Option<(OutputStream, SwsContext, AVFrame, StreamFormat)>,
+    audio: Option<(OutputStream, AudioCodec, Vec<f32>)>,
+    channels: usize,
+}
+
+impl Encoder {
+    pub fn new(path: &str, video: Option<VideoParams>, audio: Option<AudioParams>) -> Result<Self> {
+        let mut format_ctx = AVFormatContext::new_output(path)?;
+        let global_header = format_ctx.needs_global_header();
+        let set_flags = |ctx: &mut ffi::AVCodecContext, bit_rate: Option<i64>| {
+            if let Some(bit_rate) = bit_rate {
+                ctx.bit_rate = bit_rate;
+            }
+            if global_header {
+                ctx.flags |= ffi::AV_CODEC_FLAG_GLOBAL_HEADER as i32;
+            }
+        };
+
+        let video = video
+            .map(|params| -> Result<_> {
+                let codec = AVCodecRef::find_encoder(match params.codec {
+                    VideoCodec::H264 => ffi::AV_CODEC_ID_H264,
+                    VideoCodec::Ffv1 => ffi::AV_CODEC_ID_FFV1,
+                })?;
+                let codec_ctx = AVCodecContext::new_encoder(codec, |ctx| {
+                    ctx.codec_type = ffi::AVMEDIA_TYPE_VIDEO;
+                    ctx.width = params.width;
+                    ctx.height = params.height;
+                    ctx.pix_fmt = AVPixelFormat::YUV420P.0;
+                    ctx.time_base = AVRational { num: 1, den: params.frame_rate }.into();
+                    ctx.framerate = AVRational { num: params.frame_rate, den: 1 }.into();
+                    ctx.gop_size = params.frame_rate;
+                    set_flags(ctx, params.bit_rate);
+                })?;
+                let in_format = StreamFormat {
+                    width: params.width,
+                    height: params.height,
+                    pix_fmt: AVPixelFormat::RGBA,
+                };
+                let out_format = codec_ctx.stream_format();
+                let sws = SwsContext::new(in_format.clone(), out_format.clone())?;
+                let mut in_frame = AVFrame::new()?;
+                in_frame.get_buffer(&in_format).context("failed to get buffer")?;
+                let mut out_frame = AVFrame::new()?;
+                out_frame.get_buffer(&out_format).context("failed to get buffer")?;
+                Ok((OutputStream::new(&mut format_ctx, codec_ctx, out_frame)?, sws, in_frame, in_format))
+            })
+            .transpose()?;
+
+        let channels = audio.as_ref().map_or(0, |it| it.channels as usize);
+        let audio = audio
+            .map(|params| -> Result<_> {
+                let codec = AVCodecRef::find_encoder(match params.codec {
+                    AudioCodec::Aac => ffi::AV_CODEC_ID_AAC,
+                    AudioCodec::Pcm => ffi::AV_CODEC_ID_PCM_S16LE,
+                })?;
+                let codec_ctx = AVCodecContext::new_encoder(codec, |ctx| unsafe {
+                    ctx.codec_type = ffi::AVMEDIA_TYPE_AUDIO;
+                    ctx.sample_fmt = match params.codec {
+                        AudioCodec::Aac => ffi::AV_SAMPLE_FMT_FLTP,
+                        AudioCodec::Pcm => ffi::AV_SAMPLE_FMT_S16,
+                    };
+                    ctx.sample_rate = params.sample_rate;
+                    ffi::av_channel_layout_default(&mut ctx.ch_layout, params.channels);
+                    ctx.time_base = AVRational { num: 1, den: params.sample_rate }.into();
+                    set_flags(ctx, params.bit_rate.or(matches!(params.codec, AudioCodec::Aac).then_some(192_000)));
+                })?;
+                let mut frame = AVFrame::new()?;
+                let ctx = codec_ctx.as_ref();
+                frame
+                    .get_audio_buffer(codec_ctx.frame_size().unwrap_or(1024), ctx.sample_fmt, ctx.ch_layout)
+                    .context("failed to get buffer")?;
+                Ok((OutputStream::new(&mut format_ctx, codec_ctx, frame)?, params.codec, Vec::new()))
+            })
+            .transpose()?;
+
+        format_ctx.write_header()?;
+
+        Ok(Self {
+            format_ctx,
+            packet: AVPacket::new()?,
+
+            video,
+            audio,
+            channels,
+        })
+    }
+
+    /// Encodes the next frame, given as tightly packed RGBA rows.
+    pub fn write_video_frame(&mut self, rgba: &[u8]) -> Result<()> {
+        let (stream, sws, in_frame, in_format) = self.video.as_mut().context("no video stream")?;
+        let row = in_format.width as usize * 4;
+        let line_size = in_frame.line_size() as usize;
+        ensure!(rgba.len() == row * in_format.height as usize, "frame size mismatch");
+        for (src, dst) in rgba.chunks_exact(row).zip(in_frame.data_mut(0).chunks_mut(line_size)) {
+            dst[..row].copy_from_slice(src);
+        }
+        stream.frame.make_writable()?;
+        sws.scale(in_frame, &mut stream.frame);
+        stream.frame.set_pts(stream.pts);
+        stream.pts += 1;
+        stream.send(&mut self.format_ctx, &mut self.packet, false)
+    }
+
+    /// Queues interleaved samples, encoding them once a whole frame is filled.
+    pub fn write_audio(&mut self, samples: &[f32]) -> Result<()> {
+        let (stream, _, pending) = self.audio.as_mut().context("no audio stream")?;
+        pending.extend_from_slice(samples);
+        let frame_samples = stream.codec_ctx.frame_size().unwrap_or(1024) as usize * self.channels;
+        let frames = pending.len() / frame_samples;
+        for _ in 0..frames {
+            self.flush_audio(frame_samples)?;
+        }
+        Ok(())
+    }
+
+    fn flush_audio(&mut self, len: usize) -> Result<()> {
+        let channels = self.channels;
+        let (stream, codec, pending) = self.audio.as_mut().unwrap();
+        let nb_samples = len / channels;
+        stream.frame.make_writable()?;
+        stream.frame.set_nb_samples(nb_samples as i32);
+        match codec {
+            AudioCodec::Aac => {
+                for ch in 0..channels {
+                    let plane = stream.frame.samples_mut(ch);
+                    for (i, sample) in pending[..len].iter().skip(ch).step_by(channels).enumerate() {
+                        plane[i * 4..i * 4 + 4].copy_from_slice(&sample.to_ne_bytes());
+                    }
+                }
+            }
+            AudioCodec::Pcm => {
+                let plane = stream.frame.samples_mut(0);
+                for (i, sample) in pending[..len].iter().enumerate() {
+                    let sample = (sample.clamp(-1., 1.) * i16::MAX as f32) as i16;
+                    plane[i * 2..i * 2 + 2].copy_from_slice(&sample.to_ne_bytes());
+                }
+            }
+        }
+        pending.drain(..len);
+        stream.frame.set_pts(stream.pts);
+        stream.pts += nb_samples as i64;
+        stream.send(&mut self.format_ctx, &mut self.packet, false)
+    }
+
+    /// Flushes the encoders and finalizes the file.
+    pub fn finish(mut self) -> Result<()> {
+        if let Some((_, _, pending)) = &self.audio {
+            // the last frame may be shorter
+            let len = pending.len() - pending.len() % self.channels;
+            if len != 0 {
+                self.flush_audio(len)?;
+            }
+        }
+        if let Some((stream, ..)) = &mut self.video {
+            stream.send(&mut self.format_ctx, &mut self.packet, true)?;
+        }
+        if let Some((stream, ..)) = &mut self.audio {
+            stream.send(&mut self.format_ctx, &mut self.packet, true)?;
+        }
+        self.format_ctx.write_trailer()?;
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@ use std::ffi::c_void;
 extern "C" {
     pub fn avformat_alloc_context() -> *mut AVFormatContext;
     pub fn avformat_free_context(s: *mut AVFormatContext);
+    pub fn avformat_close_input(s: *mut *mut AVFormatContext);
     pub fn avformat_open_input(
         ps: *mut *mut AVFormatContext,
         url: *const ::std::os::raw::c_char,
@@ -12,14 +13,46 @@ extern "C" {
     ) -> ::std::os::raw::c_int;
     pub fn avformat_find_stream_info(ic: *mut AVFormatContext, options: *mut *mut c_void) -> ::std::os::raw::c_int;
     pub fn av_read_frame(s: *mut AVFormatContext, pkt: *mut AVPacket) -> ::std::os::raw::c_int;
+    pub fn avio_alloc_context(
+        buffer: *mut u8,
+        buffer_size: ::std::os::raw::c_int,
+        write_flag: ::std::os::raw::c_int,
+        opaque: *mut c_void,
+        read_packet: Option<unsafe extern "C" fn(*mut c_void, *mut u8, ::std::os::raw::c_int) -> ::std::os::raw::c_int>,
+        write_packet: Option<unsafe extern "C" fn(*mut c_void, *mut u8, ::std::os::raw::c_int) -> ::std::os::raw::c_int>,
+        seek: Option<unsafe extern "C" fn(*mut c_void, i64, ::std::os::raw::c_int) -> i64>,
+    ) -> *mut AVIOContext;
+    pub fn avio_context_free(s: *mut *mut AVIOContext);
+    pub fn av_seek_frame(
+        s: *mut AVFormatContext,
+        stream_index: ::std::os::raw::c_int,
+        timestamp: i64,
+        flags: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+    pub fn avformat_alloc_output_context2(
+        ctx: *mut *mut AVFormatContext,
+        oformat: *const AVOutputFormat,
+        format_name: *const ::std::os::raw::c_char,
+        filename: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+    pub fn avformat_new_stream(s: *mut AVFormatContext, c: *const AVCodec) -> *mut AVStream;
+    pub fn avformat_write_header(s: *mut AVFormatContext, options: *mut *mut c_void) -> ::std::os::raw::c_int;
+    pub fn av_interleaved_write_frame(s: *mut AVFormatContext, pkt: *mut AVPacket) -> ::std::os::raw::c_int;
+    pub fn av_write_trailer(s: *mut AVFormatContext) -> ::std::os::raw::c_int;
+    pub fn avio_open(s: *mut *mut AVIOContext, url: *const ::std::os::raw::c_char, flags: ::std::os::raw::c_int) -> ::std::os::raw::c_int;
+    pub fn avio_closep(s: *mut *mut AVIOContext) -> ::std::os::raw::c_int;
 }
 
 #[link(name = "avutil", kind = "static")]
 extern "C" {
     pub fn av_strerror(errnum: ::std::os::raw::c_int, errbuf: *mut ::std::os::raw::c_char, errbuf_size: usize) -> ::std::os::raw::c_int;
+    pub fn av_malloc(size: usize) -> *mut c_void;
+    pub fn av_freep(ptr: *mut c_void);
     pub fn av_frame_alloc() -> *mut AVFrame;
     pub fn av_frame_free(frame: *mut *mut AVFrame);
     pub fn av_frame_get_buffer(frame: *mut AVFrame, align: ::std::os::raw::c_int) -> ::std::os::raw::c_int;
+    pub fn av_frame_make_writable(frame: *mut AVFrame) -> ::std::os::raw::c_int;
+    pub fn av_channel_layout_default(ch_layout: *mut AVChannelLayout, nb_channels: ::std::os::raw::c_int);
 }
 
 #[link(name = "avcodec", kind = "static")]
@@ -33,6 +66,14 @@ extern "C" {
     pub fn avcodec_send_packet(avctx: *mut AVCodecContext, avpkt: *const AVPacket) -> ::std::os::raw::c_int;
     pub fn avcodec_receive_frame(avctx: *mut AVCodecContext, frame: *mut AVFrame) -> ::std::os::raw::c_int;
     pub fn avcodec_default_get_format(s: *mut AVCodecContext, fmt: *const AVPixelFormat) -> AVPixelFormat;
+    pub fn avcodec_flush_buffers(avctx: *mut AVCodecContext);
+    pub fn avcodec_find_encoder(id: AVCodecID) -> *mut AVCodec;
+    pub fn avcodec_parameters_from_context(par: *mut AVCodecParameters, codec: *const AVCodecContext) -> ::std::os::raw::c_int;
+    pub fn avcodec_send_frame(avctx: *mut AVCodecContext, frame: *const AVFrame) -> ::std::os::raw::c_int;
+    pub fn avcodec_receive_packet(avctx: *mut AVCodecContext, avpkt: *mut AVPacket) -> ::std::os::raw::c_int;
+    pub fn av_packet_free(pkt: *mut *mut AVPacket);
+    pub fn av_packet_unref(pkt: *mut AVPacket);
+    pub fn av_packet_rescale_ts(pkt: *mut AVPacket, tb_src: AVRational, tb_dst: AVRational);
 }
 
 #[link(name = "swscale", kind = "static")]
@@ -60,6 +101,31 @@ extern "C" {
     ) -> ::std::os::raw::c_int;
 }
 
+#[link(name = "swresample", kind = "static")]
+extern "C" {
+    pub fn swr_alloc_set_opts2(
+        ps: *mut *mut SwrContext,
+        out_ch_layout: *const AVChannelLayout,
+        out_sample_fmt: AVSampleFormat,
+        out_sample_rate: ::std::os::raw::c_int,
+        in_ch_layout: *const AVChannelLayout,
+        in_sample_fmt: AVSampleFormat,
+        in_sample_rate: ::std::os::raw::c_int,
+        log_offset: ::std::os::raw::c_int,
+        log_ctx: *mut c_void,
+    ) -> ::std::os::raw::c_int;
+    pub fn swr_init(s: *mut SwrContext) -> ::std::os::raw::c_int;
+    pub fn swr_free(s: *mut *mut SwrContext);
+    pub fn swr_get_out_samples(s: *mut SwrContext, in_samples: ::std::os::raw::c_int) -> ::std::os::raw::c_int;
+    pub fn swr_convert(
+        s: *mut SwrContext,
+        out: *mut *mut u8,
+        out_count: ::std::os::raw::c_int,
+        in_: *const *const u8,
+        in_count: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+
 pub type AVAudioServiceType = ::std::os::raw::c_uint;
 pub type AVChannelOrder = ::std::os::raw::c_uint;
 pub type AVChromaLocation = ::std::os::raw::c_uint;
@@ -78,9 +144,55 @@ pub type AVSampleFormat = ::std::os::raw::c_int;
 
 pub type AVCodec = c_void;
 pub type SwsContext = c_void;
+pub type SwrContext = c_void;
 
 pub const AV_ERROR_MAX_STRING_SIZE: u32 = 64;
+pub const AV_NOPTS_VALUE: i64 = i64::MIN;
+pub const AV_TIME_BASE: i64 = 1000000;
+pub const AVSEEK_FLAG_BACKWARD: u32 = 1;
+pub const AVSEEK_SIZE: u32 = 0x10000;
+pub const AVSEEK_FORCE: u32 = 0x20000;
+pub const AVERROR_EOF: i32 = -541478725;
+pub const AVERROR_EIO: i32 = -5;
 pub const SWS_BICUBIC: u32 = 4;
+pub const AVIO_FLAG_WRITE: u32 = 2;
+pub const AVFMT_NOFILE: u32 = 0x0001;
+pub const AVFMT_GLOBALHEADER: u32 = 0x0040;
+pub const AV_CODEC_FLAG_GLOBAL_HEADER: u32 = 1 << 22;
+
+pub const AVMEDIA_TYPE_VIDEO: AVMediaType = 0;
+pub const AVMEDIA_TYPE_AUDIO: AVMediaType = 1;
+
+pub const AV_CHANNEL_ORDER_UNSPEC: AVChannelOrder = 0;
+
+pub const AV_CODEC_ID_H264: AVCodecID = 27;
+pub const AV_CODEC_ID_FFV1: AVCodecID = 33;
+pub const AV_CODEC_ID_PCM_S16LE: AVCodecID = 0x10000;
+pub const AV_CODEC_ID_AAC: AVCodecID = 0x15002;
+
+pub const AV_SAMPLE_FMT_S16: AVSampleFormat = 1;
+pub const AV_SAMPLE_FMT_FLT: AVSampleFormat = 3;
+pub const AV_SAMPLE_FMT_FLTP: AVSampleFormat = 8;
+
+/// Only the leading fields are declared, it's always used through pointers.
+#[repr(C)]
+pub struct AVIOContext {
+    pub av_class: *const c_void,
+    pub buffer: *mut u8,
+}
+
+/// Only the leading fields are declared, it's always used through pointers.
+#[repr(C)]
+pub struct AVOutputFormat {
+    pub name: *const ::std::os::raw::c_char,
+    pub long_name: *const ::std::os::raw::c_char,
+    pub mime_type: *const ::std::os::raw::c_char,
+    pub extensions: *const ::std::os::raw::c_char,
+    pub audio_codec: AVCodecID,
+    pub video_codec: AVCodecID,
+    pub subtitle_codec: AVCodecID,
+    pub flags: ::std::os::raw::c_int,
+}
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -134,9 +246,9 @@ pub struct AVPacket {
 pub struct AVFormatContext {
     pub av_class: *const c_void,
     pub iformat: *const c_void,
-    pub oformat: *const c_void,
+    pub oformat: *const AVOutputFormat,
     pub priv_data: *mut ::std::os::raw::c_void,
-    pub pb: *mut c_void,
+    pub pb: *mut AVIOContext,
     pub ctx_flags: ::std::os::raw::c_int,
     pub nb_streams: ::std::os::raw::c_uint,
     pub streams: *mut *mut AVStream,
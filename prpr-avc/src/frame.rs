@@ -18,6 +18,45 @@ impl AVFrame {
         }
     }
 
+    /// Allocates planes for `nb_samples` audio samples.
+    pub(crate) fn get_audio_buffer(&mut self, nb_samples: i32, sample_fmt: ffi::AVSampleFormat, ch_layout: ffi::AVChannelLayout) -> AVResult<()> {
+        unsafe {
+            let this = self.0.as_mut();
+            this.nb_samples = nb_samples;
+            this.format = sample_fmt;
+            this.ch_layout = ch_layout;
+            handle(ffi::av_frame_get_buffer(self.0 .0, 0))
+        }
+    }
+
+    /// Makes sure the buffers are not shared with an encoder before writing to them.
+    pub fn make_writable(&mut self) -> AVResult<()> {
+        unsafe { handle(ffi::av_frame_make_writable(self.0 .0)) }
+    }
+
+    pub fn set_pts(&mut self, pts: i64) {
+        unsafe { self.0.as_mut().pts = pts }
+    }
+
+    pub fn set_nb_samples(&mut self, nb_samples: i32) {
+        unsafe { self.0.as_mut().nb_samples = nb_samples }
+    }
+
+    pub fn data_mut(&mut self, index: usize) -> &mut [u8] {
+        unsafe {
+            let this = self.0.as_mut();
+            std::slice::from_raw_parts_mut(this.data[index], this.linesize[index] as usize * this.height as usize)
+        }
+    }
+
+    /// Writable samples of an audio plane.
+    pub fn samples_mut(&mut self, index: usize) -> &mut [u8] {
+        unsafe {
+            let this = self.0.as_mut();
+            std::slice::from_raw_parts_mut(this.data[index], this.linesize[0] as usize)
+        }
+    }
+
     // TODO: is this correct?
     pub fn data(&self, index: usize) -> &[u8] {
         unsafe {
@@ -31,6 +70,12 @@ impl AVFrame {
         &data[..data.len() / 2]
     }
 
+    /// Presentation timestamp in the stream's time base, guessed by the decoder if missing.
+    pub fn timestamp(&self) -> Option<i64> {
+        let ts = unsafe { self.0.as_ref().best_effort_timestamp };
+        (ts != ffi::AV_NOPTS_VALUE).then_some(ts)
+    }
+
     pub fn line_size(&self) -> i32 {
         unsafe { self.0.as_ref().linesize[0] }
     }
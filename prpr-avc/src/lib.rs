@@ -6,9 +6,18 @@ pub use common::*;
 mod avformat;
 pub use avformat::*;
 
+mod avio;
+pub use avio::*;
+
+mod audio;
+pub use audio::*;
+
 mod codec;
 pub use codec::*;
 
+mod encode;
+pub use encode::*;
+
 mod frame;
 pub use frame::*;
 
@@ -18,6 +27,9 @@ pub use packet::*;
 mod stream;
 pub use stream::*;
 
+mod swr;
+pub use swr::*;
+
 mod sws;
 pub use sws::*;
 
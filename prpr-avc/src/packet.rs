@@ -1,4 +1,4 @@
-use crate::{ffi, OwnedPtr};
+use crate::{ffi, AVRational, OwnedPtr};
 use anyhow::{Context, Result};
 
 #[repr(transparent)]
@@ -15,6 +15,28 @@ impl AVPacket {
     pub fn stream_index(&self) -> i32 {
         unsafe { self.0.as_ref().stream_index }
     }
+
+    pub fn set_stream_index(&mut self, index: i32) {
+        unsafe { self.0.as_mut().stream_index = index }
+    }
+
+    /// Releases the data of the packet so it can be reused.
+    pub fn unref(&mut self) {
+        unsafe { ffi::av_packet_unref(self.0 .0) }
+    }
+
+    /// Converts the timestamps from the time base `src` to `dst`.
+    pub fn rescale_ts(&mut self, src: AVRational, dst: AVRational) {
+        unsafe { ffi::av_packet_rescale_ts(self.0 .0, src.into(), dst.into()) }
+    }
+}
+
+impl Drop for AVPacket {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::av_packet_free(self.0.as_self_mut());
+        }
+    }
 }
 
 unsafe impl Send for AVPacket {}
@@ -3,7 +3,7 @@ use anyhow::Result;
 
 #[repr(transparent)]
 #[derive(Clone, Copy)]
-pub struct AVStreamRef(*const ffi::AVStream);
+pub struct AVStreamRef(pub(crate) *const ffi::AVStream);
 impl AVStreamRef {
     pub fn index(&self) -> i32 {
         unsafe { (*self.0).index as i32 }
@@ -13,8 +13,32 @@ impl AVStreamRef {
         unsafe { (*self.0).r_frame_rate.into() }
     }
 
+    pub fn time_base(&self) -> AVRational {
+        unsafe { (*self.0).time_base.into() }
+    }
+
+    /// Timestamp of the first frame in the stream's time base, zero if unknown.
+    pub fn start_time(&self) -> i64 {
+        let start = unsafe { (*self.0).start_time };
+        if start == ffi::AV_NOPTS_VALUE {
+            0
+        } else {
+            start
+        }
+    }
+
+    /// Duration in seconds, if known.
+    pub fn duration(&self) -> Option<f64> {
+        let duration = unsafe { (*self.0).duration };
+        (duration != ffi::AV_NOPTS_VALUE).then(|| duration as f64 * self.time_base().to_f64())
+    }
+
     pub fn is_video(&self) -> bool {
-        unsafe { (*(*self.0).codecpar).codec_type == 0 }
+        unsafe { (*(*self.0).codecpar).codec_type == ffi::AVMEDIA_TYPE_VIDEO }
+    }
+
+    pub fn is_audio(&self) -> bool {
+        unsafe { (*(*self.0).codecpar).codec_type == ffi::AVMEDIA_TYPE_AUDIO }
     }
 
     pub fn codec_params(&self) -> AVCodecParamsRef {
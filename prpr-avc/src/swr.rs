@@ -0,0 +1,76 @@
+use crate::{ffi, handle, AVCodecContext, AVFrame, OwnedPtr};
+use anyhow::{bail, Result};
+use std::ptr::{null, null_mut};
+
+/// Resamples decoded audio into interleaved `f32` samples.
+pub struct SwrContext {
+    ptr: OwnedPtr<ffi::SwrContext>,
+    channels: usize,
+}
+
+impl SwrContext {
+    /// Converts the output of the decoder `codec_ctx` to `channels` channels at `sample_rate`.
+    pub fn new(codec_ctx: &AVCodecContext, channels: i32, sample_rate: i32) -> Result<Self> {
+        unsafe {
+            let ctx = codec_ctx.as_ref();
+            let mut in_layout = ctx.ch_layout;
+            if in_layout.order == ffi::AV_CHANNEL_ORDER_UNSPEC {
+                ffi::av_channel_layout_default(&mut in_layout, in_layout.nb_channels);
+            }
+            let mut out_layout: ffi::AVChannelLayout = std::mem::zeroed();
+            ffi::av_channel_layout_default(&mut out_layout, channels);
+            let mut ptr = null_mut();
+            handle(ffi::swr_alloc_set_opts2(
+                &mut ptr,
+                &out_layout,
+                ffi::AV_SAMPLE_FMT_FLT,
+                sample_rate,
+                &in_layout,
+                ctx.sample_fmt,
+                ctx.sample_rate,
+                0,
+                null_mut(),
+            ))?;
+            let Some(ptr) = OwnedPtr::new(ptr) else {
+                bail!("failed to create swr context");
+            };
+            let this = Self {
+                ptr,
+                channels: channels as usize,
+            };
+            handle(ffi::swr_init(this.ptr.0))?;
+            Ok(this)
+        }
+    }
+
+    /// Appends the samples of `frame` to `out`, or the buffered ones if `frame` is `None`.
+    pub fn convert(&mut self, frame: Option<&AVFrame>, out: &mut Vec<f32>) -> Result<()> {
+        unsafe {
+            let (input, in_count) = frame.map_or((null(), 0), |it| {
+                let it = it.0.as_ref();
+                (it.extended_data as *const *const u8, it.nb_samples)
+            });
+            let out_count = ffi::swr_get_out_samples(self.ptr.0, in_count);
+            if out_count < 0 {
+                bail!("failed to estimate output size");
+            }
+            let len = out.len();
+            out.reserve(out_count as usize * self.channels);
+            let mut output = out.as_mut_ptr().add(len) as *mut u8;
+            let count = ffi::swr_convert(self.ptr.0, &mut output, out_count, input, in_count);
+            handle(count.min(0))?;
+            out.set_len(len + count as usize * self.channels);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SwrContext {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::swr_free(self.ptr.as_self_mut());
+        }
+    }
+}
+
+unsafe impl Send for SwrContext {}
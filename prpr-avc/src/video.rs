@@ -1,6 +1,7 @@
-use crate::{AVCodecContext, AVFormatContext, AVFrame, AVPacket, AVPixelFormat, AVRational, AVStreamRef, StreamFormat, SwsContext};
+use crate::{AVCodecContext, AVFormatContext, AVFrame, AVPacket, AVPixelFormat, AVRational, AVStreamRef, MediaSource, StreamFormat, SwsContext};
 use anyhow::{Context, Result};
 use std::{
+    io::Cursor,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, Condvar, Mutex,
@@ -9,14 +10,22 @@ use std::{
 };
 use tracing::error;
 
+struct State {
+    /// `Some(None)` once the stream ended.
+    frame: Option<Option<&'static AVFrame>>,
+    /// Pending seek target in seconds.
+    seek: Option<f64>,
+}
+
 pub struct Video {
     stream_format: StreamFormat,
     video_stream: AVStreamRef,
+    duration: Option<f64>,
 
     dropped: Arc<AtomicBool>,
     ended: AtomicBool,
 
-    mutex: Arc<(Mutex<Option<Option<&'static AVFrame>>>, Condvar)>,
+    mutex: Arc<(Mutex<State>, Condvar)>,
     decode_thread: Option<JoinHandle<()>>,
 }
 
@@ -24,9 +33,25 @@ impl Video {
     pub fn open(file: impl AsRef<str>, pix_fmt: AVPixelFormat) -> Result<Self> {
         let mut format_ctx = AVFormatContext::new()?;
         format_ctx.open_input(file.as_ref())?;
+        Self::with_format(format_ctx, pix_fmt)
+    }
+
+    /// Decodes the video read from `source`, without touching the file system.
+    pub fn from_source(source: Box<dyn MediaSource>, pix_fmt: AVPixelFormat) -> Result<Self> {
+        let mut format_ctx = AVFormatContext::new()?;
+        format_ctx.open_source(source)?;
+        Self::with_format(format_ctx, pix_fmt)
+    }
+
+    pub fn from_memory(data: Vec<u8>, pix_fmt: AVPixelFormat) -> Result<Self> {
+        Self::from_source(Box::new(Cursor::new(data)), pix_fmt)
+    }
+
+    fn with_format(mut format_ctx: AVFormatContext, pix_fmt: AVPixelFormat) -> Result<Self> {
         format_ctx.find_stream_info()?;
 
         let video_stream = format_ctx.streams().into_iter().find(|it| it.is_video()).context("no video")?;
+        let duration = video_stream.duration().or_else(|| format_ctx.duration());
 
         let decoder = video_stream.find_decoder()?;
         let mut codec_ctx = AVCodecContext::new(decoder, video_stream.codec_params(), Some(pix_fmt))?;
@@ -36,7 +61,7 @@ impl Video {
             ..codec_ctx.stream_format()
         };
 
-        let mutex = Arc::new((Mutex::new(None), Condvar::new()));
+        let mutex = Arc::new((Mutex::new(State { frame: None, seek: None }), Condvar::new()));
 
         let stream_format = codec_ctx.stream_format();
 
@@ -50,41 +75,79 @@ impl Video {
         let decode_thread = std::thread::spawn({
             let mut packet = AVPacket::new()?;
             let video_index = video_stream.index();
+            let time_base = video_stream.time_base().to_f64();
+            let start_time = video_stream.start_time();
             let mutex = Arc::clone(&mutex);
             let dropped = Arc::clone(&dropped);
             move || {
                 let mut decode_main = {
                     let mutex = Arc::clone(&mutex);
                     move || -> Result<()> {
-                        while !dropped.load(Ordering::Relaxed) && format_ctx.read_frame(&mut packet)? {
+                        let mut ended = false;
+                        // frames before this (in seconds) are decoded but not shown, since seeking lands on keyframes
+                        let mut skip_until = None;
+                        loop {
+                            let seek = {
+                                let mut state = mutex.0.lock().unwrap();
+                                // keep the stream open after it ends, in case we seek back
+                                while ended && state.seek.is_none() && !dropped.load(Ordering::Relaxed) {
+                                    state = mutex.1.wait(state).unwrap();
+                                }
+                                state.seek.take()
+                            };
+                            if dropped.load(Ordering::Relaxed) {
+                                return Ok(());
+                            }
+                            if let Some(target) = seek {
+                                format_ctx.seek_frame(video_index, (target / time_base) as i64 + start_time)?;
+                                codec_ctx.flush();
+                                ended = false;
+                                skip_until = Some(target);
+                            }
+                            if !format_ctx.read_frame(&mut packet)? {
+                                ended = true;
+                                let mut state = mutex.0.lock().unwrap();
+                                state.frame = Some(None);
+                                mutex.1.notify_one();
+                                continue;
+                            }
                             if packet.stream_index() != video_index {
                                 continue;
                             }
-                            codec_ctx.send_packet(&packet)?;
+                            codec_ctx.send_packet(Some(&packet))?;
 
                             while codec_ctx.receive_frame(&mut in_frame)? {
+                                if let Some(target) = skip_until {
+                                    let time = in_frame.timestamp().map_or(f64::INFINITY, |it| (it - start_time) as f64 * time_base);
+                                    if time < target {
+                                        continue;
+                                    }
+                                    skip_until = None;
+                                }
                                 sws.scale(&in_frame, &mut out_frame);
-                                let mut frame = mutex.0.lock().unwrap();
-                                *frame = Some(Some(unsafe { std::mem::transmute(&out_frame) }));
+                                let mut state = mutex.0.lock().unwrap();
+                                if state.seek.is_some() {
+                                    break;
+                                }
+                                state.frame = Some(Some(unsafe { std::mem::transmute(&out_frame) }));
                                 mutex.1.notify_one();
-                                while frame.is_some() {
+                                while state.frame.is_some() && state.seek.is_none() {
                                     if dropped.load(Ordering::Relaxed) {
                                         return Ok(());
                                     }
-                                    frame = mutex.1.wait(frame).unwrap();
+                                    state = mutex.1.wait(state).unwrap();
+                                }
+                                if state.seek.is_some() {
+                                    break;
                                 }
                             }
                         }
-                        let mut frame = mutex.0.lock().unwrap();
-                        *frame = Some(None);
-                        mutex.1.notify_one();
-                        Ok(())
                     }
                 };
                 if let Err(err) = decode_main() {
                     error!("decode failed: {err:?}");
-                    let mut frame = mutex.0.lock().unwrap();
-                    *frame = Some(None);
+                    let mut state = mutex.0.lock().unwrap();
+                    state.frame = Some(None);
                     mutex.1.notify_one();
                 }
             }
@@ -93,6 +156,7 @@ impl Video {
         Ok(Self {
             stream_format,
             video_stream,
+            duration,
 
             dropped,
             ended: AtomicBool::default(),
@@ -110,19 +174,33 @@ impl Video {
         self.video_stream.frame_rate()
     }
 
+    /// Duration in seconds, if the container tells.
+    pub fn duration(&self) -> Option<f64> {
+        self.duration
+    }
+
+    /// Makes the next frame the first one at or after `time` seconds. The frame pending now is dropped.
+    pub fn seek(&self, time: f64) {
+        let mut state = self.mutex.0.lock().unwrap();
+        state.seek = Some(time.max(0.));
+        state.frame = None;
+        self.ended.store(false, Ordering::SeqCst);
+        self.mutex.1.notify_one();
+    }
+
     pub fn with_frame<R>(&self, f: impl FnOnce(&AVFrame) -> R) -> Option<R> {
-        let mut frame = self.mutex.0.lock().unwrap();
+        let mut state = self.mutex.0.lock().unwrap();
         loop {
-            let Some(data) = *frame else {
-        		frame = self.mutex.1.wait(frame).unwrap();
-        		continue;
-        	};
+            let Some(data) = state.frame else {
+                state = self.mutex.1.wait(state).unwrap();
+                continue;
+            };
             let Some(data) = data else {
                 self.ended.store(true, Ordering::SeqCst);
                 return None;
             };
             let res = f(data);
-            *frame = None;
+            state.frame = None;
             self.mutex.1.notify_one();
             break Some(res);
         }
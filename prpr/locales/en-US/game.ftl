@@ -9,3 +9,7 @@ speed = Speed
 ex-time-out-of-range = Time is out of range
 ex-invalid-format = Invalid format
 ex-time-set = Time changed
+
+chart-reloaded = Chart reloaded
+chart-reload-failed = Failed to reload chart
+remote-load-failed = Failed to load chart
@@ -38,5 +38,27 @@ line-location = On line #{ $lid }
 # extra
 shader-load-failed = Cannot load shader from { $path }
 shader-not-found = Cannot find preset shader { $shader }
+param-not-found = Shader { $shader } has no parameter { $name }, available: { $available }
+param-type-mismatch = Parameter { $name } should be a { $expected }
+param-not-integer = Parameter { $name } should be an integer, got { $value }
+param-out-of-range = Parameter { $name } is { $value }, outside { $min }..{ $max }
 effect-location = In effect #{ $id }
+easing-not-found = Cannot find easing { $name }
+easing-invalid = Invalid easing { $name }
 video-load-failed = Failed to read video from { $path }
+texture-load-failed = Failed to read texture from { $path }
+
+# validate
+validate-parse-failed = Failed to parse chart: { $error }
+validate-invalid-time = Note has an invalid time
+validate-note-before-start = Note is before the start of the chart
+validate-hold-end-before-start = Hold ends before it starts
+validate-note-after-end = Note ends after the track ({ $time }s > { $length }s)
+validate-parent-out-of-range = Parent line #{ $parent } does not exist
+validate-parent-cycle = Line is its own ancestor
+validate-unknown-easing = Unknown easing type { $easing }, treated as linear
+validate-texture-missing = Cannot find texture { $path }
+validate-invalid-bpm-factor = Invalid bpmfactor { $factor }, treated as 1
+validate-unknown-line-group = Line group #{ $group } does not exist
+validate-invalid-judge-area = Invalid judgeArea { $area }, treated as 1
+validate-hitsound-missing = Cannot find hit sound { $path }
@@ -9,3 +9,7 @@ speed = 速度
 ex-time-out-of-range = 时间不在范围内
 ex-invalid-format = 格式有误
 ex-time-set = 设置成功
+
+chart-reloaded = 谱面已重新加载
+chart-reload-failed = 谱面重新加载失败
+remote-load-failed = 谱面加载失败
@@ -38,5 +38,27 @@ line-location = #{ $lid } 行中
 # extra
 shader-load-failed = 无法从 { $path } 中加载 shader
 shader-not-found = 未找到预置 shader { $shader }
+param-not-found = Shader { $shader } 没有参数 { $name }，可用参数: { $available }
+param-type-mismatch = 参数 { $name } 的类型应为 { $expected }
+param-not-integer = 参数 { $name } 应为整数，实际为 { $value }
+param-out-of-range = 参数 { $name } 的值 { $value } 超出范围 { $min }..{ $max }
 effect-location = #{ $id } 号 effect 中
+easing-not-found = 未找到缓动 { $name }
+easing-invalid = 无效的缓动 { $name }
 video-load-failed = 从 { $path } 中加载视频失败
+texture-load-failed = 从 { $path } 中加载贴图失败
+
+# validate
+validate-parse-failed = 谱面解析失败: { $error }
+validate-invalid-time = 音符时间无效
+validate-note-before-start = 音符在谱面开始之前
+validate-hold-end-before-start = Hold 的结束时间早于开始时间
+validate-note-after-end = 音符在音乐结束之后 ({ $time }s > { $length }s)
+validate-parent-out-of-range = 父判定线 #{ $parent } 不存在
+validate-parent-cycle = 判定线的父级关系成环
+validate-unknown-easing = 未知缓动类型 { $easing }，按线性处理
+validate-texture-missing = 找不到贴图 { $path }
+validate-invalid-bpm-factor = 无效的 bpmfactor { $factor }，按 1 处理
+validate-unknown-line-group = 判定线分组 #{ $group } 不存在
+validate-invalid-judge-area = 无效的 judgeArea { $area }，按 1 处理
+validate-hitsound-missing = 找不到打击音效 { $path }
@@ -0,0 +1,277 @@
+use crate::{
+    core::{Anim, Chart, NoteKind},
+    info::ChartInfo,
+};
+use serde::Serialize;
+
+/// Width of the sliding window used for peak density, in seconds.
+const PEAK_WINDOW: f32 = 2.;
+/// Samples taken between two keyframes when measuring judge line motion.
+const MOTION_SAMPLES: usize = 8;
+/// Keyframes closer than this (in seconds) are treated as a jump rather than motion.
+const MIN_MOTION_SPAN: f32 = 1e-3;
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KindCounts {
+    pub click: u32,
+    pub drag: u32,
+    pub flick: u32,
+    pub hold: u32,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChartStats {
+    /// Real (non-fake) notes.
+    pub note_count: u32,
+    pub counts: KindCounts,
+    /// From the first note to the end of the last one, in seconds of chart time.
+    pub duration: f32,
+    /// Notes per second, one bucket for each second starting at the first note.
+    pub density: Vec<u32>,
+    pub average_density: f32,
+    /// Highest number of notes per second over any two-second window.
+    pub peak_density: f32,
+    /// Time of the first note in that window.
+    pub peak_start: f32,
+    /// Number of distinct times shared by two or more notes.
+    pub multiple_groups: u32,
+    /// Fraction of the duration during which at least one hold is being held.
+    pub hold_coverage: f32,
+    /// Average and peak speed of judge lines, in screen widths per second.
+    pub average_line_speed: f32,
+    pub peak_line_speed: f32,
+    /// Rough difficulty in the same scale as [`ChartInfo::difficulty`].
+    pub estimated_difficulty: f32,
+}
+
+impl ChartStats {
+    pub fn new(chart: &Chart) -> Self {
+        let notes: Vec<_> = chart.lines.iter().flat_map(|line| line.notes.iter()).filter(|note| !note.fake).collect();
+        if notes.is_empty() {
+            return Self::default();
+        }
+        let mut counts = KindCounts::default();
+        for note in &notes {
+            *match note.kind {
+                NoteKind::Click => &mut counts.click,
+                NoteKind::Drag => &mut counts.drag,
+                NoteKind::Flick => &mut counts.flick,
+                NoteKind::Hold { .. } => &mut counts.hold,
+            } += 1;
+        }
+
+        let end_time = |kind: &NoteKind, time: f32| match kind {
+            NoteKind::Hold { end_time, .. } => end_time.max(time),
+            _ => time,
+        };
+        let mut times: Vec<f32> = notes.iter().map(|note| note.time).collect();
+        times.sort_by(f32::total_cmp);
+        let start = times[0];
+        let end = notes.iter().map(|note| end_time(&note.kind, note.time)).fold(start, f32::max);
+        let duration = end - start;
+
+        let mut density = vec![0; duration.floor() as usize + 1];
+        for time in &times {
+            density[((time - start).floor() as usize).min(density.len() - 1)] += 1;
+        }
+
+        let (mut peak, mut peak_start) = (0, start);
+        let mut j = 0;
+        for (i, time) in times.iter().enumerate() {
+            while times[j] < time - PEAK_WINDOW {
+                j += 1;
+            }
+            if i + 1 - j > peak {
+                peak = i + 1 - j;
+                peak_start = times[j];
+            }
+        }
+
+        let mut hinted: Vec<f32> = notes.iter().filter(|note| note.multiple_hint).map(|note| note.time).collect();
+        hinted.sort_by(f32::total_cmp);
+        hinted.dedup();
+        let multiple_groups = hinted.len() as u32;
+
+        let mut holds: Vec<(f32, f32)> = notes
+            .iter()
+            .filter_map(|note| match note.kind {
+                NoteKind::Hold { end_time, .. } if end_time > note.time => Some((note.time, end_time)),
+                _ => None,
+            })
+            .collect();
+        holds.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut covered = 0.;
+        let mut current: Option<(f32, f32)> = None;
+        for (s, e) in holds {
+            match &mut current {
+                Some((_, ce)) if s <= *ce => *ce = ce.max(e),
+                _ => {
+                    if let Some((cs, ce)) = current {
+                        covered += ce - cs;
+                    }
+                    current = Some((s, e));
+                }
+            }
+        }
+        if let Some((cs, ce)) = current {
+            covered += ce - cs;
+        }
+
+        let (average_line_speed, peak_line_speed) = line_speeds(chart, start, end);
+        let average_density = if duration > 0. { notes.len() as f32 / duration } else { notes.len() as f32 };
+        let mut stats = Self {
+            note_count: notes.len() as u32,
+            counts,
+            duration,
+            density,
+            average_density,
+            peak_density: peak as f32 / PEAK_WINDOW,
+            peak_start,
+            multiple_groups,
+            hold_coverage: if duration > 0. { covered / duration } else { 0. },
+            average_line_speed,
+            peak_line_speed,
+            estimated_difficulty: 0.,
+        };
+        stats.estimated_difficulty = stats.estimate();
+        stats
+    }
+
+    // Hand-tuned against official charts; only meant to catch levels that are way off.
+    fn estimate(&self) -> f32 {
+        let n = self.note_count.max(1) as f32;
+        let tap_ratio = (self.counts.click + self.counts.hold) as f32 / n;
+        let flick_ratio = self.counts.flick as f32 / n;
+        let multiple_ratio = self.multiple_groups as f32 * 2. / n;
+        let density = self.average_density * (0.6 + 0.4 * tap_ratio) + 0.3 * flick_ratio * self.average_density;
+        let burst = (self.peak_density - self.average_density).max(0.);
+        let motion = self.average_line_speed.min(4.);
+        (1. + 2.6 * density.powf(0.75) + 0.7 * burst.powf(0.8) + 1.2 * multiple_ratio.min(1.) + 0.6 * motion).clamp(1., 17.)
+    }
+
+    /// Whether the difficulty declared in `info` is further than `tolerance` from the estimate.
+    pub fn is_mislabeled(&self, info: &ChartInfo, tolerance: f32) -> bool {
+        let declared = level_number(&info.level).unwrap_or(info.difficulty);
+        (declared - self.estimated_difficulty).abs() > tolerance
+    }
+}
+
+/// Extracts the number from level strings such as `IN Lv.15` or `AT 16.2`.
+pub fn level_number(level: &str) -> Option<f32> {
+    let end = level.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = level[..end].rfind(|c: char| !(c.is_ascii_digit() || c == '.')).map_or(0, |it| it + 1);
+    level[start..end].trim_start_matches('.').parse().ok()
+}
+
+fn keyframe_times<T>(anim: &Anim<T>, out: &mut Vec<f32>) {
+    let mut cur = Some(anim);
+    while let Some(anim) = cur {
+        out.extend(anim.keyframes.iter().map(|it| it.time));
+        cur = anim.next.as_deref();
+    }
+}
+
+/// Samples every segment between keyframes on its own, so that short moves aren't skipped and jumps
+/// between segments aren't counted as motion.
+fn line_speeds(chart: &Chart, start: f32, end: f32) -> (f32, f32) {
+    if end <= start || chart.lines.is_empty() {
+        return (0., 0.);
+    }
+    let (mut distance, mut time, mut peak) = (0., 0., 0f32);
+    for line in &chart.lines {
+        let mut bounds = vec![start, end];
+        keyframe_times(&line.object.translation.0, &mut bounds);
+        keyframe_times(&line.object.translation.1, &mut bounds);
+        bounds.retain(|it| (start..=end).contains(it));
+        bounds.sort_by(f32::total_cmp);
+        bounds.dedup();
+
+        let (mut x, mut y) = (line.object.translation.0.clone(), line.object.translation.1.clone());
+        let mut pos = |t: f32| {
+            x.set_time(t);
+            y.set_time(t);
+            (x.now() / 2., y.now() / 2.)
+        };
+        for w in bounds.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            if b - a < MIN_MOTION_SPAN {
+                continue;
+            }
+            // stop just short of the next keyframe, where the value may jump
+            let b = b - (b - a) * 1e-3;
+            let dt = (b - a) / MOTION_SAMPLES as f32;
+            let mut last = pos(a);
+            for i in 1..=MOTION_SAMPLES {
+                let now = pos(a + i as f32 * dt);
+                let d = ((now.0 - last.0).powi(2) + (now.1 - last.1).powi(2)).sqrt();
+                distance += d;
+                time += dt;
+                peak = peak.max(d / dt);
+                last = now;
+            }
+        }
+    }
+    (if time > 0. { distance / time } else { 0. }, peak)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::ChartExtra,
+        fs::MemoryFileSystem,
+        parse::{parse_rpe, RPE_WIDTH},
+    };
+    use serde_json::json;
+
+    fn load(source: &str) -> Chart {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(parse_rpe(source, &mut MemoryFileSystem::default(), ChartExtra::default()))
+            .unwrap()
+    }
+
+    #[test]
+    fn stats() {
+        let note = |beat: i32| {
+            json!({
+                "type": 1, "above": 1, "startTime": [beat, 0, 1], "endTime": [beat, 0, 1], "positionX": 0.0, "yOffset": 0.0,
+                "alpha": 255, "size": 1.0, "speed": 1.0, "isFake": 0, "visibleTime": 999999.0
+            })
+        };
+        // 120 BPM, so beats are half seconds
+        let chart = json!({
+            "META": { "offset": 0, "RPEVersion": 140, "name": "stats" },
+            "BPMList": [{ "bpm": 120.0, "startTime": [0, 0, 1] }],
+            "judgeLineList": [{
+                "Group": 0,
+                "Name": "line",
+                "Texture": "line.png",
+                "father": -1,
+                "isCover": 1,
+                "eventLayers": [{
+                    // a move lasting 10ms
+                    "moveXEvents": [
+                        { "easingType": 1, "start": 0.0, "end": 300.0, "startTime": [8, 0, 1], "endTime": [8, 1, 50] }
+                    ],
+                    "speedEvents": [{ "start": 10.0, "end": 10.0, "startTime": [0, 0, 1], "endTime": [20, 0, 1] }]
+                }],
+                "notes": [note(0), note(10), note(11), note(12), note(20)]
+            }]
+        });
+        let stats = ChartStats::new(&load(&chart.to_string()));
+        assert_eq!(stats.note_count, 5);
+        assert_eq!(stats.duration, 10.);
+        // 5, 5.5 and 6 fall in one window, which starts at its first note
+        assert_eq!(stats.peak_density, 1.5);
+        assert_eq!(stats.peak_start, 5.);
+        let speed = 300. / (RPE_WIDTH / 2.) / 2. / 0.01;
+        assert!((stats.peak_line_speed - speed).abs() < speed * 0.01, "{} != {speed}", stats.peak_line_speed);
+        // the line moves that far once in ten seconds
+        let average = 300. / (RPE_WIDTH / 2.) / 2. / 10.;
+        assert!((stats.average_line_speed - average).abs() < average * 0.01, "{} != {average}", stats.average_line_speed);
+    }
+}
@@ -1,14 +1,18 @@
 use crate::{
     core::{
-        Anim, AnimVector, BezierTween, BpmList, Chart, ChartExtra, ChartSettings, ClampedTween, CtrlObject, JudgeLine, JudgeLineCache, JudgeLineKind,
-        Keyframe, Note, NoteKind, Object, StaticTween, Tweenable, UIElement,
+        Anim, AnimVector, BezierTween, BpmList, Chart, ChartExtra, ChartSettings, ClampedTween, CtrlObject, EventKind, JudgeLine, JudgeLineCache,
+        JudgeLineKind, Keyframe, LinkedEvent, Note, NoteKind, Object, PiecewiseTween, RangedTween, SpringTween, StaticTween, StepsTween,
+        TweenFunction, Tweenable, UIElement,
     },
     judge::JudgeStatus,
     parse::process_lines,
 };
 use anyhow::{bail, Result};
 use byteorder::{LittleEndian as LE, ReadBytesExt, WriteBytesExt};
-use macroquad::{prelude::Color, texture::Texture2D};
+use macroquad::{
+    prelude::{Color, WHITE},
+    texture::Texture2D,
+};
 use std::{
     cell::RefCell,
     io::{Read, Write},
@@ -16,16 +20,31 @@ use std::{
     rc::Rc,
 };
 
+/// Magic bytes of a versioned PBC file. Files without it are read as version 1.
+pub const PBC_MAGIC: &[u8; 4] = b"PBC\0";
+/// Version 3 adds custom tweens (steps, spring, piecewise and ranged), version 4 per-note tints, judge widths and hit sounds, version 5 RPE
+/// line groups and link groups.
+pub const PBC_VERSION: u8 = 5;
+
 pub trait BinaryData: Sized {
     fn read_binary<R: Read>(r: &mut BinaryReader<R>) -> Result<Self>;
     fn write_binary<W: Write>(&self, w: &mut BinaryWriter<W>) -> Result<()>;
 }
 
-pub struct BinaryReader<R: Read>(pub R, u32);
+pub struct BinaryReader<R: Read>(pub R, u32, u8);
 
 impl<R: Read> BinaryReader<R> {
     pub fn new(reader: R) -> Self {
-        Self(reader, 0)
+        Self(reader, 0, PBC_VERSION)
+    }
+
+    pub fn with_version(reader: R, version: u8) -> Self {
+        Self(reader, 0, version)
+    }
+
+    #[inline]
+    pub fn version(&self) -> u8 {
+        self.2
     }
 
     pub fn reset_time(&mut self) {
@@ -33,6 +52,9 @@ impl<R: Read> BinaryReader<R> {
     }
 
     pub fn time(&mut self) -> Result<f32> {
+        if self.2 >= 2 {
+            return self.read();
+        }
         self.1 += self.uleb()? as u32;
         Ok(self.1 as f32 / 1000.)
     }
@@ -59,23 +81,16 @@ impl<R: Read> BinaryReader<R> {
     }
 }
 
-pub struct BinaryWriter<W: Write>(pub W, u32);
+pub struct BinaryWriter<W: Write>(pub W);
 
 impl<W: Write> BinaryWriter<W> {
     pub fn new(writer: W) -> Self {
-        Self(writer, 0)
-    }
-
-    pub fn reset_time(&mut self) {
-        self.1 = 0;
+        Self(writer)
     }
 
     pub fn time(&mut self, v: f32) -> Result<()> {
-        let v = (v * 1000.).round() as u32;
-        assert!(v >= self.1);
-        self.uleb((v - self.1) as _)?;
-        self.1 = v;
-        Ok(())
+        // times used to be stored as millisecond deltas, which is lossy
+        self.write_val(v)
     }
 
     pub fn array<T: BinaryData>(&mut self, v: &[T]) -> Result<()> {
@@ -151,6 +166,16 @@ impl BinaryData for f32 {
     }
 }
 
+impl BinaryData for f64 {
+    fn read_binary<R: Read>(r: &mut BinaryReader<R>) -> Result<Self> {
+        Ok(r.0.read_f64::<LE>()?)
+    }
+
+    fn write_binary<W: Write>(&self, w: &mut BinaryWriter<W>) -> Result<()> {
+        Ok(w.0.write_f64::<LE>(*self)?)
+    }
+}
+
 impl BinaryData for String {
     fn read_binary<R: Read>(r: &mut BinaryReader<R>) -> Result<Self> {
         Ok(String::from_utf8(r.array()?)?)
@@ -163,14 +188,17 @@ impl BinaryData for String {
 
 impl BinaryData for Color {
     fn read_binary<R: Read>(r: &mut BinaryReader<R>) -> Result<Self> {
-        Ok(Self::from_rgba(r.read()?, r.read()?, r.read()?, r.read()?))
+        if r.version() < 2 {
+            return Ok(Self::from_rgba(r.read()?, r.read()?, r.read()?, r.read()?));
+        }
+        Ok(Self::new(r.read()?, r.read()?, r.read()?, r.read()?))
     }
 
     fn write_binary<W: Write>(&self, w: &mut BinaryWriter<W>) -> Result<()> {
-        w.write_val((self.r * 256.) as u8)?;
-        w.write_val((self.g * 256.) as u8)?;
-        w.write_val((self.b * 256.) as u8)?;
-        w.write_val((self.a * 256.) as u8)?;
+        w.write_val(self.r)?;
+        w.write_val(self.g)?;
+        w.write_val(self.b)?;
+        w.write_val(self.a)?;
         Ok(())
     }
 }
@@ -182,39 +210,86 @@ impl<T: BinaryData> BinaryData for Keyframe<T> {
         Ok(Self {
             time: r.time()?,
             value: r.read()?,
-            tween: {
-                let b = r.read::<u8>()?;
-                match b & 0xC0 {
-                    0 => StaticTween::get_rc(b),
-                    0x80 => Rc::new(ClampedTween::new(b & 0x7f, r.read()?..r.read()?)),
-                    0xC0 => Rc::new(BezierTween::new((r.read()?, r.read()?), (r.read()?, r.read()?))),
-                    _ => panic!("invalid tween"),
-                }
-            },
+            tween: read_tween(r)?,
         })
     }
 
     fn write_binary<W: Write>(&self, w: &mut BinaryWriter<W>) -> Result<()> {
         w.time(self.time)?;
         w.write(&self.value)?;
-        let tween = self.tween.as_any();
-        if let Some(t) = tween.downcast_ref::<StaticTween>() {
-            w.write_val(t.0)?;
-        } else if let Some(t) = tween.downcast_ref::<ClampedTween>() {
-            w.write_val(0x80 | t.0)?;
-            w.write_val(t.1.start)?;
-            w.write_val(t.1.end)?;
-        } else if let Some(t) = tween.downcast_ref::<BezierTween>() {
-            w.write_val(0xC0)?;
-            w.write_val(t.p1.0)?;
-            w.write_val(t.p1.1)?;
-            w.write_val(t.p2.0)?;
-            w.write_val(t.p2.1)?;
-        }
-        Ok(())
+        write_tween(w, &self.tween)
     }
 }
 
+fn read_tween<R: Read>(r: &mut BinaryReader<R>) -> Result<Rc<dyn TweenFunction>> {
+    let b = r.read::<u8>()?;
+    Ok(match b & 0xC0 {
+        0 => StaticTween::get_rc(b),
+        0x80 => Rc::new(ClampedTween::new(b & 0x7f, r.read()?..r.read()?)),
+        0xC0 => Rc::new(BezierTween::new((r.read()?, r.read()?), (r.read()?, r.read()?))),
+        _ => match b & 0x3f {
+            0 => Rc::new(StepsTween {
+                steps: r.uleb()? as u32,
+                jump_start: r.read()?,
+            }),
+            1 => Rc::new(SpringTween::new(r.read()?, r.read()?)),
+            2 => {
+                let len = r.uleb()? as usize;
+                let mut points = Vec::with_capacity(len);
+                for _ in 0..len {
+                    points.push((r.read()?, r.read()?));
+                }
+                Rc::new(PiecewiseTween::new(points))
+            }
+            3 => {
+                let range = r.read()?..r.read()?;
+                Rc::new(RangedTween::new(read_tween(r)?, range))
+            }
+            kind => bail!("invalid tween: {kind}"),
+        },
+    })
+}
+
+fn write_tween<W: Write>(w: &mut BinaryWriter<W>, tween: &Rc<dyn TweenFunction>) -> Result<()> {
+    let tween = tween.as_any();
+    if let Some(t) = tween.downcast_ref::<StaticTween>() {
+        w.write_val(t.0)?;
+    } else if let Some(t) = tween.downcast_ref::<ClampedTween>() {
+        w.write_val(0x80 | t.0)?;
+        w.write_val(t.1.start)?;
+        w.write_val(t.1.end)?;
+    } else if let Some(t) = tween.downcast_ref::<BezierTween>() {
+        w.write_val(0xC0)?;
+        w.write_val(t.p1.0)?;
+        w.write_val(t.p1.1)?;
+        w.write_val(t.p2.0)?;
+        w.write_val(t.p2.1)?;
+    } else if let Some(t) = tween.downcast_ref::<StepsTween>() {
+        w.write_val(0x40_u8)?;
+        w.uleb(t.steps as u64)?;
+        w.write_val(t.jump_start)?;
+    } else if let Some(t) = tween.downcast_ref::<SpringTween>() {
+        w.write_val(0x41_u8)?;
+        w.write_val(t.damping)?;
+        w.write_val(t.frequency)?;
+    } else if let Some(t) = tween.downcast_ref::<PiecewiseTween>() {
+        w.write_val(0x42_u8)?;
+        w.uleb(t.points().len() as u64)?;
+        for (x, y) in t.points() {
+            w.write_val(*x)?;
+            w.write_val(*y)?;
+        }
+    } else if let Some(t) = tween.downcast_ref::<RangedTween>() {
+        w.write_val(0x43_u8)?;
+        w.write_val(t.range.start)?;
+        w.write_val(t.range.end)?;
+        write_tween(w, &t.inner)?;
+    } else {
+        bail!("unsupported tween");
+    }
+    Ok(())
+}
+
 fn read_opt<R: Read, T: BinaryData + Tweenable>(r: &mut BinaryReader<R>) -> Result<Option<Box<Anim<T>>>> {
     Ok(match r.read::<u8>()? {
         0 => None,
@@ -244,7 +319,6 @@ impl<T: BinaryData + Tweenable> BinaryData for Anim<T> {
             } else {
                 w.write_val(2_u8)?;
                 w.uleb(cur.keyframes.len() as _)?;
-                w.reset_time();
                 for kf in cur.keyframes.iter() {
                     kf.write_binary(w)?;
                 }
@@ -318,13 +392,23 @@ impl BinaryData for Note {
             time: r.time()?,
             height: r.read()?,
             speed: if r.read()? { r.read::<f32>()? } else { 1. },
-            end_speed: if r.read()? { r.read::<f32>()? } else { 1. },
-            start_height: r.read()?,
+            end_speed: if r.version() < 2 {
+                1.
+            } else if r.read()? {
+                r.read::<f32>()?
+            } else {
+                1.
+            },
+            start_height: if r.version() < 2 { 0. } else { r.read()? },
             above: r.read()?,
             multiple_hint: false,
             fake: r.read()?,
             judge: JudgeStatus::NotJudged,
-            format: r.read()?,
+            format: if r.version() < 2 { false } else { r.read()? },
+            tint: if r.version() < 4 { WHITE } else { r.read()? },
+            hit_fx_color: if r.version() < 4 || !r.read::<bool>()? { None } else { Some(r.read()?) },
+            judge_scale: if r.version() < 4 { 1. } else { r.read()? },
+            hitsound: if r.version() < 4 || !r.read::<bool>()? { None } else { Some(r.read()?) },
         })
     }
 
@@ -350,8 +434,50 @@ impl BinaryData for Note {
             w.write_val(true)?;
             w.write_val(self.speed)?;
         }
+        if self.end_speed == 1.0 {
+            w.write_val(false)?;
+        } else {
+            w.write_val(true)?;
+            w.write_val(self.end_speed)?;
+        }
+        w.write_val(self.start_height)?;
         w.write_val(self.above)?;
         w.write_val(self.fake)?;
+        w.write_val(self.format)?;
+        w.write(&self.tint)?;
+        if let Some(color) = &self.hit_fx_color {
+            w.write_val(true)?;
+            w.write(color)?;
+        } else {
+            w.write_val(false)?;
+        }
+        w.write_val(self.judge_scale)?;
+        if let Some(path) = &self.hitsound {
+            w.write_val(true)?;
+            w.write(path)?;
+        } else {
+            w.write_val(false)?;
+        }
+        Ok(())
+    }
+}
+
+impl BinaryData for LinkedEvent {
+    fn read_binary<R: Read>(r: &mut BinaryReader<R>) -> Result<Self> {
+        let Some(kind) = EventKind::from_u8(r.read()?) else {
+            bail!("invalid event kind");
+        };
+        Ok(Self {
+            kind,
+            time: r.read()?,
+            group: r.read()?,
+        })
+    }
+
+    fn write_binary<W: Write>(&self, w: &mut BinaryWriter<W>) -> Result<()> {
+        w.write_val(self.kind as u8)?;
+        w.write_val(self.time)?;
+        w.write_val(self.group)?;
         Ok(())
     }
 }
@@ -380,6 +506,7 @@ impl BinaryData for JudgeLine {
         let ctrl_obj = RefCell::new(r.read()?);
         let incline = r.read()?;
         let z_index = r.read()?;
+        let (group, link_groups) = if r.version() < 5 { (0, Vec::new()) } else { (r.uleb()? as usize, r.array()?) };
         Ok(Self {
             object,
             kind,
@@ -393,6 +520,8 @@ impl BinaryData for JudgeLine {
             ctrl_obj,
             incline,
             z_index,
+            group,
+            link_groups,
 
             cache,
         })
@@ -427,6 +556,8 @@ impl BinaryData for JudgeLine {
         w.write(self.ctrl_obj.borrow().deref())?;
         w.write(&self.incline)?;
         w.write(&self.z_index)?;
+        w.uleb(self.group as u64)?;
+        w.array(&self.link_groups)?;
         Ok(())
     }
 }
@@ -446,19 +577,407 @@ impl BinaryData for ChartSettings {
     }
 }
 
+impl BinaryData for BpmList {
+    fn read_binary<R: Read>(r: &mut BinaryReader<R>) -> Result<Self> {
+        Ok(BpmList::new((0..r.uleb()?).map(|_| Ok((r.read()?, r.read()?))).collect::<Result<_>>()?))
+    }
+
+    fn write_binary<W: Write>(&self, w: &mut BinaryWriter<W>) -> Result<()> {
+        let ranges = self.ranges();
+        w.uleb(ranges.len() as _)?;
+        for (beats, bpm) in ranges {
+            w.write_val(beats)?;
+            w.write_val(bpm)?;
+        }
+        Ok(())
+    }
+}
+
 impl BinaryData for Chart {
     fn read_binary<R: Read>(r: &mut BinaryReader<R>) -> Result<Self> {
         let offset = r.read()?;
+        let bpm_list = if r.version() < 2 { BpmList::new(vec![(0., 60.)]) } else { r.read()? };
         let mut lines = r.array()?;
         process_lines(&mut lines);
         let settings = r.read()?;
-        Ok(Chart::new(offset, lines, BpmList::new(vec![(0., 60.)]), settings, ChartExtra::default()))
+        let extra = ChartExtra {
+            // effects and videos need the chart's file system, see `GameScene::load_chart`
+            source: if r.version() < 2 || !r.read::<bool>()? { None } else { Some(r.read()?) },
+            ..Default::default()
+        };
+        let mut chart = Chart::new(offset, lines, bpm_list, settings, extra);
+        if r.version() >= 5 {
+            chart.line_groups = r.array()?;
+        }
+        Ok(chart)
     }
 
     fn write_binary<W: Write>(&self, w: &mut BinaryWriter<W>) -> Result<()> {
         w.write_val(self.offset)?;
+        w.write(self.bpm_list.borrow().deref())?;
         w.array(&self.lines)?;
         w.write(&self.settings)?;
+        if let Some(source) = &self.extra.source {
+            w.write_val(true)?;
+            w.write(source)?;
+        } else {
+            w.write_val(false)?;
+        }
+        w.array(&self.line_groups)?;
         Ok(())
     }
 }
+
+/// Reads a PBC chart, falling back to the unversioned legacy layout when there's no header.
+pub fn read_pbc(bytes: &[u8]) -> Result<Chart> {
+    if let Some(rest) = bytes.strip_prefix(PBC_MAGIC) {
+        let Some((&version, rest)) = rest.split_first() else {
+            bail!("truncated pbc header");
+        };
+        if version > PBC_VERSION {
+            bail!("unsupported pbc version: {version}");
+        }
+        BinaryReader::with_version(rest, version).read()
+    } else {
+        BinaryReader::with_version(bytes, 1).read()
+    }
+}
+
+/// Writes a versioned PBC chart.
+///
+/// [`ChartExtra`] is the one part not encoded field by field: effects and videos hold shaders, textures and decoders loaded from the
+/// chart's file system, so only the `extra.json` it was parsed from is kept, and `GameScene::load_chart` parses it again.
+pub fn write_pbc(chart: &Chart, w: impl Write) -> Result<()> {
+    let mut w = BinaryWriter::new(w);
+    w.0.write_all(PBC_MAGIC)?;
+    w.write_val(PBC_VERSION)?;
+    w.write(chart)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fs::MemoryFileSystem, parse::parse_rpe};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use serde_json::json;
+    use std::fmt::Debug;
+
+    fn load_rpe(source: &str) -> Chart {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(parse_rpe(source, &mut MemoryFileSystem::default(), ChartExtra::default()))
+            .unwrap()
+    }
+
+    fn encode(chart: &Chart) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_pbc(chart, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn assert_anim<T: Tweenable + PartialEq + Debug>(a: &Anim<T>, b: &Anim<T>, end: f32) {
+        let (mut a, mut b) = (a.clone(), b.clone());
+        let mut time = 0.;
+        while time <= end {
+            a.set_time(time);
+            b.set_time(time);
+            assert_eq!(a.now_opt(), b.now_opt(), "at {time}");
+            time += 0.1;
+        }
+    }
+
+    fn assert_object(a: &Object, b: &Object, end: f32) {
+        assert_anim(&a.alpha, &b.alpha, end);
+        assert_anim(&a.rotation, &b.rotation, end);
+        assert_anim(&a.translation.0, &b.translation.0, end);
+        assert_anim(&a.translation.1, &b.translation.1, end);
+        assert_anim(&a.scale.0, &b.scale.0, end);
+        assert_anim(&a.scale.1, &b.scale.1, end);
+    }
+
+    fn assert_note(a: &Note, b: &Note, end: f32) {
+        assert_object(&a.object, &b.object, end);
+        assert_eq!(format!("{:?}", a.kind), format!("{:?}", b.kind));
+        assert_eq!(a.time, b.time);
+        assert_eq!(a.height, b.height);
+        assert_eq!(a.speed, b.speed);
+        assert_eq!(a.end_speed, b.end_speed);
+        assert_eq!(a.start_height, b.start_height);
+        assert_eq!(a.above, b.above);
+        assert_eq!(a.multiple_hint, b.multiple_hint);
+        assert_eq!(a.fake, b.fake);
+        assert_eq!(a.format, b.format);
+        assert_eq!(a.tint, b.tint);
+        assert_eq!(a.hit_fx_color, b.hit_fx_color);
+        assert_eq!(a.judge_scale, b.judge_scale);
+        assert_eq!(a.hitsound, b.hitsound);
+    }
+
+    fn assert_line(a: &JudgeLine, b: &JudgeLine, end: f32) {
+        assert_object(&a.object, &b.object, end);
+        match (&a.kind, &b.kind) {
+            (JudgeLineKind::Normal, JudgeLineKind::Normal) => {}
+            (JudgeLineKind::Texture(_, a), JudgeLineKind::Texture(_, b)) => assert_eq!(a, b),
+            (JudgeLineKind::Text(a), JudgeLineKind::Text(b)) => assert_anim(a, b, end),
+            (JudgeLineKind::Paint(a, _), JudgeLineKind::Paint(b, _)) => assert_anim(a, b, end),
+            _ => panic!("judge line kind mismatch"),
+        }
+        assert_anim(&a.height, &b.height, end);
+        assert_anim(&a.color, &b.color, end);
+        assert_anim(&a.incline, &b.incline, end);
+        let (ca, cb) = (a.ctrl_obj.borrow(), b.ctrl_obj.borrow());
+        assert_anim(&ca.alpha, &cb.alpha, 2.);
+        assert_anim(&ca.size, &cb.size, 2.);
+        assert_anim(&ca.pos, &cb.pos, 2.);
+        assert_anim(&ca.y, &cb.y, 2.);
+        assert_eq!(a.parent, b.parent);
+        assert_eq!(a.show_below, b.show_below);
+        assert_eq!(a.attach_ui.map(|it| it as u8), b.attach_ui.map(|it| it as u8));
+        assert_eq!(a.z_index, b.z_index);
+        assert_eq!(a.group, b.group);
+        let links = |line: &JudgeLine| line.link_groups.iter().map(|it| (it.kind as u8, it.time, it.group)).collect::<Vec<_>>();
+        assert_eq!(links(a), links(b));
+        assert_eq!(a.notes.len(), b.notes.len());
+        for (a, b) in a.notes.iter().zip(b.notes.iter()) {
+            assert_note(a, b, end);
+        }
+    }
+
+    /// Writes `chart`, reads it back and checks that nothing was lost.
+    fn assert_round_trip(chart: &Chart) -> Chart {
+        let bytes = encode(chart);
+        let decoded = read_pbc(&bytes).unwrap();
+        // the encoding covers every field, so it must be a fixpoint
+        assert_eq!(encode(&decoded), bytes);
+
+        let end = chart.lines.iter().flat_map(|it| it.height.keyframes.last()).map(|it| it.time).fold(1., f32::max);
+        assert_eq!(chart.offset, decoded.offset);
+        assert_eq!(chart.bpm_list.borrow().ranges(), decoded.bpm_list.borrow().ranges());
+        assert_eq!(chart.settings.pe_alpha_extension, decoded.settings.pe_alpha_extension);
+        assert_eq!(chart.settings.hold_partial_cover, decoded.settings.hold_partial_cover);
+        assert_eq!(chart.extra.source, decoded.extra.source);
+        assert_eq!(chart.line_groups, decoded.line_groups);
+        assert_eq!(chart.order, decoded.order);
+        assert_eq!(chart.lines.len(), decoded.lines.len());
+        for (a, b) in chart.lines.iter().zip(decoded.lines.iter()) {
+            assert_line(a, b, end);
+        }
+        decoded
+    }
+
+    #[test]
+    fn fixtures() {
+        for source in [include_str!("../tests/fixtures/basic.json"), include_str!("../tests/fixtures/groups.json")] {
+            let mut chart = load_rpe(source);
+            chart.extra.source = Some(r#"{"effects":[]}"#.to_owned());
+            assert_round_trip(&chart);
+        }
+    }
+
+    #[test]
+    fn v4_note_fields() {
+        let chart = assert_round_trip(&load_rpe(include_str!("../tests/fixtures/basic.json")));
+        let notes = &chart.lines[0].notes;
+        let hold = notes.iter().find(|it| matches!(it.kind, NoteKind::Hold { .. })).unwrap();
+        assert_eq!(hold.tint, Color::from_rgba(255, 0, 0, 255));
+        assert_eq!(hold.speed, 1.5);
+        let flick = notes.iter().find(|it| matches!(it.kind, NoteKind::Flick)).unwrap();
+        assert_eq!(flick.hit_fx_color, Some(Color::from_rgba(0, 255, 128, 255)));
+        assert_eq!(flick.judge_scale, 2.);
+        let drag = notes.iter().find(|it| matches!(it.kind, NoteKind::Drag)).unwrap();
+        assert_eq!(drag.hitsound.as_deref(), Some("drum.wav"));
+        assert!(drag.fake);
+    }
+
+    fn random_rpe(rng: &mut StdRng) -> String {
+        let beat = |rng: &mut StdRng| json!([rng.gen_range(0..16), rng.gen_range(0..4), 4]);
+        let span = |rng: &mut StdRng| {
+            let start = rng.gen_range(0..16);
+            (json!([start, 0, 1]), json!([start + rng.gen_range(1..4), rng.gen_range(0..3), 3]))
+        };
+        let events = |rng: &mut StdRng, range: f32| {
+            (0..rng.gen_range(1..4))
+                .map(|_| {
+                    let (start_time, end_time) = span(rng);
+                    let left = if rng.gen_bool(0.3) { rng.gen_range(0.0..0.5) } else { 0. };
+                    json!({
+                        "linkgroup": rng.gen_range(0..3),
+                        "easingType": rng.gen_range(0..32),
+                        "easingLeft": left,
+                        "easingRight": if rng.gen_bool(0.3) { rng.gen_range(0.5..1.0) } else { 1. },
+                        "bezier": rng.gen_bool(0.2) as u8,
+                        "bezierPoints": [rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0)],
+                        "start": rng.gen_range(-range..range),
+                        "end": rng.gen_range(-range..range),
+                        "startTime": start_time,
+                        "endTime": end_time,
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+        let lines = (0..rng.gen_range(1..5))
+            .map(|id| {
+                let notes = (0..rng.gen_range(0..20))
+                    .map(|_| {
+                        let start = beat(rng);
+                        let color = |rng: &mut StdRng| rng.gen_bool(0.3).then(|| [rng.gen::<u8>(), rng.gen::<u8>(), rng.gen::<u8>()]);
+                        let mut note = json!({
+                            "type": rng.gen_range(1..=4),
+                            "above": rng.gen_range(0..=1),
+                            "startTime": start,
+                            "endTime": [start[0].as_i64().unwrap() + 1, 0, 1],
+                            "positionX": rng.gen_range(-675.0..675.0),
+                            "yOffset": rng.gen_range(-10.0..10.0),
+                            "alpha": rng.gen_range(0..=255),
+                            "size": if rng.gen_bool(0.5) { 1. } else { rng.gen_range(0.5..2.0) },
+                            "speed": if rng.gen_bool(0.5) { 1. } else { rng.gen_range(0.5..2.0) },
+                            "isFake": rng.gen_range(0..=1),
+                            "visibleTime": if rng.gen_bool(0.8) { 999999. } else { rng.gen_range(0.1..2.0) },
+                            "judgeArea": if rng.gen_bool(0.5) { 1. } else { rng.gen_range(0.5..3.0) },
+                        });
+                        if let Some(tint) = color(rng) {
+                            note["tint"] = json!(tint);
+                        }
+                        if let Some(tint) = color(rng) {
+                            note["tintHitEffects"] = json!(tint);
+                        }
+                        if rng.gen_bool(0.2) {
+                            note["hitsound"] = json!(format!("hit{}.wav", rng.gen_range(0..3)));
+                        }
+                        note
+                    })
+                    .collect::<Vec<_>>();
+                let speed = (0..rng.gen_range(1..3))
+                    .map(|_| {
+                        let (start_time, end_time) = span(rng);
+                        json!({
+                            "linkgroup": rng.gen_range(0..3),
+                            "start": rng.gen_range(-5.0..15.0),
+                            "end": rng.gen_range(-5.0..15.0),
+                            "startTime": start_time,
+                            "endTime": end_time,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                json!({
+                    "Group": rng.gen_range(0..2),
+                    "Name": format!("line {id}"),
+                    "Texture": "line.png",
+                    "father": if id > 0 && rng.gen_bool(0.5) { rng.gen_range(0..id) } else { -1 },
+                    "bpmfactor": if rng.gen_bool(0.5) { 1. } else { [0.5, 2., 1.5][rng.gen_range(0..3)] },
+                    "isCover": rng.gen_range(0..=1),
+                    "zOrder": rng.gen_range(-2..3),
+                    "eventLayers": [{
+                        "alphaEvents": events(rng, 255.),
+                        "moveXEvents": events(rng, 675.),
+                        "moveYEvents": events(rng, 450.),
+                        "rotateEvents": events(rng, 360.),
+                        "speedEvents": speed,
+                    }],
+                    "extended": {
+                        "scaleXEvents": events(rng, 2.),
+                        "scaleYEvents": events(rng, 2.),
+                        "inclineEvents": events(rng, 45.),
+                    },
+                    "notes": notes,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "META": { "offset": rng.gen_range(-500..500) },
+            "BPMList": [
+                { "bpm": rng.gen_range(60.0..240.0), "startTime": [0, 0, 1] },
+                { "bpm": rng.gen_range(60.0..240.0), "startTime": [rng.gen_range(1..16), 1, 2] },
+            ],
+            "judgeLineGroup": ["Default", "Other"],
+            "judgeLineList": lines,
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn random_charts() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        for _ in 0..64 {
+            assert_round_trip(&load_rpe(&random_rpe(&mut rng)));
+        }
+    }
+
+    #[test]
+    fn legacy_v1() {
+        // hand-written in the unversioned layout: millisecond time deltas, byte colours and no speeds past the first
+        let mut w = BinaryWriter::new(Vec::new());
+        let default_anim = |w: &mut BinaryWriter<Vec<u8>>| {
+            w.write_val(1_u8).unwrap();
+            w.write_val(0_u8).unwrap();
+        };
+        w.write_val(0.25_f32).unwrap(); // offset
+        w.uleb(1).unwrap(); // lines
+        for _ in 0..6 {
+            default_anim(&mut w); // object
+        }
+        w.write_val(0_u8).unwrap(); // kind
+        default_anim(&mut w); // height
+        w.uleb(2).unwrap(); // notes
+        for (kind, delta) in [(0_u8, 1000), (3, 500)] {
+            for _ in 0..6 {
+                default_anim(&mut w);
+            }
+            w.write_val(kind).unwrap();
+            w.uleb(delta).unwrap();
+            w.write_val(2.5_f32).unwrap(); // height
+            w.write_val(kind == 3).unwrap();
+            if kind == 3 {
+                w.write_val(1.5_f32).unwrap(); // speed
+            }
+            w.write_val(true).unwrap(); // above
+            w.write_val(false).unwrap(); // fake
+        }
+        // colour, with one keyframe stored as bytes
+        w.write_val(2_u8).unwrap();
+        w.uleb(1).unwrap();
+        w.uleb(0).unwrap();
+        for v in [255_u8, 0, 0, 255] {
+            w.write_val(v).unwrap();
+        }
+        w.write_val(0_u8).unwrap();
+        w.write_val(0_u8).unwrap();
+        w.uleb(0).unwrap(); // parent
+        w.write_val(true).unwrap(); // show below
+        w.write_val(0_u8).unwrap(); // attach ui
+        w.write_val(8_u8).unwrap();
+        for _ in 0..4 {
+            default_anim(&mut w); // ctrl object
+        }
+        default_anim(&mut w); // incline
+        w.write_val(0_i32).unwrap(); // z index
+        w.write_val(0_u8).unwrap();
+        w.write_val(1_u8).unwrap(); // settings
+
+        let chart = read_pbc(&w.0).unwrap();
+        assert_eq!(chart.offset, 0.25);
+        assert!(chart.settings.hold_partial_cover);
+        assert!(chart.extra.source.is_none());
+        let line = &chart.lines[0];
+        assert_eq!(line.color.keyframes[0].value, Color::from_rgba(255, 0, 0, 255));
+        let times: Vec<_> = line.notes.iter().map(|it| it.time).collect();
+        assert_eq!(times, [1_f32, 1.5]);
+        let drag = line.notes.iter().find(|it| matches!(it.kind, NoteKind::Drag)).unwrap();
+        assert_eq!(drag.speed, 1.5);
+        assert_eq!(drag.end_speed, 1.);
+        assert_eq!(drag.tint, WHITE);
+        assert_eq!(drag.judge_scale, 1.);
+        assert!(drag.hitsound.is_none());
+
+        // and it upgrades to the current version without loss
+        assert_round_trip(&chart);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let mut bytes = PBC_MAGIC.to_vec();
+        bytes.push(PBC_VERSION + 1);
+        assert!(read_pbc(&bytes).is_err());
+    }
+}
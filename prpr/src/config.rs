@@ -1,3 +1,4 @@
+use crate::judge::JudgeProfile;
 use bitflags::bitflags;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
@@ -29,6 +30,16 @@ pub enum ChallengeModeColor {
     Rainbow,
 }
 
+#[derive(Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum KeyMode {
+    /// Any key hits the earliest pending note and holds every hold and drag.
+    #[default]
+    Any,
+    /// Only keys in `key_lanes` and `key_nearest` are used, see [`KeyMap`](crate::judge::KeyMap).
+    Mapped,
+}
+
 #[derive(Clone, Deserialize, Serialize)]
 #[serde(default)]
 #[serde(rename_all = "camelCase")]
@@ -49,15 +60,27 @@ pub struct Config {
     pub fix_aspect_ratio: bool,
     pub fxaa: bool,
     pub interactive: bool,
+    pub judge_profile: JudgeProfile,
+    pub key_lanes: Vec<String>,
+    pub key_mode: KeyMode,
+    pub key_nearest: Vec<String>,
     pub note_scale: f32,
     pub mods: Mods,
     pub mp_enabled: bool,
     pub mp_address: String,
     pub offline_mode: bool,
     pub offset: f32,
+    /// Where live play events are written, see [`crate::overlay::sink_from_target`].
+    pub overlay_targets: Vec<String>,
+    /// Allows `tcp://` overlay targets outside of loopback. Events are sent unencrypted.
+    pub overlay_allow_remote: bool,
     pub particle: bool,
     pub player_name: String,
     pub player_rks: f32,
+    /// Port of the local control server, see [`crate::remote`]. Disabled when `None`.
+    pub remote_port: Option<u16>,
+    /// Secret clients of the control server must send in the `X-Prpr-Token` header. The server won't start without one.
+    pub remote_token: String,
     pub res_pack_path: Option<String>,
     pub sample_count: u32,
     pub show_acc: bool,
@@ -66,6 +89,8 @@ pub struct Config {
     pub volume_music: f32,
     pub volume_sfx: f32,
     pub volume_bgm: f32,
+    /// Reloads charts played from a folder whenever their files change, see [`GameScene::watch`](crate::scene::GameScene::watch).
+    pub watch_chart: bool,
     pub watermark: String,
     pub roman: bool,
     pub chinese: bool,
@@ -97,15 +122,23 @@ impl Default for Config {
             fix_aspect_ratio: false,
             fxaa: false,
             interactive: true,
+            judge_profile: JudgeProfile::STANDARD,
+            key_lanes: ["D", "F", "J", "K"].map(str::to_owned).to_vec(),
+            key_mode: KeyMode::default(),
+            key_nearest: Vec::new(),
             mods: Mods::default(),
             mp_address: "mp2.phira.cn:12345".to_owned(),
             mp_enabled: false,
             note_scale: 1.0,
             offline_mode: false,
             offset: 0.,
+            overlay_targets: Vec::new(),
+            overlay_allow_remote: false,
             particle: true,
             player_name: "Guest".to_string(),
             player_rks: 15.,
+            remote_port: None,
+            remote_token: String::new(),
             res_pack_path: None,
             sample_count: 1,
             show_acc: false,
@@ -114,6 +147,7 @@ impl Default for Config {
             volume_music: 1.,
             volume_sfx: 1.,
             volume_bgm: 1.,
+            watch_chart: false,
             watermark: "".to_string(),
             roman: false,
             chinese: false,
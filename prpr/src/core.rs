@@ -13,13 +13,13 @@ mod anim;
 pub use anim::{Anim, AnimFloat, AnimVector, Keyframe};
 
 mod chart;
-pub use chart::{Chart, ChartExtra, ChartSettings};
+pub use chart::{Chart, ChartExtra, ChartSettings, HitSoundMapping};
 
 mod effect;
-pub use effect::{Effect, Uniform};
+pub use effect::{Effect, ParamType, PresetParam, ShaderPreset, Uniform};
 
 mod line;
-pub use line::{JudgeLine, JudgeLineCache, JudgeLineKind, UIElement};
+pub use line::{EventKind, JudgeLine, JudgeLineCache, JudgeLineKind, LinkedEvent, UIElement};
 
 mod note;
 use macroquad::prelude::set_pc_assets_folder;
@@ -32,13 +32,16 @@ mod render;
 pub use render::{copy_fbo, internal_id, MSRenderTarget};
 
 mod resource;
-pub use resource::{NoteStyle, ParticleEmitter, ResPackInfo, Resource, ResourcePack, DPI_VALUE};
+pub use resource::{NoteStyle, ParticleEmitter, ResPackIcons, ResPackInfo, Resource, ResourcePack, DPI_VALUE};
 
 mod smooth;
 pub use smooth::Smooth;
 
 mod tween;
-pub use tween::{easing_from, BezierTween, ClampedTween, StaticTween, TweenFunction, TweenId, TweenMajor, TweenMinor, Tweenable, TWEEN_FUNCTIONS};
+pub use tween::{
+    easing_from, BezierTween, ClampedTween, PiecewiseTween, RangedTween, SpringTween, StaticTween, StepsTween, TweenFunction, TweenId, TweenMajor,
+    TweenMinor, TweenRegistry, Tweenable, TWEEN_FUNCTIONS,
+};
 
 mod video;
 pub use video::Video;
@@ -55,7 +58,7 @@ pub fn init_assets() {
     set_pc_assets_folder("assets");
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, serde::Serialize)]
 pub struct Triple(i32, u32, u32);
 impl Default for Triple {
     fn default() -> Self {
@@ -67,6 +70,22 @@ impl Triple {
     pub fn beats(&self) -> f32 {
         self.0 as f32 + self.1 as f32 / self.2 as f32
     }
+
+    /// Finds a fraction with a small denominator close enough to `beats`.
+    pub fn from_beats(beats: f32) -> Self {
+        const MAX_DENOMINATOR: u32 = 10000;
+        let whole = beats.floor();
+        let frac = beats - whole;
+        let den = (1..=64)
+            .find(|den| ((frac * *den as f32).round() / *den as f32 - frac).abs() < 1e-4)
+            .unwrap_or(MAX_DENOMINATOR);
+        let num = (frac * den as f32).round() as u32;
+        if num == den {
+            Self(whole as i32 + 1, 0, 1)
+        } else {
+            Self(whole as i32, num, den)
+        }
+    }
 }
 
 #[derive(Default)] // the default is a dummy
@@ -92,6 +111,11 @@ impl BpmList {
         BpmList { elements, cursor: 0 }
     }
 
+    /// The `(beat, bpm)` pairs this list was built from.
+    pub fn ranges(&self) -> Vec<(f32, f32)> {
+        self.elements.iter().map(|(beats, _, bpm)| (*beats, *bpm)).collect()
+    }
+
     pub fn time_beats(&mut self, beats: f32) -> f32 {
         while let Some(kf) = self.elements.get(self.cursor + 1) {
             if kf.0 > beats {
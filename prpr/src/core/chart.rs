@@ -1,15 +1,33 @@
-use super::{BpmList, Effect, JudgeLine, JudgeLineKind, Matrix, Resource, UIElement, Vector, Video};
+use super::{BpmList, Effect, JudgeLine, JudgeLineKind, Matrix, Resource, TweenRegistry, UIElement, Vector, Video};
 use crate::{fs::FileSystem, judge::JudgeStatus, ui::Ui};
 use anyhow::{Context, Result};
 use macroquad::prelude::*;
-use std::cell::RefCell;
+use sasa::AudioClip;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
 use tracing::warn;
 
+/// A custom hit sound `extra.json` assigns to the notes at `beat`, on every line if `line` is `None`.
+pub struct HitSoundMapping {
+    pub line: Option<usize>,
+    pub beat: f32,
+    /// `beat` converted with the BPM list of `extra.json`, for lines without a BPM list of their own.
+    pub time: f32,
+    pub path: String,
+}
+
 #[derive(Default)]
 pub struct ChartExtra {
     pub effects: Vec<Effect>,
     pub global_effects: Vec<Effect>,
     pub videos: Vec<Video>,
+    /// Custom easings declared in `extra.json`.
+    pub tweens: TweenRegistry,
+    pub hitsounds: Vec<HitSoundMapping>,
+    /// The `extra.json` this was parsed from, kept so the chart can be written back.
+    pub source: Option<String>,
 }
 
 #[derive(Default)]
@@ -24,9 +42,14 @@ pub struct Chart {
     pub bpm_list: RefCell<BpmList>,
     pub settings: ChartSettings,
     pub extra: ChartExtra,
+    /// Names of RPE's judge line groups, which only organize lines in the editor.
+    pub line_groups: Vec<String>,
 
     pub order: Vec<usize>,
     pub attach_ui: [Option<usize>; 8],
+
+    /// Custom hit sounds of notes, keyed by their paths.
+    pub hitsounds: HashMap<String, AudioClip>,
 }
 
 impl Chart {
@@ -49,9 +72,12 @@ impl Chart {
             bpm_list: RefCell::new(bpm_list),
             settings,
             extra,
+            line_groups: Vec::new(),
 
             order,
             attach_ui,
+
+            hitsounds: HashMap::new(),
         }
     }
 
@@ -78,6 +104,57 @@ impl Chart {
         Ok(())
     }
 
+    /// Assigns the hit sounds mapped in `extra.json` to the notes on their beats.
+    ///
+    /// `line_bpm` holds the BPM list of every line when the format has them (e.g. RPE with `bpmfactor`), since note times
+    /// only match the mapped beat on the line's own list. Without it, the times converted by `extra.json` are used.
+    pub fn map_hitsounds(&mut self, mut line_bpm: Option<&mut [BpmList]>) {
+        for mapping in &self.extra.hitsounds {
+            if mapping.line.map_or(false, |it| it >= self.lines.len()) {
+                warn!("hit sound {} is mapped to line #{}, which does not exist", mapping.path, mapping.line.unwrap());
+                continue;
+            }
+            for (id, line) in self.lines.iter_mut().enumerate().filter(|(id, _)| mapping.line.map_or(true, |it| it == *id)) {
+                let time = match &mut line_bpm {
+                    Some(bpm) => bpm[id].time_beats(mapping.beat),
+                    None => mapping.time,
+                };
+                line.notes
+                    .iter_mut()
+                    .filter(|note| (note.time - time).abs() < 1e-3)
+                    .for_each(|note| note.hitsound = Some(mapping.path.clone()));
+            }
+        }
+    }
+
+    /// Loads every custom hit sound. Notes whose sound can't be loaded fall back to the default one.
+    pub async fn load_hitsounds(&mut self, fs: &mut dyn FileSystem) {
+        let mut failed = HashSet::new();
+        for path in self.lines.iter().flat_map(|it| it.notes.iter()).filter_map(|it| it.hitsound.as_ref()) {
+            if self.hitsounds.contains_key(path) || failed.contains(path) {
+                continue;
+            }
+            let clip = match fs.load_file(path).await {
+                Ok(data) => AudioClip::new(data),
+                Err(err) => Err(err),
+            };
+            match clip {
+                Ok(clip) => {
+                    self.hitsounds.insert(path.clone(), clip);
+                }
+                Err(err) => {
+                    warn!("failed to load hit sound {path}, using the default one: {err:?}");
+                    failed.insert(path.clone());
+                }
+            }
+        }
+        for note in self.lines.iter_mut().flat_map(|it| it.notes.iter_mut()) {
+            if note.hitsound.as_ref().map_or(false, |it| failed.contains(it)) {
+                note.hitsound = None;
+            }
+        }
+    }
+
     pub fn reset(&mut self) {
         self.lines
             .iter_mut()
@@ -98,10 +175,10 @@ impl Chart {
         for (line, tr) in self.lines.iter_mut().zip(trs) {
             line.update(res, tr, &mut guard);
         }
-        drop(guard);
         for effect in &mut self.extra.effects {
-            effect.update(res);
+            effect.update(res, &mut guard, &self.lines);
         }
+        drop(guard);
         for video in &mut self.extra.videos {
             if let Err(err) = video.update(res.time) {
                 warn!("video error: {err:?}");
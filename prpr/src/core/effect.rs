@@ -1,25 +1,161 @@
-use super::{Anim, Resource, Tweenable};
-use crate::ext::{get_viewport, screen_aspect};
+use super::{Anim, BpmList, JudgeLine, Resource, Tweenable};
+use crate::ext::{get_viewport, nalgebra_to_glm, screen_aspect, SafeTexture};
 use anyhow::{anyhow, bail, Result};
 use macroquad::prelude::*;
 use miniquad::UniformType;
 use once_cell::sync::Lazy;
-use phf::phf_map;
 use regex::Regex;
 use std::{collections::HashSet, ops::Range};
 
-static SHADERS: phf::Map<&'static str, &'static str> = phf_map! {
-    "chromatic" => include_str!("shaders/chromatic.glsl"),
-    "circleBlur" => include_str!("shaders/circle_blur.glsl"),
-    "fisheye" => include_str!("shaders/fisheye.glsl"),
-    "glitch" => include_str!("shaders/glitch.glsl"),
-    "grayscale" => include_str!("shaders/grayscale.glsl"),
-    "noise" => include_str!("shaders/noise.glsl"),
-    "pixel" => include_str!("shaders/pixel.glsl"),
-    "radialBlur" => include_str!("shaders/radial_blur.glsl"),
-    "shockwave" => include_str!("shaders/shockwave.glsl"),
-    "vignette" => include_str!("shaders/vignette.glsl"),
-};
+/// Type of a preset shader parameter as written in `extra.json`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParamType {
+    Float,
+    /// A float uniform that only makes sense with whole numbers, like a sample count.
+    Int,
+    Vec2,
+    Color,
+}
+
+impl ParamType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Float => "float",
+            Self::Int => "int",
+            Self::Vec2 => "vec2",
+            Self::Color => "color",
+        }
+    }
+}
+
+/// A shader parameter, declared by an annotation after its uniform:
+///
+/// ```glsl
+/// uniform float sampleCount; // %3% int 1..64 Number of colour slices sampled
+/// ```
+///
+/// The default value between `%`s is required, the rest is optional: `int` marks a float that only takes whole numbers, then come the
+/// inclusive bounds of a scalar and a description.
+#[derive(Debug)]
+pub struct PresetParam {
+    pub name: String,
+    pub kind: ParamType,
+    /// The default value, as written in the annotation.
+    pub default: String,
+    /// Inclusive bounds of valid values, for scalar parameters.
+    pub range: Option<(f32, f32)>,
+    pub description: String,
+}
+
+impl PresetParam {
+    /// Parses the annotated uniforms of `shader`.
+    pub fn parse_all(shader: &str) -> Result<Vec<Self>> {
+        static DEF_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"uniform\s+(\w+)\s+(\w+);\s*//\s*%([^%]+)%(.*)").unwrap());
+        static REST_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*(int\b)?\s*(?:(-?\d+(?:\.\d+)?)\.\.(-?\d+(?:\.\d+)?))?\s*(.*)$").unwrap());
+        DEF_REGEX
+            .captures_iter(shader)
+            .map(|caps| -> Result<Self> {
+                let type_name = &caps[1];
+                let rest = REST_REGEX.captures(&caps[4]).unwrap();
+                let int = rest.get(1).is_some();
+                let kind = match type_name {
+                    "float" if int => ParamType::Int,
+                    "float" => ParamType::Float,
+                    "vec2" => ParamType::Vec2,
+                    "vec4" => ParamType::Color,
+                    _ => bail!("Unknown type: {type_name}"),
+                };
+                if int && kind != ParamType::Int {
+                    bail!("Only floats can be int");
+                }
+                let range = match (rest.get(2), rest.get(3)) {
+                    (Some(min), Some(max)) => Some((min.as_str().parse()?, max.as_str().parse()?)),
+                    _ => None,
+                };
+                Ok(Self {
+                    name: caps[2].to_owned(),
+                    kind,
+                    default: caps[3].to_owned(),
+                    range,
+                    description: rest[4].trim().to_owned(),
+                })
+            })
+            .collect()
+    }
+
+    fn default_uniform(&self) -> Result<Box<dyn Uniform>> {
+        let name = self.name.clone();
+        let value = self.default.as_str();
+        Ok(match self.kind {
+            ParamType::Float | ParamType::Int => Box::new((name, value.trim().parse::<f32>()?)),
+            ParamType::Vec2 => Box::new((name, {
+                let (x, y) = value.split_once(',').ok_or_else(|| anyhow!("Expected x,y"))?;
+                vec2(x.trim().parse()?, y.trim().parse()?)
+            })),
+            ParamType::Color => Box::new((name, {
+                let values: Vec<_> = value.split(',').map(|it| it.trim()).collect();
+                if values.len() != 4 {
+                    bail!("Expected r,g,b,a");
+                }
+                Color::new(values[0].parse()?, values[1].parse()?, values[2].parse()?, values[3].parse()?)
+            })),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ShaderPreset {
+    pub name: &'static str,
+    pub source: &'static str,
+    pub description: &'static str,
+    /// Parsed from the annotations in `source`, see [`PresetParam`].
+    pub params: Vec<PresetParam>,
+}
+
+impl ShaderPreset {
+    pub fn param(&self, name: &str) -> Option<&PresetParam> {
+        self.params.iter().find(|it| it.name == name)
+    }
+}
+
+static PRESETS: Lazy<Vec<ShaderPreset>> = Lazy::new(|| {
+    [
+        (
+            "chromatic",
+            include_str!("shaders/chromatic.glsl"),
+            "Chromatic aberration spreading colours away from the centre",
+        ),
+        (
+            "circleBlur",
+            include_str!("shaders/circle_blur.glsl"),
+            "Replaces each pixel with the brightest one within a circle",
+        ),
+        ("fisheye", include_str!("shaders/fisheye.glsl"), "Fisheye lens distortion"),
+        (
+            "glitch",
+            include_str!("shaders/glitch.glsl"),
+            "Randomly shifts horizontal blocks and splits colour channels",
+        ),
+        ("grayscale", include_str!("shaders/grayscale.glsl"), "Desaturates the screen"),
+        ("noise", include_str!("shaders/noise.glsl"), "Jitters pixels randomly"),
+        ("pixel", include_str!("shaders/pixel.glsl"), "Pixelates the screen"),
+        ("radialBlur", include_str!("shaders/radial_blur.glsl"), "Zoom blur towards a centre"),
+        (
+            "shockwave",
+            include_str!("shaders/shockwave.glsl"),
+            "A ring-shaped distortion expanding from a centre",
+        ),
+        ("vignette", include_str!("shaders/vignette.glsl"), "Darkens the edges of the screen"),
+    ]
+    .into_iter()
+    .map(|(name, source, description)| ShaderPreset {
+        name,
+        source,
+        description,
+        params: PresetParam::parse_all(source).unwrap_or_else(|err| panic!("invalid annotations in preset {name}: {err:?}")),
+    })
+    .collect()
+});
 
 pub trait UniformValue: Clone + Default {
     const UNIFORM_TYPE: UniformType;
@@ -69,45 +205,17 @@ impl<T: UniformValue + Tweenable> Uniform for (String, Anim<T>) {
     }
 }
 
-pub struct Effect {
-    time_range: Range<f32>,
-    t: f32,
+struct Pass {
     material: Material,
     defaults: Vec<Box<dyn Uniform>>,
-    uniforms: Vec<Box<dyn Uniform>>,
-    pub global: bool,
 }
 
-impl Effect {
-    pub fn get_preset(name: &str) -> Option<&'static str> {
-        SHADERS.get(name).copied()
-    }
-
-    pub fn new(time_range: Range<f32>, shader: &str, uniforms: Vec<Box<dyn Uniform>>, global: bool) -> Result<Self> {
-        static DEF_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"uniform\s+(\w+)\s+(\w+);\s+//\s+%([^%]+)%").unwrap());
-        let defaults = DEF_REGEX
-            .captures_iter(shader)
-            .map(|caps| -> Result<Box<dyn Uniform>> {
-                let type_name = caps.get(1).unwrap().as_str();
-                let name = caps.get(2).unwrap().as_str().to_owned();
-                let value = caps.get(3).unwrap().as_str();
-                Ok(match type_name {
-                    "float" => Box::new((name, value.parse::<f32>()?)),
-                    "vec2" => Box::new((name, {
-                        let (x, y) = value.split_once(',').ok_or_else(|| anyhow!("Expected x,y"))?;
-                        vec2(x.trim().parse()?, y.trim().parse()?)
-                    })),
-                    "vec4" => Box::new((name, {
-                        let values: Vec<_> = value.split(',').map(|it| it.trim()).collect();
-                        if values.len() != 4 {
-                            bail!("Expected r,g,b,a");
-                        }
-                        Color::new(values[0].parse()?, values[1].parse()?, values[2].parse()?, values[3].parse()?)
-                    })),
-                    _ => bail!("Unknown type: {type_name}"),
-                })
-            })
-            .collect::<Result<Vec<Box<dyn Uniform>>>>()?;
+impl Pass {
+    fn new(shader: &str, uniforms: &[Box<dyn Uniform>], textures: &[(String, SafeTexture)]) -> Result<Self> {
+        let defaults = PresetParam::parse_all(shader)?
+            .iter()
+            .map(PresetParam::default_uniform)
+            .collect::<Result<Vec<_>>>()?;
         let mut ocurred_uniforms = HashSet::new();
         let mut new_uniforms = Vec::new();
         let mut add_uniform = |(name, its_type): (String, UniformType)| {
@@ -121,34 +229,109 @@ impl Effect {
         add_uniform(("time".to_owned(), UniformType::Float1));
         add_uniform(("screenSize".to_owned(), UniformType::Float2));
         add_uniform(("UVScale".to_owned(), UniformType::Float2));
-        for u in &uniforms {
+        add_uniform(("beat".to_owned(), UniformType::Float1));
+        add_uniform(("bpm".to_owned(), UniformType::Float1));
+        add_uniform(("combo".to_owned(), UniformType::Float1));
+        add_uniform(("lineTransform".to_owned(), UniformType::Mat4));
+        for u in uniforms {
             add_uniform(u.uniform_pair());
         }
         Ok(Self {
-            time_range,
-            t: f32::NEG_INFINITY,
-            defaults,
             material: load_material(
                 VERTEX_SHADER,
                 shader,
                 MaterialParams {
                     uniforms: new_uniforms,
-                    textures: vec!["screenTexture".to_owned()],
+                    textures: std::iter::once("screenTexture".to_owned())
+                        .chain(textures.iter().map(|it| it.0.clone()))
+                        .collect(),
                     ..Default::default()
                 },
             )?,
+            defaults,
+        })
+    }
+}
+
+/// A post-processing shader. Besides `screenTexture`, every pass gets the built-in uniforms `time`,
+/// `screenSize`, `UVScale`, `beat`, `bpm`, `combo` and `lineTransform`.
+pub struct Effect {
+    time_range: Range<f32>,
+    t: f32,
+    passes: Vec<Pass>,
+    uniforms: Vec<Box<dyn Uniform>>,
+    textures: Vec<(String, SafeTexture)>,
+    beat: f32,
+    bpm: f32,
+    combo: f32,
+    line_transform: Mat4,
+    pub global: bool,
+    /// The judge line whose transform is passed as `lineTransform`.
+    pub line: Option<usize>,
+}
+
+impl Effect {
+    pub fn get_preset(name: &str) -> Option<&'static str> {
+        Self::preset(name).map(|it| it.source)
+    }
+
+    pub fn preset(name: &str) -> Option<&'static ShaderPreset> {
+        PRESETS.iter().find(|it| it.name == name)
+    }
+
+    /// Every preset shader along with its parameters, e.g. for autocompletion in editors.
+    pub fn presets() -> &'static [ShaderPreset] {
+        &PRESETS
+    }
+
+    pub fn new(time_range: Range<f32>, shader: &str, uniforms: Vec<Box<dyn Uniform>>, global: bool) -> Result<Self> {
+        Self::multi_pass(time_range, &[shader], uniforms, Vec::new(), global)
+    }
+
+    /// Creates an effect running `shaders` one after another, each reading the output of the previous one
+    /// from `screenTexture`. `textures` are bound as extra samplers in every pass.
+    pub fn multi_pass(
+        time_range: Range<f32>,
+        shaders: &[&str],
+        uniforms: Vec<Box<dyn Uniform>>,
+        textures: Vec<(String, SafeTexture)>,
+        global: bool,
+    ) -> Result<Self> {
+        if shaders.is_empty() {
+            bail!("Expected at least one pass");
+        }
+        Ok(Self {
+            time_range,
+            t: f32::NEG_INFINITY,
+            passes: shaders
+                .iter()
+                .map(|shader| Pass::new(shader, &uniforms, &textures))
+                .collect::<Result<_>>()?,
             uniforms,
+            textures,
+            beat: 0.,
+            bpm: 0.,
+            combo: 0.,
+            line_transform: Mat4::IDENTITY,
             global,
+            line: None,
         })
     }
 
-    pub fn update(&mut self, res: &Resource) {
+    pub fn update(&mut self, res: &Resource, bpm_list: &mut BpmList, lines: &[JudgeLine]) {
         let t = res.time;
         self.t = t;
         if self.time_range.contains(&t) {
             for uniform in &mut self.uniforms {
                 uniform.set_time(t);
             }
+            self.beat = bpm_list.beat(t);
+            self.bpm = bpm_list.now_bpm(t);
+            self.combo = res.combo as f32;
+            self.line_transform = self
+                .line
+                .and_then(|id| lines.get(id))
+                .map_or(Mat4::IDENTITY, |line| nalgebra_to_glm(&line.now_transform(res, lines)));
         }
     }
 
@@ -157,36 +340,48 @@ impl Effect {
             return;
         }
         let mut gl = unsafe { get_internal_gl() };
-        gl.flush();
+        for pass in &self.passes {
+            gl.flush();
 
-        for def in &self.defaults {
-            def.apply(&self.material);
-        }
-        for uniform in &self.uniforms {
-            uniform.apply(&self.material);
+            let material = pass.material;
+            for def in &pass.defaults {
+                def.apply(&material);
+            }
+            for uniform in &self.uniforms {
+                uniform.apply(&material);
+            }
+            material.set_uniform("time", self.t);
+            material.set_uniform("beat", self.beat);
+            material.set_uniform("bpm", self.bpm);
+            material.set_uniform("combo", self.combo);
+            material.set_uniform("lineTransform", self.line_transform);
+            for (name, tex) in &self.textures {
+                material.set_texture(name, **tex);
+            }
+            let target = res.chart_target.as_mut().unwrap();
+            target.swap();
+            let tex = target.old().texture;
+            material.set_texture("screenTexture", tex);
+            let screen_dim = vec2(tex.width(), tex.height());
+            material.set_uniform("screenSize", screen_dim);
+            gl.quad_gl.render_pass(Some(target.output().render_pass));
+
+            let vp = get_viewport();
+            material.set_uniform("UVScale", vec2(vp.2 as _, vp.3 as _) / screen_dim);
+
+            gl_use_material(material);
+            let top = 1. / if self.global { screen_aspect() } else { res.aspect_ratio };
+            draw_rectangle(-1., -top, 2., top * 2., WHITE);
         }
-        self.material.set_uniform("time", self.t);
-        let target = res.chart_target.as_mut().unwrap();
-        target.swap();
-        let tex = target.old().texture;
-        self.material.set_texture("screenTexture", tex);
-        let screen_dim = vec2(tex.width(), tex.height());
-        self.material.set_uniform("screenSize", screen_dim);
-        gl.quad_gl.render_pass(Some(target.output().render_pass));
-
-        let vp = get_viewport();
-        self.material.set_uniform("UVScale", vec2(vp.2 as _, vp.3 as _) / screen_dim);
-
-        gl_use_material(self.material);
-        let top = 1. / if self.global { screen_aspect() } else { res.aspect_ratio };
-        draw_rectangle(-1., -top, 2., top * 2., WHITE);
         gl_use_default_material();
     }
 }
 
 impl Drop for Effect {
     fn drop(&mut self) {
-        self.material.delete();
+        for pass in &self.passes {
+            pass.material.delete();
+        }
     }
 }
 
@@ -205,3 +400,50 @@ void main() {
     gl_Position = Projection * Model * vec4(position, 1);
     uv = (texcoord - vec2(0.5)) * UVScale + vec2(0.5);
 }"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets() {
+        for preset in Effect::presets() {
+            assert!(!preset.params.is_empty(), "{} has no parameters", preset.name);
+            for param in &preset.params {
+                assert!(!param.description.is_empty(), "{}.{} has no description", preset.name, param.name);
+                param.default_uniform().unwrap();
+            }
+        }
+        let param = Effect::preset("chromatic").unwrap().param("sampleCount").unwrap();
+        assert_eq!(param.kind, ParamType::Int);
+        assert_eq!(param.range, Some((1., 64.)));
+        assert_eq!(param.default, "3");
+        assert_eq!(param.description, "Number of colour slices sampled");
+        let param = Effect::preset("vignette").unwrap().param("color").unwrap();
+        assert_eq!(param.kind, ParamType::Color);
+        assert_eq!(param.range, None);
+    }
+
+    #[test]
+    fn annotations() {
+        let params = PresetParam::parse_all(
+            "uniform float a; // %1.5%\n\
+             uniform float b;// %-2% -3.5..0.5\n\
+             uniform vec2 c; // %0.5, 0.5% Where it is\n\
+             uniform float d; // %4% int 0..8 Steps\n\
+             uniform float untouched;\n",
+        )
+        .unwrap();
+        let summary: Vec<_> = params.iter().map(|it| (it.name.as_str(), it.kind, it.range, it.description.as_str())).collect();
+        let expected: [(&str, ParamType, Option<(f32, f32)>, &str); 4] = [
+            ("a", ParamType::Float, None, ""),
+            ("b", ParamType::Float, Some((-3.5, 0.5)), ""),
+            ("c", ParamType::Vec2, None, "Where it is"),
+            ("d", ParamType::Int, Some((0., 8.)), "Steps"),
+        ];
+        assert_eq!(summary, expected);
+        assert_eq!(params[2].default, "0.5, 0.5");
+        assert!(PresetParam::parse_all("uniform vec2 a; // %0, 0% int").is_err());
+        assert!(PresetParam::parse_all("uniform mat4 a; // %0%").is_err());
+    }
+}
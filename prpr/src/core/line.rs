@@ -2,16 +2,16 @@ use super::{chart::ChartSettings, object::CtrlObject, Anim, AnimFloat, BpmList,
 use crate::{
     config::Mods,
     ext::{draw_text_aligned, get_viewport, NotNanExt, SafeTexture},
-    judge::{JudgeStatus, LIMIT_BAD},
+    judge::JudgeStatus,
     ui::Ui,
 };
 use macroquad::prelude::*;
 use miniquad::{RenderPass, Texture, TextureParams, TextureWrap};
 use nalgebra::Rotation2;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 
-#[derive(Clone, Copy, Deserialize)]
+#[derive(Clone, Copy, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 #[repr(u8)]
 pub enum UIElement {
@@ -40,6 +40,52 @@ impl UIElement {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EventKind {
+    Alpha,
+    MoveX,
+    MoveY,
+    Rotate,
+    Speed,
+    Color,
+    Text,
+    ScaleX,
+    ScaleY,
+    Incline,
+    Paint,
+}
+
+impl EventKind {
+    pub fn from_u8(val: u8) -> Option<Self> {
+        Some(match val {
+            0 => Self::Alpha,
+            1 => Self::MoveX,
+            2 => Self::MoveY,
+            3 => Self::Rotate,
+            4 => Self::Speed,
+            5 => Self::Color,
+            6 => Self::Text,
+            7 => Self::ScaleX,
+            8 => Self::ScaleY,
+            9 => Self::Incline,
+            10 => Self::Paint,
+            _ => return None,
+        })
+    }
+}
+
+/// Puts an RPE event into a link group, so that the editor edits it together with the rest of the group.
+///
+/// Link groups don't affect playback and are only kept to export them back.
+#[derive(Clone)]
+pub struct LinkedEvent {
+    pub kind: EventKind,
+    /// Start time of the event, in seconds.
+    pub time: f32,
+    pub group: i32,
+}
+
 #[derive(Default)]
 pub enum JudgeLineKind {
     #[default]
@@ -111,6 +157,9 @@ pub struct JudgeLine {
     pub z_index: i32,
     pub show_below: bool,
     pub attach_ui: Option<UIElement>,
+    /// Index into [`Chart::line_groups`](super::Chart::line_groups).
+    pub group: usize,
+    pub link_groups: Vec<LinkedEvent>,
 
     pub cache: JudgeLineCache,
 }
@@ -189,7 +238,22 @@ impl JudgeLine {
                             color.a = 0.10 + 0.90 * color.a;
                         }
                         let len = res.info.line_length;
-                        draw_line(-len, 0., len, 0., 0.0075, color);
+                        if let Some(texture) = &res.res_pack.judge_line {
+                            let h = len * 2. * texture.height() / texture.width();
+                            draw_texture_ex(
+                                **texture,
+                                -len,
+                                -h / 2.,
+                                color,
+                                DrawTextureParams {
+                                    dest_size: Some(vec2(len * 2., h)),
+                                    flip_y: true,
+                                    ..Default::default()
+                                },
+                            );
+                        } else {
+                            draw_line(-len, 0., len, 0., 0.0075, color);
+                        }
                     }
                     JudgeLineKind::Texture(texture, _) => {
                         let mut color = color.unwrap_or(WHITE);
@@ -289,7 +353,7 @@ impl JudgeLine {
                 incline_sin: self.incline.now_opt().map(|it| it.to_radians().sin()).unwrap_or_default(),
             };
             if res.config.has_mod(Mods::FADE_OUT) {
-                config.invisible_time = LIMIT_BAD;
+                config.invisible_time = res.config.judge_profile.bad;
             }
             if alpha < 0.0 {
                 if !settings.pe_alpha_extension {
@@ -48,6 +48,15 @@ pub struct Note {
     pub fake: bool,
     pub judge: JudgeStatus,
     pub format: bool,
+
+    /// Multiplied into the note's colour.
+    pub tint: Color,
+    /// Overrides the resource pack's hit effect colours.
+    pub hit_fx_color: Option<Color>,
+    /// Width of the hit zone relative to the default one.
+    pub judge_scale: f32,
+    /// Path of a custom hit sound in the chart's file system, see [`Chart::hitsounds`](super::Chart::hitsounds).
+    pub hitsound: Option<String>,
 }
 
 pub struct RenderConfig<'a> {
@@ -114,8 +123,8 @@ fn draw_tex_pts(res: &Resource, texture: Texture2D, order: i8, p: [Point; 4], co
         .push((order, texture.raw_miniquad_texture_handle().gl_internal_id()), vertices);
 }
 
-fn draw_center(res: &Resource, tex: Texture2D, order: i8, scale: f32, color: Color) {
-    let hf = vec2(scale, tex.height() * scale / tex.width());
+fn draw_center(res: &Resource, tex: Texture2D, source: Rect, order: i8, scale: f32, color: Color) {
+    let hf = vec2(scale, tex.height() * source.h * scale / (tex.width() * source.w));
     draw_tex(
         res,
         tex,
@@ -124,6 +133,7 @@ fn draw_center(res: &Resource, tex: Texture2D, order: i8, scale: f32, color: Col
         -hf.y,
         color,
         DrawTextureParams {
+            source: Some(source),
             dest_size: Some(hf * 2.),
             ..Default::default()
         },
@@ -148,6 +158,11 @@ impl Note {
         line.object.rotation.now() + if self.above { 0. } else { 180. }
     }
 
+    /// Colour of hit effects, `default` coming from the resource pack.
+    pub fn fx_color(&self, default: Color) -> Color {
+        self.hit_fx_color.map_or(default, |it| Color { a: default.a, ..it })
+    }
+
     pub fn plain(&self) -> bool {
         !self.fake && !matches!(self.kind, NoteKind::Hold { .. }) && self.object.translation.1.keyframes.len() <= 1
         // && self.ctrl_obj.is_default()
@@ -156,17 +171,17 @@ impl Note {
     pub fn update(&mut self, res: &mut Resource, parent_rot: f32, parent_tr: &Matrix, ctrl_obj: &mut CtrlObject, line_height: f32, bpm_list: &mut BpmList) {
         self.object.set_time(res.time);
         //let mut _immediate_particle = false;
-        let color = if let JudgeStatus::Hold(perfect, ref mut at, ..) = self.judge {
+        let fx = if let JudgeStatus::Hold(perfect, ref mut at, ..) = self.judge {
             if res.time >= *at {
                 //_immediate_particle = true;
                 let beat = if self.format { 30. / bpm_list.now_bpm(0.) } else { 30. / bpm_list.now_bpm(self.time) };
                 //println!("{} {} {}", bpm_list.now_bpm(0.), beat, res.config.speed);
                 *at = res.time + beat / res.config.speed; //HOLD_PARTICLE_INTERVAL
-                Some(if perfect && !res.config.all_good {
-                    res.res_pack.info.fx_perfect()
-                } else {
-                    res.res_pack.info.fx_good()
-                })
+                let good = !perfect || res.config.all_good;
+                Some((
+                    self.fx_color(if good { res.res_pack.info.fx_good() } else { res.res_pack.info.fx_perfect() }),
+                    good,
+                ))
             } else {
                 None
             }
@@ -174,14 +189,14 @@ impl Note {
             None
         };
 
-        if let Some(color) = color {
+        if let Some((color, good)) = fx {
             self.init_ctrl_obj(ctrl_obj, line_height);
             let rotation = if res.config.chart_debug { 
                 if self.above { 0. } else { 180. } } 
                 else { random_rotate() 
             };
             res.with_model(parent_tr * self.now_transform(res, ctrl_obj, 0., 0.), |res| {
-                res.emit_at_origin(parent_rot + rotation, color)
+                res.emit_at_origin(parent_rot + rotation, color, good)
             });
         }
     }
@@ -224,13 +239,16 @@ impl Note {
             return;
         }
         let scale = (if self.multiple_hint {
-            res.res_pack.note_style_mh.click.width() / res.res_pack.note_style.click.width()
+            res.res_pack.note_style_mh.frame_width() / res.res_pack.note_style.frame_width()
         } else {
             1.0
         }) * res.note_width;
         let ctrl_obj = &mut config.ctrl_obj;
         self.init_ctrl_obj(ctrl_obj, config.line_height);
         let mut color = self.object.now_color();
+        color.r *= self.tint.r;
+        color.g *= self.tint.g;
+        color.b *= self.tint.b;
         color.a *= res.alpha * ctrl_obj.alpha.now_opt().unwrap_or(1.);
         let spd = self.speed * ctrl_obj.y.now_opt().unwrap_or(1.);
         let end_spd = self.end_speed * ctrl_obj.y.now_opt().unwrap_or(1.);
@@ -275,13 +293,14 @@ impl Note {
         } else {
             &res.res_pack.note_style
         };
+        let frame = style.frame_rect(res.time);
         let draw = |res: &mut Resource, tex: Texture2D| {
             let mut color = color;
             if !config.draw_below {
                 color.a *= (self.time - res.time).min(0.) / FADEOUT_TIME + 1.;
             }
             res.with_model(self.now_transform(res, ctrl_obj, base, config.incline_sin), |res| {
-                draw_center(res, tex, order, scale, color);
+                draw_center(res, tex, frame, order, scale, color);
             });
         };
         match self.kind {
@@ -339,6 +358,7 @@ impl Note {
                     }
                     let tex = &style.hold;
                     let ratio = style.hold_ratio();
+                    let frame = style.frame_rect(res.time);
                     // body
                     // TODO (end_height - height) is not always total height
                     draw_tex(
@@ -356,11 +376,11 @@ impl Note {
                             source: Some({
                                 if res.res_pack.info.hold_repeat {
                                     let hold_body = style.hold_body.as_ref().unwrap();
-                                    let width = hold_body.width();
+                                    let width = hold_body.width() * frame.w;
                                     let height = hold_body.height();
-                                    Rect::new(0., 0., 1., (top - bottom) / scale / 2. * width / height)
+                                    Rect::new(frame.x, 0., frame.w, (top - bottom) / scale / 2. * width / height)
                                 } else {
-                                    style.hold_body_rect()
+                                    Rect { x: frame.x, ..style.hold_body_rect() }
                                 }
                             }),
                             dest_size: Some(vec2(scale * 2., top - bottom)),
@@ -370,7 +390,7 @@ impl Note {
                     );
                     // head
                     if res.time < self.time || res.res_pack.info.hold_keep_head {
-                        let r = style.hold_head_rect();
+                        let r = Rect { x: frame.x, ..style.hold_head_rect() };
                         let hf = vec2(scale, r.h / r.w * scale * ratio);
                         draw_tex(
                             res,
@@ -388,7 +408,7 @@ impl Note {
                         );
                     }
                     // tail
-                    let r = style.hold_tail_rect();
+                    let r = Rect { x: frame.x, ..style.hold_tail_rect() };
                     let hf = vec2(scale, r.h / r.w * scale * ratio);
                     draw_tex(
                         res,
@@ -439,6 +459,7 @@ impl BadNote {
                     NoteKind::Flick => *style.flick,
                     _ => unreachable!(),
                 },
+                style.frame_rect(res.time),
                 self.kind.order(),
                 res.note_width,
                 Color::new(0.423529, 0.262745, 0.262745, (self.time - res.time).max(-1.) / BAD_TIME + 1.),
@@ -1,7 +1,7 @@
-use super::{MSRenderTarget, Matrix, Point, NOTE_WIDTH_RATIO_BASE};
+use super::{Chart, MSRenderTarget, Matrix, Point, NOTE_WIDTH_RATIO_BASE};
 use crate::{
     config::Config,
-    ext::{create_audio_manger, nalgebra_to_glm, SafeTexture},
+    ext::{create_audio_manger, load_music, nalgebra_to_glm, SafeTexture},
     fs::FileSystem,
     info::ChartInfo,
     particle::{AtlasConfig, ColorCurve, Emitter, EmitterConfig},
@@ -11,7 +11,7 @@ use macroquad::prelude::*;
 use miniquad::{gl::{GLuint, GL_LINEAR}, Texture, TextureWrap};
 use sasa::{AudioClip, AudioManager, Sfx};
 use serde::Deserialize;
-use std::{cell::RefCell, collections::BTreeMap, ops::DerefMut, path::Path, sync::atomic::AtomicU32};
+use std::{cell::RefCell, collections::{BTreeMap, HashMap}, ops::DerefMut, path::Path, sync::atomic::AtomicU32};
 
 pub const MAX_SIZE: usize = 64; // needs tweaking
 pub static DPI_VALUE: AtomicU32 = AtomicU32::new(250);
@@ -51,6 +51,16 @@ fn default_tinted() -> bool {
     true
 }
 
+#[inline]
+fn default_frames() -> u32 {
+    1
+}
+
+#[inline]
+fn default_fps() -> f32 {
+    10.
+}
+
 #[allow(dead_code)]
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -69,6 +79,10 @@ pub struct ResPackInfo {
     pub hide_particles: bool,
     #[serde(default = "default_tinted")]
     pub hit_fx_tinted: bool,
+    /// Atlas of `hit_fx_perfect.png`, `hit_fx` if absent.
+    pub hit_fx_perfect: Option<(u32, u32)>,
+    /// Atlas of `hit_fx_good.png`, `hit_fx` if absent.
+    pub hit_fx_good: Option<(u32, u32)>,
 
     pub hold_atlas: (u32, u32),
     #[serde(rename = "holdAtlasMH")]
@@ -81,6 +95,12 @@ pub struct ResPackInfo {
     #[serde(default)]
     pub hold_compact: bool,
 
+    /// Number of frames laid out horizontally in every note texture, for animated notes.
+    #[serde(default = "default_frames")]
+    pub note_frames: u32,
+    #[serde(default = "default_fps")]
+    pub note_fps: f32,
+
     #[serde(default = "default_perfect")]
     pub color_perfect: u32,
     #[serde(default = "default_good")]
@@ -136,6 +156,8 @@ pub struct NoteStyle {
     pub drag: SafeTexture,
     pub hold_body: Option<SafeTexture>,
     pub hold_atlas: (u32, u32),
+    pub frames: u32,
+    pub fps: f32,
 }
 
 impl NoteStyle {
@@ -143,6 +165,9 @@ impl NoteStyle {
         if (self.hold_atlas.0 + self.hold_atlas.1) as f32 >= self.hold.height() {
             bail!("Invalid atlas");
         }
+        if self.frames == 0 {
+            bail!("Invalid frame count");
+        }
         Ok(())
     }
 
@@ -151,27 +176,49 @@ impl NoteStyle {
         t as f32 / self.hold.height()
     }
 
+    /// Width of a single frame of the click texture.
+    pub fn frame_width(&self) -> f32 {
+        self.click.width() / self.frames as f32
+    }
+
+    /// Source rect of the animation frame shown at `time`.
+    pub fn frame_rect(&self, time: f32) -> Rect {
+        let w = 1. / self.frames as f32;
+        let index = ((time * self.fps).floor() as i64).rem_euclid(self.frames as i64);
+        Rect::new(index as f32 * w, 0., w, 1.)
+    }
+
     pub fn hold_ratio(&self) -> f32 {
-        self.hold.height() / self.hold.width()
+        self.hold.height() / self.hold.width() * self.frames as f32
     }
 
     pub fn hold_head_rect(&self) -> Rect {
         let sy = self.to_uv(self.hold_atlas.1);
-        Rect::new(0., 1. - sy, 1., sy)
+        Rect::new(0., 1. - sy, 1. / self.frames as f32, sy)
     }
 
     pub fn hold_body_rect(&self) -> Rect {
         let sy = self.to_uv(self.hold_atlas.0);
         let ey = 1. - self.to_uv(self.hold_atlas.1);
-        Rect::new(0., sy, 1., ey - sy)
+        Rect::new(0., sy, 1. / self.frames as f32, ey - sy)
     }
 
     pub fn hold_tail_rect(&self) -> Rect {
         let ey = self.to_uv(self.hold_atlas.0);
-        Rect::new(0., 0., 1., ey)
+        Rect::new(0., 0., 1. / self.frames as f32, ey)
     }
 }
 
+/// Optional icons replacing the built-in ones.
+#[derive(Default)]
+pub struct ResPackIcons {
+    pub pause: Option<SafeTexture>,
+    pub back: Option<SafeTexture>,
+    pub retry: Option<SafeTexture>,
+    pub resume: Option<SafeTexture>,
+    pub proceed: Option<SafeTexture>,
+}
+
 pub struct ResourcePack {
     pub info: ResPackInfo,
     pub note_style: NoteStyle,
@@ -181,6 +228,11 @@ pub struct ResourcePack {
     pub sfx_flick: AudioClip,
     pub ending: AudioClip,
     pub hit_fx: SafeTexture,
+    pub hit_fx_perfect: Option<SafeTexture>,
+    pub hit_fx_good: Option<SafeTexture>,
+    /// Texture of normal judge lines, tinted with the line colour.
+    pub judge_line: Option<SafeTexture>,
+    pub icons: ResPackIcons,
 }
 
 impl ResourcePack {
@@ -202,6 +254,14 @@ impl ResourcePack {
                 SafeTexture::from(image::load_from_memory(&fs.load_file($path).await.with_context(|| format!("Missing {}", $path))?)?).with_filter(GL_LINEAR)
             };
         }
+        macro_rules! load_opt_tex {
+            ($path:literal) => {
+                match fs.load_file($path).await.ok() {
+                    Some(data) => Some(SafeTexture::from(image::load_from_memory(&data).with_context(|| format!("Invalid {}", $path))?).with_filter(GL_LINEAR)),
+                    None => None,
+                }
+            };
+        }
         let info: ResPackInfo = serde_yaml::from_str(&String::from_utf8(fs.load_file("info.yml").await.context("Missing info.yml")?)?)?;
         let mut note_style = NoteStyle {
             click: load_tex!("click.png"),
@@ -210,6 +270,8 @@ impl ResourcePack {
             drag: load_tex!("drag.png"),
             hold_body: None,
             hold_atlas: info.hold_atlas,
+            frames: info.note_frames,
+            fps: info.note_fps,
         };
         note_style.verify()?;
         let mut note_style_mh = NoteStyle {
@@ -219,6 +281,8 @@ impl ResourcePack {
             drag: load_tex!("drag_mh.png"),
             hold_body: None,
             hold_atlas: info.hold_atlas_mh,
+            frames: info.note_frames,
+            fps: info.note_fps,
         };
         note_style_mh.verify()?;
         if info.hold_repeat {
@@ -240,6 +304,16 @@ impl ResourcePack {
             get_body(&mut note_style_mh);
         }
         let hit_fx = image::load_from_memory(&fs.load_file("hit_fx.png").await.context("Missing hit_fx.png")?)?.into();
+        let hit_fx_perfect = load_opt_tex!("hit_fx_perfect.png");
+        let hit_fx_good = load_opt_tex!("hit_fx_good.png");
+        let judge_line = load_opt_tex!("judge_line.png");
+        let icons = ResPackIcons {
+            pause: load_opt_tex!("pause.png"),
+            back: load_opt_tex!("back.png"),
+            retry: load_opt_tex!("retry.png"),
+            resume: load_opt_tex!("resume.png"),
+            proceed: load_opt_tex!("proceed.png"),
+        };
 
         macro_rules! load_clip {
             ($path:literal) => {
@@ -259,6 +333,10 @@ impl ResourcePack {
             sfx_flick: load_clip!("flick.ogg"),
             ending: load_clip!("ending.ogg"),
             hit_fx,
+            hit_fx_perfect,
+            hit_fx_good,
+            judge_line,
+            icons,
         })
     }
 }
@@ -266,6 +344,7 @@ impl ResourcePack {
 pub struct ParticleEmitter {
     scale: f32,
     emitter: Emitter,
+    emitter_good: Emitter,
     emitter_square: Emitter,
     hide_particles: bool,
 }
@@ -280,21 +359,29 @@ impl ParticleEmitter {
             end.a = 0.;
             ColorCurve { start, mid, end }
         };
-        let mut res = Self {
-            scale: res_pack.info.hit_fx_scale,
-            emitter: Emitter::new(EmitterConfig {
+        let hit_fx = |texture: &Option<SafeTexture>, atlas: Option<(u32, u32)>| {
+            let (texture, atlas) = match texture {
+                Some(texture) => (texture, atlas.unwrap_or(res_pack.info.hit_fx)),
+                None => (&res_pack.hit_fx, res_pack.info.hit_fx),
+            };
+            Emitter::new(EmitterConfig {
                 local_coords: false,
-                texture: Some(*res_pack.hit_fx),
+                texture: Some(**texture),
                 lifetime: res_pack.info.hit_fx_duration,
                 lifetime_randomness: 0.0,
                 initial_rotation_randomness: 0.0,
                 initial_direction_spread: 0.0,
                 initial_velocity: 0.0,
-                atlas: Some(AtlasConfig::new(res_pack.info.hit_fx.0 as _, res_pack.info.hit_fx.1 as _, ..)),
+                atlas: Some(AtlasConfig::new(atlas.0 as _, atlas.1 as _, ..)),
                 emitting: false,
                 colors_curve,
                 ..Default::default()
-            }),
+            })
+        };
+        let mut res = Self {
+            scale: res_pack.info.hit_fx_scale,
+            emitter: hit_fx(&res_pack.hit_fx_perfect, res_pack.info.hit_fx_perfect),
+            emitter_good: hit_fx(&res_pack.hit_fx_good, res_pack.info.hit_fx_good),
             emitter_square: Emitter::new(EmitterConfig {
                 local_coords: false,
                 lifetime: res_pack.info.hit_fx_duration,
@@ -314,10 +401,12 @@ impl ParticleEmitter {
         Ok(res)
     }
 
-    pub fn emit_at(&mut self, pt: Vec2, rotation: f32, color: Color) {
-        self.emitter.config.initial_rotation = rotation;
-        self.emitter.config.base_color = color;
-        self.emitter.emit(pt, 1);
+    /// Emits a hit effect, using the good sheet of the resource pack if `good` is set.
+    pub fn emit_at(&mut self, pt: Vec2, rotation: f32, color: Color, good: bool) {
+        let emitter = if good { &mut self.emitter_good } else { &mut self.emitter };
+        emitter.config.initial_rotation = rotation;
+        emitter.config.base_color = color;
+        emitter.emit(pt, 1);
         if !self.hide_particles {
             self.emitter_square.config.base_color = color;
             self.emitter_square.emit(pt, 4);
@@ -326,11 +415,13 @@ impl ParticleEmitter {
 
     pub fn draw(&mut self, dt: f32) {
         self.emitter.draw(vec2(0., 0.), dt);
+        self.emitter_good.draw(vec2(0., 0.), dt);
         self.emitter_square.draw(vec2(0., 0.), dt);
     }
 
     pub fn set_scale(&mut self, scale: f32) {
         self.emitter.config.size = self.scale * scale / 5.;
+        self.emitter_good.config.size = self.scale * scale / 5.;
         self.emitter_square.config.size = self.scale * scale / 44.;
     }
 }
@@ -376,6 +467,8 @@ pub struct Resource {
 
     pub alpha: f32,
     pub judge_line_color: Color,
+    /// The player's current combo, exposed to shaders.
+    pub combo: u32,
 
     pub camera: Camera2D,
 
@@ -389,6 +482,8 @@ pub struct Resource {
     pub icon_retry: SafeTexture,
     pub icon_resume: SafeTexture,
     pub icon_proceed: SafeTexture,
+    /// Pause button from the resource pack, bars are drawn if absent.
+    pub icon_pause: Option<SafeTexture>,
 
     pub emitter: ParticleEmitter,
 
@@ -398,6 +493,8 @@ pub struct Resource {
     pub sfx_click: Sfx,
     pub sfx_drag: Sfx,
     pub sfx_flick: Sfx,
+    /// Custom hit sounds of the chart's notes, see [`Chart::hitsounds`].
+    pub hitsounds: HashMap<String, Sfx>,
 
     pub chart_target: Option<MSRenderTarget>,
     pub no_effect: bool,
@@ -475,7 +572,7 @@ impl Resource {
         };
 
         let mut audio = create_audio_manger(&config)?;
-        let music = AudioClip::new(fs.load_file(&info.music).await?)?;
+        let music = load_music(fs.load_file(&info.music).await?)?;
         let track_length = music.length();
         let buffer_size = Some(1024);
         let sfx_click = audio.create_sfx(res_pack.sfx_click.clone(), buffer_size)?;
@@ -490,6 +587,21 @@ impl Resource {
 
         let no_effect = config.disable_effect || has_no_effect;
 
+        macro_rules! load_icon {
+            ($field:ident, $path:literal) => {
+                if let Some(icon) = &res_pack.icons.$field {
+                    icon.clone()
+                } else {
+                    load_tex!($path)
+                }
+            };
+        }
+        let icon_back = load_icon!(back, "back.png");
+        let icon_retry = load_icon!(retry, "retry.png");
+        let icon_resume = load_icon!(resume, "resume.png");
+        let icon_proceed = load_icon!(proceed, "proceed.png");
+        let icon_pause = res_pack.icons.pause.clone();
+
         macroquad::window::gl_set_drawcall_buffer_capacity(MAX_SIZE * 4, MAX_SIZE * 6);
         Ok(Self {
             config,
@@ -503,6 +615,7 @@ impl Resource {
 
             alpha: 1.,
             judge_line_color: res_pack.info.fx_perfect_line(),
+            combo: 0,
 
             camera,
 
@@ -512,10 +625,11 @@ impl Resource {
             challenge_icons: Self::load_challenge_icons().await?,
             res_pack,
             player: if let Some(player) = player { player } else { load_tex!("player.png") },
-            icon_back: load_tex!("back.png"),
-            icon_retry: load_tex!("retry.png"),
-            icon_resume: load_tex!("resume.png"),
-            icon_proceed: load_tex!("proceed.png"),
+            icon_back,
+            icon_retry,
+            icon_resume,
+            icon_proceed,
+            icon_pause,
 
             emitter,
 
@@ -525,6 +639,7 @@ impl Resource {
             sfx_click,
             sfx_drag,
             sfx_flick,
+            hitsounds: HashMap::new(),
 
             chart_target: None,
             no_effect,
@@ -535,7 +650,15 @@ impl Resource {
         })
     }
 
-    pub fn emit_at_origin(&mut self, rotation: f32, color: Color) {
+    pub fn load_hitsounds(&mut self, chart: &Chart) -> Result<()> {
+        self.hitsounds.clear();
+        for (path, clip) in &chart.hitsounds {
+            self.hitsounds.insert(path.clone(), self.audio.create_sfx(clip.clone(), Some(1024))?);
+        }
+        Ok(())
+    }
+
+    pub fn emit_at_origin(&mut self, rotation: f32, color: Color, good: bool) {
         if !self.config.particle {
             return;
         }
@@ -544,9 +667,21 @@ impl Resource {
             vec2(if self.config.flip_x() { -pt.x } else { pt.x }, -pt.y),
             if self.res_pack.info.hit_fx_rotate { rotation.to_radians() } else { 0. },
             color,
+            good,
         );
     }
 
+    /// Switches chart effects on or off, e.g. after the chart is reloaded. The render target they
+    /// need is (re)created on the next [`Resource::update_size`].
+    pub fn set_no_effect(&mut self, no_effect: bool) {
+        let no_effect = self.config.disable_effect || no_effect;
+        if self.no_effect != no_effect {
+            self.no_effect = no_effect;
+            self.chart_target = None;
+            self.last_vp = (0, 0, 0, 0);
+        }
+    }
+
     pub fn update_size(&mut self, vp: (i32, i32, i32, i32)) -> bool {
         if self.last_vp == vp {
             return false;
@@ -5,8 +5,8 @@ precision mediump float;
 varying lowp vec2 uv;
 uniform sampler2D screenTexture;
 
-uniform float sampleCount; // %3% int 1..64
-uniform float power; // %0.01%
+uniform float sampleCount; // %3% int 1..64 Number of colour slices sampled
+uniform float power; // %0.01% How far the slices are spread
 
 vec3 chromatic_slice(float t) {
   vec3 res = vec3(1.0 - t, 1.0 - abs(t - 1.0), t - 1.0);
@@ -6,7 +6,7 @@ varying lowp vec2 uv;
 uniform vec2 screenSize;
 uniform sampler2D screenTexture;
 
-uniform float size; // %10.0%
+uniform float size; // %10.0% Radius of the circle in pixels
 
 void main() {
   vec4 c = texture2D(screenTexture, uv);
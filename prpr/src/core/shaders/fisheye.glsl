@@ -6,7 +6,7 @@ varying lowp vec2 uv;
 uniform vec2 screenSize;
 uniform sampler2D screenTexture;
 
-uniform float power; // %-0.1%
+uniform float power; // %-0.1% Strength of the distortion, negative values pinch instead of bulging
 
 void main() {
   vec2 p = vec2(uv.x, uv.y * screenSize.y / screenSize.x);
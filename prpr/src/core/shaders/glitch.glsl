@@ -6,11 +6,11 @@ varying lowp vec2 uv;
 uniform sampler2D screenTexture;
 uniform float time;
 
-uniform float power; // %0.03%
-uniform float rate; // %0.6% 0..1
-uniform float speed; // %5.0%
-uniform float blockCount; // %30.5%
-uniform float colorRate; // %0.01% 0..1
+uniform float power; // %0.03% How far blocks are shifted
+uniform float rate; // %0.6% 0..1 Chance of glitching at any moment
+uniform float speed; // %5.0% How often the glitch changes
+uniform float blockCount; // %30.5% Number of horizontal blocks
+uniform float colorRate; // %0.01% 0..1 How far the red and blue channels are split
 
 float my_trunc(float x) {
   return x < 0.0? -floor(-x): floor(x);
@@ -5,7 +5,7 @@ precision mediump float;
 varying lowp vec2 uv;
 uniform sampler2D screenTexture;
 
-uniform float factor; // %1.0% 0..1
+uniform float factor; // %1.0% 0..1 How gray the screen becomes
 
 void main() {
   vec3 color = texture2D(screenTexture, uv).xyz;
@@ -5,8 +5,8 @@ precision highp float;
 varying lowp vec2 uv;
 uniform sampler2D screenTexture;
 
-uniform float seed; // %81.0%
-uniform float power; // %0.03% 0..1
+uniform float seed; // %81.0% Seed of the noise, animate it to make the noise move
+uniform float power; // %0.03% 0..1 How far pixels are jittered
 
 vec2 random(vec2 pos) {
   return fract(sin(vec2(dot(pos, vec2(12.9898,78.233)), dot(pos, vec2(-148.998,-65.233)))) * 43758.5453);
@@ -6,7 +6,7 @@ varying lowp vec2 uv;
 uniform vec2 screenSize;
 uniform sampler2D screenTexture;
 
-uniform float size; // %10.0%
+uniform float size; // %10.0% Size of the pixels in screen pixels
 
 void main() {
   vec2 factor = screenSize / size;
@@ -5,10 +5,10 @@ precision mediump float;
 varying lowp vec2 uv;
 uniform sampler2D screenTexture;
 
-uniform float centerX; // %0.5% 0..1
-uniform float centerY; // %0.5% 0..1
-uniform float power; // %0.01% 0..1
-uniform float sampleCount; // %6% int 1..64
+uniform float centerX; // %0.5% 0..1 Horizontal position of the centre
+uniform float centerY; // %0.5% 0..1 Vertical position of the centre
+uniform float power; // %0.01% 0..1 Length of the blur
+uniform float sampleCount; // %6% int 1..64 Number of samples along the blur
 
 void main() {
   vec2 direction = uv - vec2(centerX, centerY);
@@ -6,12 +6,12 @@ varying lowp vec2 uv;
 uniform vec2 screenSize;
 uniform sampler2D screenTexture;
 
-uniform float progress; // %0.2% 0..1
-uniform float centerX; // %0.5% 0..1
-uniform float centerY; // %0.5% 0..1
-uniform float width; // %0.1%
-uniform float distortion; // %0.8%
-uniform float expand; // %10.0%
+uniform float progress; // %0.2% 0..1 Radius of the ring
+uniform float centerX; // %0.5% 0..1 Horizontal position of the centre
+uniform float centerY; // %0.5% 0..1 Vertical position of the centre
+uniform float width; // %0.1% Thickness of the ring
+uniform float distortion; // %0.8% Sharpness of the distortion
+uniform float expand; // %10.0% How much the ring pushes pixels outwards
 
 void main() {
   float aspect = screenSize.y / screenSize.x;
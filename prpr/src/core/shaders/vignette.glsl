@@ -6,9 +6,9 @@ varying lowp vec2 uv;
 uniform vec2 screenSize;
 uniform sampler2D screenTexture;
 
-uniform vec4 color; // %0.0, 0.0, 0.0, 1.0%
-uniform float extend; // %0.25% 0..1
-uniform float radius; // %15.0%
+uniform vec4 color; // %0.0, 0.0, 0.0, 1.0% Colour of the edges
+uniform float extend; // %0.25% 0..1 How far the vignette reaches
+uniform float radius; // %15.0% Size of the clear area
 
 void main() {
   vec2 new_uv = uv * (1.0 - uv.yx);
@@ -1,6 +1,7 @@
+use super::EPS;
 use macroquad::prelude::{vec2, Color, Rect, Vec2};
 use once_cell::sync::Lazy;
-use std::{any::Any, ops::Range, rc::Rc};
+use std::{any::Any, collections::HashMap, ops::Range, rc::Rc};
 
 pub type TweenId = u8;
 
@@ -257,6 +258,161 @@ impl BezierTween {
     }
 }
 
+/// Jumps between `steps` equal levels, like CSS `steps()`. With `jump_start`, the first jump happens at the very start.
+pub struct StepsTween {
+    pub steps: u32,
+    pub jump_start: bool,
+}
+
+impl TweenFunction for StepsTween {
+    fn y(&self, x: f32) -> f32 {
+        let n = self.steps.max(1) as f32;
+        let step = (x * n).floor() + if self.jump_start { 1. } else { 0. };
+        step.clamp(0., n) / n
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A damped spring released at 0 and settling at 1, scaled so that it ends exactly at 1.
+pub struct SpringTween {
+    /// Damping ratio; below 1 the spring overshoots and oscillates.
+    pub damping: f32,
+    /// Oscillations over the whole tween (undamped).
+    pub frequency: f32,
+    scale: f32,
+}
+
+impl TweenFunction for SpringTween {
+    fn y(&self, x: f32) -> f32 {
+        Self::raw(self.damping, self.frequency, x) / self.scale
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl SpringTween {
+    pub fn new(damping: f32, frequency: f32) -> Self {
+        let damping = damping.max(0.);
+        let frequency = frequency.max(1e-3);
+        let scale = Self::raw(damping, frequency, 1.);
+        Self {
+            damping,
+            frequency,
+            scale: if scale.abs() < 1e-4 { 1. } else { scale },
+        }
+    }
+
+    fn raw(damping: f32, frequency: f32, x: f32) -> f32 {
+        let omega = 2. * PI * frequency;
+        if damping < 1. {
+            let wd = omega * (1. - damping * damping).sqrt();
+            1. - (-damping * omega * x).exp() * ((wd * x).cos() + damping * omega / wd * (wd * x).sin())
+        } else {
+            // critically damped; overdamping barely differs at this scale
+            1. - (1. + omega * x) * (-omega * x).exp()
+        }
+    }
+}
+
+/// Linear interpolation between sampled `(x, y)` points, sorted by x.
+pub struct PiecewiseTween(Vec<(f32, f32)>);
+
+impl TweenFunction for PiecewiseTween {
+    fn y(&self, x: f32) -> f32 {
+        let points = &self.0;
+        let Some(first) = points.first() else {
+            return x;
+        };
+        let i = points.partition_point(|it| it.0 <= x);
+        if i == 0 {
+            return first.1;
+        }
+        if i == points.len() {
+            return points[i - 1].1;
+        }
+        let (a, b) = (points[i - 1], points[i]);
+        f32::tween(&a.1, &b.1, (x - a.0) / (b.0 - a.0))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl PiecewiseTween {
+    pub fn new(mut points: Vec<(f32, f32)>) -> Self {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self(points)
+    }
+
+    #[inline]
+    pub fn points(&self) -> &[(f32, f32)] {
+        &self.0
+    }
+}
+
+/// [`ClampedTween`] for arbitrary tweens.
+pub struct RangedTween {
+    pub inner: Rc<dyn TweenFunction>,
+    pub range: Range<f32>,
+    y_range: Range<f32>,
+}
+
+impl TweenFunction for RangedTween {
+    fn y(&self, x: f32) -> f32 {
+        let span = self.y_range.end - self.y_range.start;
+        if span.abs() < EPS {
+            // the inner tween ends where it starts over this range; fall back to linear
+            return x;
+        }
+        (self.inner.y(f32::tween(&self.range.start, &self.range.end, x)) - self.y_range.start) / span
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl RangedTween {
+    pub fn new(inner: Rc<dyn TweenFunction>, range: Range<f32>) -> Self {
+        let y_range = inner.y(range.start)..inner.y(range.end);
+        Self { inner, range, y_range }
+    }
+}
+
+/// Named tweens defined by a chart, optionally bound to numeric easing ids so that RPE events can use them.
+#[derive(Default)]
+pub struct TweenRegistry {
+    names: HashMap<String, Rc<dyn TweenFunction>>,
+    ids: HashMap<i32, Rc<dyn TweenFunction>>,
+}
+
+impl TweenRegistry {
+    pub fn register(&mut self, name: impl Into<String>, id: Option<i32>, tween: Rc<dyn TweenFunction>) {
+        if let Some(id) = id {
+            self.ids.insert(id, Rc::clone(&tween));
+        }
+        self.names.insert(name.into(), tween);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<dyn TweenFunction>> {
+        self.names.get(name).cloned()
+    }
+
+    pub fn get_id(&self, id: i32) -> Option<Rc<dyn TweenFunction>> {
+        self.ids.get(&id).cloned()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
 #[repr(u8)]
 pub enum TweenMajor {
     Plain,
@@ -374,3 +530,30 @@ impl Tweenable for Rect {
         Self::new(f32::tween(&x.x, &y.x, t), f32::tween(&x.y, &y.y, t), f32::tween(&x.w, &y.w, t), f32::tween(&x.h, &y.h, t))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranged() {
+        let inner: Rc<dyn TweenFunction> = Rc::new(PiecewiseTween::new(vec![(0., 0.), (1., 2.)]));
+        let tween = RangedTween::new(inner, 0.5..1.);
+        assert!((tween.y(0.) - 0.).abs() < EPS);
+        assert!((tween.y(0.5) - 0.5).abs() < EPS);
+        assert!((tween.y(1.) - 1.).abs() < EPS);
+    }
+
+    #[test]
+    fn ranged_flat() {
+        let inner: Rc<dyn TweenFunction> = Rc::new(PiecewiseTween::new(vec![(0., 0.5), (0.4, 0.5), (0.6, 1.), (1., 0.5)]));
+        for range in [0.1..0.3, 0.2..0.2, 0.3..1.] {
+            let tween = RangedTween::new(Rc::clone(&inner), range);
+            for x in [0., 0.25, 0.5, 1.] {
+                let y = tween.y(x);
+                assert!(y.is_finite());
+                assert_eq!(y, x);
+            }
+        }
+    }
+}
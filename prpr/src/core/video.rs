@@ -3,9 +3,11 @@ use crate::ext::{source_of_image, ScaleType};
 use anyhow::Result;
 use macroquad::prelude::*;
 use miniquad::{Texture, TextureFormat, TextureParams, TextureWrap};
-use prpr_avc::AVPixelFormat;
-use std::{cell::RefCell, io::Write};
-use tempfile::NamedTempFile;
+use prpr_avc::{AVPixelFormat, MediaSource};
+use std::cell::RefCell;
+
+/// How far ahead (in seconds) a jump must go before seeking is cheaper than decoding every frame up to it.
+const SEEK_THRESHOLD: f64 = 1.;
 
 thread_local! {
     static VIDEO_BUFFERS: RefCell<[Vec<u8>; 3]> = RefCell::default();
@@ -13,7 +15,6 @@ thread_local! {
 
 pub struct Video {
     video: prpr_avc::Video,
-    _video_file: NamedTempFile,
 
     material: Material,
     tex_y: Texture2D,
@@ -43,11 +44,8 @@ fn new_tex(w: u32, h: u32) -> Texture2D {
 }
 
 impl Video {
-    pub fn new(data: Vec<u8>, start_time: f32, scale_type: ScaleType, alpha: Anim<f32>, dim: Anim<f32>) -> Result<Self> {
-        let mut video_file = NamedTempFile::new()?;
-        video_file.write_all(&data)?;
-        drop(data);
-        let video = prpr_avc::Video::open(video_file.path().as_os_str().to_str().unwrap(), AVPixelFormat::YUV420P)?;
+    pub fn new(source: Box<dyn MediaSource>, start_time: f32, scale_type: ScaleType, alpha: Anim<f32>, dim: Anim<f32>) -> Result<Self> {
+        let video = prpr_avc::Video::from_source(source, AVPixelFormat::YUV420P)?;
         let frame_delta = video.frame_rate().to_f64_inv();
         let format = video.stream_format();
         let w = format.width as u32;
@@ -71,7 +69,6 @@ impl Video {
 
         Ok(Self {
             video,
-            _video_file: video_file,
 
             material,
             tex_y,
@@ -88,13 +85,39 @@ impl Video {
         })
     }
 
+    /// Moves decoding to `frame`, so that it's the next one read.
+    fn seek_to(&mut self, frame: usize) {
+        // half a frame early so that rounding in timestamps doesn't skip it
+        self.video.seek((frame as f64 - 0.5) * self.frame_delta);
+        self.next_frame = frame;
+        self.ended = false;
+    }
+
     pub fn update(&mut self, t: f32) -> Result<()> {
-        if t < self.start_time || self.ended {
+        if t < self.start_time {
+            // rewound to before the video, so restart it
+            if self.next_frame != 0 || self.ended {
+                self.seek_to(0);
+            }
             return Ok(());
         }
         self.alpha.set_time(t);
         self.dim.set_time(t);
-        let that_frame = ((t - self.start_time) as f64 / self.frame_delta) as usize;
+        let time = (t - self.start_time) as f64;
+        let that_frame = (time / self.frame_delta) as usize;
+        // the frame on screen is `next_frame - 1`
+        if that_frame + 1 < self.next_frame {
+            self.seek_to(that_frame);
+        } else if !self.ended && that_frame > self.next_frame && (that_frame - self.next_frame) as f64 * self.frame_delta > SEEK_THRESHOLD {
+            if self.video.duration().map_or(false, |it| time >= it) {
+                self.ended = true;
+            } else {
+                self.seek_to(that_frame);
+            }
+        }
+        if self.ended {
+            return Ok(());
+        }
         if self.next_frame <= that_frame {
             VIDEO_BUFFERS.with(|it| {
                 let mut buf = it.borrow_mut();
@@ -13,16 +13,17 @@ use macroquad::prelude::*;
 use miniquad::{gl::GLenum, BlendFactor, BlendState, BlendValue, CompareFunc, Equation, PrimitiveType, StencilFaceState, StencilOp, StencilState};
 use once_cell::sync::Lazy;
 use ordered_float::{Float, NotNan};
-use sasa::AudioManager;
+use sasa::{AudioClip, AudioManager, Frame};
 use serde::Deserialize;
 use std::{
     future::Future,
+    io::Cursor,
     ops::Deref,
     pin::Pin,
     sync::{Arc, Mutex},
     task::{Poll, RawWaker, RawWakerVTable, Waker},
 };
-use tracing::{debug, info_span};
+use tracing::{debug, info_span, warn};
 
 pub type LocalTask<R> = Option<Pin<Box<dyn Future<Output = R>>>>;
 
@@ -439,6 +440,93 @@ pub fn create_audio_manger(config: &Config) -> Result<AudioManager> {
     }
 }
 
+/// Music file contents shared between the decoders, so that falling back doesn't copy them.
+struct SharedData(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for SharedData {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+fn decode_with_symphonia(data: Arc<Vec<u8>>) -> Result<(Vec<Frame>, u32)> {
+    use symphonia::core::{
+        audio::SampleBuffer,
+        codecs::DecoderOptions,
+        errors::Error as SymphoniaError,
+        formats::FormatOptions,
+        io::MediaSourceStream,
+        meta::MetadataOptions,
+        probe::Hint,
+    };
+
+    let source = MediaSourceStream::new(Box::new(Cursor::new(SharedData(data))), Default::default());
+    let mut format = symphonia::default::get_probe()
+        .format(&Hint::new(), source, &FormatOptions::default(), &MetadataOptions::default())?
+        .format;
+    let track = format.default_track().ok_or_else(|| anyhow!("no audio track"))?;
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate;
+    let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+    let mut buffer: Option<SampleBuffer<f32>> = None;
+    let mut frames = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(err)) => {
+                warn!("skipping malformed packet: {err}");
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let spec = *decoded.spec();
+        sample_rate = Some(spec.rate);
+        let channels = spec.channels.count();
+        if buffer.as_ref().map_or(true, |it| it.capacity() < decoded.capacity() * channels) {
+            buffer = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+        let buffer = buffer.as_mut().unwrap();
+        buffer.copy_interleaved_ref(decoded);
+        if channels == 1 {
+            frames.extend(buffer.samples().iter().map(|&it| Frame(it, it)));
+        } else {
+            frames.extend(buffer.samples().chunks_exact(channels).map(|it| Frame(it[0], it[1])));
+        }
+    }
+    Ok((frames, sample_rate.ok_or_else(|| anyhow!("unknown sample rate"))?))
+}
+
+fn decode_with_ffmpeg(data: Arc<Vec<u8>>) -> Result<(Vec<Frame>, u32)> {
+    let audio = prpr_avc::decode_audio(Box::new(Cursor::new(SharedData(data))), 2, None)?;
+    Ok((audio.samples.chunks_exact(2).map(|it| Frame(it[0], it[1])).collect(), audio.sample_rate))
+}
+
+/// Decodes music with symphonia, falling back to FFmpeg for formats it can't handle (AAC, Opus, audio tracks of videos...).
+pub fn decode_music(data: Vec<u8>) -> Result<(Vec<Frame>, u32)> {
+    let data = Arc::new(data);
+    match decode_with_symphonia(Arc::clone(&data)) {
+        Ok(res) => Ok(res),
+        Err(err) => {
+            warn!("symphonia failed to decode music, falling back to FFmpeg: {err:?}");
+            decode_with_ffmpeg(data)
+        }
+    }
+}
+
+/// Like [decode_music], but wrapped in a clip ready to be played.
+pub fn load_music(data: Vec<u8>) -> Result<AudioClip> {
+    let (frames, sample_rate) = decode_music(data)?;
+    Ok(AudioClip::from_raw(frames, sample_rate))
+}
+
 pub fn make_pipeline(write_color: bool, pass_op: StencilOp, test_func: CompareFunc, test_ref: i32) -> GlPipeline {
     let InternalGlContext {
         quad_gl: gl,
@@ -4,6 +4,7 @@ use async_trait::async_trait;
 use chardetng::EncodingDetector;
 use concat_string::concat_string;
 use macroquad::prelude::load_file;
+use prpr_avc::MediaSource;
 use serde::Deserialize;
 use serde_json::Value;
 use std::{
@@ -50,6 +51,10 @@ pub fn update_zip<R: Read + Seek>(zip: &mut ZipArchive<R>, patches: HashMap<Stri
 pub trait FileSystem: Send {
     async fn load_file(&mut self, path: &str) -> Result<Vec<u8>>;
     async fn exists(&mut self, path: &str) -> Result<bool>;
+    /// Opens `path` for streaming reads. By default the whole file is loaded into memory.
+    async fn open_reader(&mut self, path: &str) -> Result<Box<dyn MediaSource>> {
+        Ok(Box::new(Cursor::new(self.load_file(path).await?)))
+    }
     fn list_root(&self) -> Result<Vec<String>>;
     fn clone_box(&self) -> Box<dyn FileSystem>;
     fn as_any(&mut self) -> &mut dyn Any;
@@ -108,6 +113,17 @@ impl FileSystem for ExternalFileSystem {
         self.0.exists(path)
     }
 
+    async fn open_reader(&mut self, path: &str) -> Result<Box<dyn MediaSource>> {
+        #[cfg(target_arch = "wasm32")]
+        {
+            unimplemented!("cannot use external file system on wasm32")
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            Ok(Box::new(self.0.open(path)?))
+        }
+    }
+
     fn list_root(&self) -> Result<Vec<String>> {
         Ok(self.0.read_dir(".")?.filter_map(|res| res.ok()?.file_name().into_string().ok()).collect())
     }
@@ -191,6 +207,14 @@ impl FileSystem for PatchedFileSystem {
         Ok(self.0.exists(path).await? || self.1.contains_key(path))
     }
 
+    async fn open_reader(&mut self, path: &str) -> Result<Box<dyn MediaSource>> {
+        if let Some(data) = self.1.get(path) {
+            Ok(Box::new(Cursor::new(data.clone())))
+        } else {
+            self.0.open_reader(path).await
+        }
+    }
+
     fn list_root(&self) -> Result<Vec<String>> {
         let mut res = self.0.list_root()?;
         res.extend(self.1.keys().cloned());
@@ -207,6 +231,34 @@ impl FileSystem for PatchedFileSystem {
     }
 }
 
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct MemoryFileSystem(pub HashMap<String, Vec<u8>>);
+
+#[cfg(test)]
+#[async_trait]
+impl FileSystem for MemoryFileSystem {
+    async fn load_file(&mut self, path: &str) -> Result<Vec<u8>> {
+        self.0.get(path).cloned().ok_or_else(|| anyhow!("no such file: {path}"))
+    }
+
+    async fn exists(&mut self, path: &str) -> Result<bool> {
+        Ok(self.0.contains_key(path))
+    }
+
+    fn list_root(&self) -> Result<Vec<String>> {
+        Ok(self.0.keys().cloned().collect())
+    }
+
+    fn clone_box(&self) -> Box<dyn FileSystem> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
 fn infer_diff(info: &mut ChartInfo, level: &str) {
     if let Ok(val) = level
         .chars()
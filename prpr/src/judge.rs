@@ -1,7 +1,11 @@
+mod keyboard;
+pub use keyboard::{key_from_name, KeyMap};
+
 use crate::{
-    config::Config,
-    core::{BadNote, Chart, NoteKind, Point, Resource, Vector, NOTE_WIDTH_RATIO_BASE},
+    config::{Config, KeyMode},
+    core::{BadNote, Chart, Note, NoteKind, Point, Resource, Vector, NOTE_WIDTH_RATIO_BASE},
     ext::{get_viewport, NotNanExt},
+    replay::{JudgeInput, KeyEvent, Replay, ReplayFrame},
 };
 use macroquad::prelude::{
     utils::{register_input_subscriber, repeat_all_miniquad_input},
@@ -10,8 +14,15 @@ use macroquad::prelude::{
 use miniquad::{EventHandler, MouseButton};
 use once_cell::sync::Lazy;
 use sasa::{PlaySfxParams, Sfx};
-use serde::Serialize;
-use std::{cell::RefCell, collections::HashMap, num::FpCategory};
+use serde::{Deserialize, Serialize};
+use anyhow::Result;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    num::FpCategory,
+    sync::Arc,
+};
 use tracing::debug;
 
 pub const FLICK_SPEED_THRESHOLD: f32 = 0.8;
@@ -21,6 +32,66 @@ pub const LIMIT_BAD: f32 = 0.22;
 pub const UP_TOLERANCE: f32 = 0.05;
 pub const DIST_FACTOR: f32 = 0.2;
 
+/// Timing windows (in seconds of real time) and gesture thresholds used to judge notes.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct JudgeProfile {
+    pub perfect: f32,
+    pub good: f32,
+    pub bad: f32,
+    pub flick_speed_threshold: f32,
+    pub up_tolerance: f32,
+}
+
+impl JudgeProfile {
+    pub const STANDARD: Self = Self {
+        perfect: LIMIT_PERFECT,
+        good: LIMIT_GOOD,
+        bad: LIMIT_BAD,
+        flick_speed_threshold: FLICK_SPEED_THRESHOLD,
+        up_tolerance: UP_TOLERANCE,
+    };
+
+    pub const STRICT: Self = Self {
+        perfect: 0.04,
+        good: 0.075,
+        bad: 0.14,
+        flick_speed_threshold: FLICK_SPEED_THRESHOLD,
+        up_tolerance: 0.03,
+    };
+
+    pub const LENIENT: Self = Self {
+        perfect: 0.12,
+        good: 0.2,
+        bad: 0.26,
+        flick_speed_threshold: 0.6,
+        up_tolerance: 0.1,
+    };
+
+    pub fn preset(name: &str) -> Option<Self> {
+        Some(match name {
+            "standard" => Self::STANDARD,
+            "strict" => Self::STRICT,
+            "lenient" => Self::LENIENT,
+            _ => return None,
+        })
+    }
+
+    /// Name of the preset this profile equals, if any.
+    pub fn preset_name(&self) -> Option<&'static str> {
+        [("standard", Self::STANDARD), ("strict", Self::STRICT), ("lenient", Self::LENIENT)]
+            .into_iter()
+            .find(|(_, it)| it == self)
+            .map(|(name, _)| name)
+    }
+}
+
+impl Default for JudgeProfile {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
 const EARLY_OFFSET: f32 = 0.07;
 
 pub fn play_sfx(sfx: &mut Sfx, config: &Config) {
@@ -32,6 +103,26 @@ pub fn play_sfx(sfx: &mut Sfx, config: &Config) {
     });
 }
 
+/// Plays the note's custom hit sound if it has one, otherwise the resource pack's sound for its kind.
+pub fn play_note_sfx(res: &mut Resource, note: &Note) {
+    let sfx = if let Some(sfx) = note.hitsound.as_ref().and_then(|it| res.hitsounds.get_mut(it)) {
+        sfx
+    } else {
+        match note.kind {
+            NoteKind::Click | NoteKind::Hold { .. } => &mut res.sfx_click,
+            NoteKind::Drag => &mut res.sfx_drag,
+            NoteKind::Flick => &mut res.sfx_flick,
+        }
+    };
+    play_sfx(sfx, &res.config);
+}
+
+/// Horizontal distance from a note, scaled by its judge width so that wider notes are hit from further away.
+#[inline]
+fn judge_dist(dx: f32, judge_scale: f32) -> f32 {
+    dx.abs() / judge_scale.max(1e-3)
+}
+
 #[cfg(all(not(target_os = "windows"), not(target_os = "ios")))]
 fn get_uptime() -> f64 {
     let mut time = libc::timespec { tv_sec: 0, tv_nsec: 0 };
@@ -59,11 +150,15 @@ pub struct FlickTracker {
 }
 
 impl FlickTracker {
-    pub fn new(_dpi: u32, time: f32, point: Point) -> Self {
+    pub fn new(dpi: u32, time: f32, point: Point) -> Self {
+        Self::with_threshold(FLICK_SPEED_THRESHOLD, dpi, time, point)
+    }
+
+    pub fn with_threshold(speed_threshold: f32, _dpi: u32, time: f32, point: Point) -> Self {
         // TODO maybe a better approach?
         let dpi = 275;
         Self {
-            threshold: FLICK_SPEED_THRESHOLD * dpi as f32 / 386.,
+            threshold: speed_threshold * dpi as f32 / 386.,
             last_point: point,
             last_delta: None,
             last_time: time,
@@ -114,6 +209,34 @@ pub enum Judgement {
     Miss,
 }
 
+fn kind_name(kind: &NoteKind) -> &'static str {
+    match kind {
+        NoteKind::Click => "click",
+        NoteKind::Hold { .. } => "hold",
+        NoteKind::Flick => "flick",
+        NoteKind::Drag => "drag",
+    }
+}
+
+/// A single committed judgement.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineEntry {
+    pub line_id: u32,
+    pub note_id: u32,
+    /// Chart time of the note (the head, for holds).
+    pub note_time: f32,
+    /// Hit time minus note time in real seconds, negative when early. `None` when there is no timing to speak of.
+    pub offset: Option<f32>,
+    pub judgement: Judgement,
+    #[serde(serialize_with = "serialize_kind")]
+    pub kind: NoteKind,
+}
+
+fn serialize_kind<S: serde::Serializer>(kind: &NoteKind, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(kind_name(kind))
+}
+
 #[cfg(not(feature = "closed"))]
 #[derive(Default)]
 pub(crate) struct JudgeInner {
@@ -197,6 +320,9 @@ impl JudgeInner {
             early,
             late: self.diffs.len() as u32 - early,
             std: 0.,
+            replay: None,
+            judge_profile: JudgeProfile::STANDARD,
+            timeline: Vec::new(),
         }
     }
 
@@ -226,15 +352,24 @@ pub struct Judge {
 
     pub(crate) inner: JudgeInner,
     pub judgements: RefCell<Vec<(f32, u32, u32, Result<Judgement, bool>)>>,
+
+    recording: Option<Replay>,
+    playback: Option<(Arc<Replay>, usize)>,
+    profile: JudgeProfile,
+    timeline: Vec<TimelineEntry>,
+    // mapped keys currently held down, by id
+    held_keys: HashMap<u32, Option<(f32, f32)>>,
+    // resolved once, the config doesn't change during a play
+    key_map: Option<KeyMap>,
 }
 
 static SUBSCRIBER_ID: Lazy<usize> = Lazy::new(register_input_subscriber);
 thread_local! {
-    static TOUCHES: RefCell<(Vec<Touch>, i32, u32)> = RefCell::default();
+    static TOUCHES: RefCell<(Vec<Touch>, i32, u32, Vec<(KeyCode, bool)>)> = RefCell::default();
 }
 
 impl Judge {
-    pub fn new(chart: &Chart) -> Self {
+    pub fn new(chart: &Chart, config: &Config) -> Self {
         let notes = chart
             .lines
             .iter()
@@ -253,14 +388,50 @@ impl Judge {
 
             inner: JudgeInner::new(chart.lines.iter().map(|it| it.notes.iter().filter(|it| !it.fake).count() as u32).sum()),
             judgements: RefCell::new(Vec::new()),
+
+            recording: None,
+            playback: None,
+            profile: JudgeProfile::STANDARD,
+            timeline: Vec::new(),
+            held_keys: HashMap::new(),
+            key_map: (config.key_mode == KeyMode::Mapped).then(|| KeyMap::new(config)),
         }
     }
 
     pub fn reset(&mut self) {
         self.notes.iter_mut().for_each(|it| it.1 = 0);
         self.trackers.clear();
+        self.last_time = 0.;
         self.inner.reset();
         self.judgements.borrow_mut().clear();
+        self.timeline.clear();
+        self.held_keys.clear();
+        if let Some(replay) = &mut self.recording {
+            replay.frames.clear();
+        }
+        if let Some((_, cursor)) = &mut self.playback {
+            *cursor = 0;
+        }
+    }
+
+    fn key_held_at(&self, x: f32) -> bool {
+        self.held_keys.values().any(|zone| zone_contains(*zone, x))
+    }
+
+    fn push_timeline(&mut self, line_id: u32, note_id: u32, note: &Note, judgement: Judgement, offset: Option<f32>) {
+        self.timeline.push(TimelineEntry {
+            line_id,
+            note_id,
+            note_time: note.time,
+            offset,
+            judgement,
+            kind: note.kind.clone(),
+        });
+    }
+
+    #[inline]
+    pub fn timeline(&self) -> &[TimelineEntry] {
+        &self.timeline
     }
 
     pub fn commit(&mut self, t: f32, what: Judgement, line_id: u32, note_id: u32, diff: f32) {
@@ -284,11 +455,11 @@ impl Judge {
     }
 
     pub(crate) fn on_new_frame() {
-        let mut handler = Handler(Vec::new(), 0, 0);
+        let mut handler = Handler(Vec::new(), 0, 0, Vec::new());
         repeat_all_miniquad_input(&mut handler, *SUBSCRIBER_ID);
         handler.finalize();
         TOUCHES.with(|it| {
-            *it.borrow_mut() = (handler.0, handler.1, handler.2);
+            *it.borrow_mut() = (handler.0, handler.1, handler.2, handler.3);
         });
     }
 
@@ -322,81 +493,182 @@ impl Judge {
         })
     }
 
+    #[allow(unused_variables)]
+    fn convert_time(uptime: f64, t: f32, spd: f32, time: f64) -> f64 {
+        if time.is_infinite() {
+            f64::NEG_INFINITY
+        } else {
+            #[cfg(target_os = "windows")]
+            {
+                time
+            }
+            #[cfg(not(target_os = "windows"))]
+            {
+                t as f64 - (uptime - time) * spd as f64
+            }
+        }
+    }
+
+    fn collect_input(&self, res: &Resource) -> JudgeInput {
+        let spd = res.config.speed;
+
+        #[cfg(not(target_os = "windows"))]
+        let uptime = get_uptime();
+        #[cfg(target_os = "windows")]
+        let uptime = 0.;
+
+        let t = res.time;
+        let mut touches = touches();
+        let btn = MouseButton::Left;
+        let id = button_to_id(btn);
+        if is_mouse_button_pressed(btn) {
+            let p = mouse_position();
+            touches.push(Touch {
+                id,
+                phase: TouchPhase::Started,
+                position: vec2(p.0, p.1),
+                time: f64::NEG_INFINITY,
+            });
+        } else if is_mouse_button_down(btn) {
+            let p = mouse_position();
+            touches.push(Touch {
+                id,
+                phase: TouchPhase::Moved,
+                position: vec2(p.0, p.1),
+                time: f64::NEG_INFINITY,
+            });
+        } else if is_mouse_button_released(btn) {
+            let p = mouse_position();
+            touches.push(Touch {
+                id,
+                phase: TouchPhase::Ended,
+                position: vec2(p.0, p.1),
+                time: f64::NEG_INFINITY,
+            });
+        }
+        let tr = Self::touch_transform(res.config.flip_x());
+        let touches = touches
+            .into_iter()
+            .map(|mut it| {
+                tr(&mut it);
+                it.time = Self::convert_time(uptime, t, spd, it.time);
+                it
+            })
+            .collect();
+        fn to_local(Vec2 { x, y }: Vec2) -> Vec2 {
+            vec2(x / screen_width() * 2. - 1., y / screen_height() * 2. - 1.)
+        }
+        TOUCHES.with(|it| {
+            let guard = it.borrow();
+            let (key_delta, keys_down, keys) = if let Some(map) = &self.key_map {
+                (0, 0, guard.3.iter().filter_map(|(key, pressed)| map.event(*key, *pressed)).collect())
+            } else {
+                (guard.1, guard.2, Vec::new())
+            };
+            JudgeInput {
+                touches,
+                events: guard
+                    .0
+                    .iter()
+                    .cloned()
+                    .map(|mut it| {
+                        it.position = to_local(it.position);
+                        it.time = Self::convert_time(uptime, t, spd, it.time);
+                        it
+                    })
+                    .collect(),
+                key_delta,
+                keys_down,
+                keys,
+            }
+        })
+    }
+
+    /// Records the input of every following [`Judge::update`] call into a [`Replay`].
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Replay::default());
+    }
+
+    #[inline]
+    pub fn recording(&self) -> Option<&Replay> {
+        self.recording.as_ref()
+    }
+
+    /// Feeds recorded input back instead of reading live touches.
+    pub fn play_replay(&mut self, replay: Arc<Replay>) {
+        self.recording = None;
+        self.playback = Some((replay, 0));
+    }
+
     pub fn update(&mut self, res: &mut Resource, chart: &mut Chart, bad_notes: &mut Vec<BadNote>) {
+        self.profile = res.config.judge_profile;
         if res.config.autoplay() {
             self.auto_play_update(res, chart);
             return;
         }
+        if let Some((replay, mut cursor)) = self.playback.take() {
+            let now = res.time;
+            while let Some(frame) = replay.frames.get(cursor).filter(|it| it.time <= now) {
+                // when recording, the chart had last been updated to the previous frame, and line transforms are read from that state
+                if let Some(prev) = cursor.checked_sub(1).and_then(|it| replay.frames.get(it)) {
+                    for line in &mut chart.lines {
+                        line.object.set_time(prev.time);
+                    }
+                }
+                res.time = frame.time;
+                self.update_with_input(res, chart, bad_notes, frame.input.clone());
+                cursor += 1;
+            }
+            res.time = now;
+            self.playback = Some((replay, cursor));
+            return;
+        }
+        let input = self.collect_input(res);
+        if let Some(replay) = &mut self.recording {
+            replay.speed = res.config.speed;
+            replay.aspect_ratio = res.aspect_ratio;
+            replay.judge_profile = res.config.judge_profile;
+            replay.frames.push(ReplayFrame {
+                time: res.time,
+                input: input.clone(),
+            });
+        }
+        self.update_with_input(res, chart, bad_notes, input);
+    }
+
+    fn update_with_input(&mut self, res: &mut Resource, chart: &mut Chart, bad_notes: &mut Vec<BadNote>, input: JudgeInput) {
         const X_DIFF_MAX: f32 = 0.21 / (16. / 9.) * 2.;
         let spd = res.config.speed;
-
-        #[cfg(not(target_os = "windows"))]
-        let uptime = get_uptime();
+        let JudgeProfile {
+            perfect: limit_perfect,
+            good: limit_good,
+            bad: limit_bad,
+            flick_speed_threshold,
+            up_tolerance,
+        } = self.profile;
 
         let t = res.time;
-        // TODO optimize
-        let mut touches: HashMap<u64, Touch> = {
-            let mut touches = touches();
-            let btn = MouseButton::Left;
-            let id = button_to_id(btn);
-            if is_mouse_button_pressed(btn) {
-                let p = mouse_position();
-                touches.push(Touch {
-                    id,
-                    phase: TouchPhase::Started,
-                    position: vec2(p.0, p.1),
-                    time: f64::NEG_INFINITY,
-                });
-            } else if is_mouse_button_down(btn) {
-                let p = mouse_position();
-                touches.push(Touch {
-                    id,
-                    phase: TouchPhase::Moved,
-                    position: vec2(p.0, p.1),
-                    time: f64::NEG_INFINITY,
-                });
-            } else if is_mouse_button_released(btn) {
-                let p = mouse_position();
-                touches.push(Touch {
-                    id,
-                    phase: TouchPhase::Ended,
-                    position: vec2(p.0, p.1),
-                    time: f64::NEG_INFINITY,
-                });
-            }
-            let tr = Self::touch_transform(res.config.flip_x());
-            touches
-                .into_iter()
-                .map(|mut it| {
-                    tr(&mut it);
-                    (it.id, it)
-                })
-                .collect()
-        };
-        let (events, keys_down) = TOUCHES.with(|it| {
-            let guard = it.borrow();
-            (guard.0.clone(), guard.2)
-        });
-        self.key_down_count = self.key_down_count.saturating_add_signed(TOUCHES.with(|it| it.borrow().1));
+        // ordered by id so that judging the same input twice gives the same result
+        let mut touches: BTreeMap<u64, Touch> = input.touches.into_iter().map(|it| (it.id, it)).collect();
+        self.key_down_count = self.key_down_count.saturating_add_signed(input.key_delta);
+        let keys_down = input.keys_down;
+        let keys = input.keys;
         {
-            fn to_local(Vec2 { x, y }: Vec2) -> Point {
-                Point::new(x / screen_width() * 2. - 1., y / screen_height() * 2. - 1.)
-            }
-            let delta = (t / spd - self.last_time) as f64 / (events.len() + 1) as f64;
+            let delta = (t / spd - self.last_time) as f64 / (input.events.len() + 1) as f64;
             let mut t = self.last_time as f64;
             for Touch {
                 id,
                 phase,
                 position: p,
                 time,
-            } in events.into_iter()
+            } in input.events.into_iter()
             {
                 t += delta;
                 let t = t as f32;
-                let p = to_local(p);
+                let p = Point::new(p.x, p.y);
                 match phase {
                     TouchPhase::Started => {
-                        self.trackers.insert(id, FlickTracker::new(res.dpi, t, p));
+                        self.trackers.insert(id, FlickTracker::with_threshold(flick_speed_threshold, res.dpi, t, p));
                         touches
                             .entry(id)
                             .or_insert_with(|| Touch {
@@ -418,24 +690,7 @@ impl Judge {
                 }
             }
         }
-        let touches: Vec<Touch> = touches
-            .into_values()
-            .map(|mut it| {
-                it.time = if it.time.is_infinite() {
-                    f64::NEG_INFINITY
-                } else {
-                    #[cfg(target_os = "windows")]
-                    {
-                        it.time
-                    }
-                    #[cfg(not(target_os = "windows"))]
-                    {
-                        t as f64 - (uptime - it.time) * spd as f64
-                    }
-                };
-                it
-            })
-            .collect();
+        let touches: Vec<Touch> = touches.into_values().collect();
         // pos[line][touch]
         let mut pos = Vec::<Vec<Option<Point>>>::with_capacity(chart.lines.len());
         for id in 0..pos.capacity() {
@@ -476,7 +731,7 @@ impl Judge {
                 continue;
             }
             let t = time_of(touch);
-            let mut closest = (None, X_DIFF_MAX, LIMIT_BAD, LIMIT_BAD + (X_DIFF_MAX / NOTE_WIDTH_RATIO_BASE - 1.).max(0.) * DIST_FACTOR);
+            let mut closest = (None, X_DIFF_MAX, limit_bad, limit_bad + (X_DIFF_MAX / NOTE_WIDTH_RATIO_BASE - 1.).max(0.) * DIST_FACTOR);
             for (line_id, ((line, pos), (idx, st))) in chart.lines.iter_mut().zip(pos.iter()).zip(self.notes.iter_mut()).enumerate() {
                 let Some(pos) = pos[id] else { continue; };
                 for id in &idx[*st..] {
@@ -494,21 +749,21 @@ impl Judge {
                     let dt = if dt < 0. { (dt + EARLY_OFFSET).min(0.).abs() } else { dt };
                     let x = &mut note.object.translation.0;
                     x.set_time(t);
-                    let dist = (x.now() - pos.x).abs();
+                    let dist = judge_dist(x.now() - pos.x, note.judge_scale);
                     if dist > X_DIFF_MAX {
                         continue;
                     }
                     if dt
                         > if matches!(note.kind, NoteKind::Click) {
-                            LIMIT_BAD - LIMIT_PERFECT * (dist - 0.9).max(0.)
+                            limit_bad - limit_perfect * (dist - 0.9).max(0.)
                         } else {
-                            LIMIT_GOOD
+                            limit_good
                         }
                     {
                         continue;
                     }
                     let dt = if matches!(note.kind, NoteKind::Flick | NoteKind::Drag) {
-                        dt + LIMIT_GOOD
+                        dt + limit_good
                     } else {
                         dt
                     };
@@ -530,16 +785,16 @@ impl Judge {
                     if matches!(note.kind, NoteKind::Flick) {
                         continue; // to next loop
                     }
-                    if dt <= LIMIT_GOOD || matches!(note.kind, NoteKind::Hold { .. }) {
+                    if dt <= limit_good || matches!(note.kind, NoteKind::Hold { .. }) {
                         match note.kind {
                             NoteKind::Click => {
                                 note.judge = JudgeStatus::Judged;
-                                judgements.push((if dt <= LIMIT_PERFECT { Judgement::Perfect } else { Judgement::Good }, line_id, id, Some(t)));
+                                judgements.push((if dt <= limit_perfect { Judgement::Perfect } else { Judgement::Good }, line_id, id, Some(t)));
                             }
                             NoteKind::Hold { .. } => {
-                                play_sfx(&mut res.sfx_click, &res.config);
-                                self.judgements.borrow_mut().push((t, line_id as _, id, Err(dt <= LIMIT_PERFECT)));
-                                note.judge = JudgeStatus::Hold(dt <= LIMIT_PERFECT, t, t, false, f32::INFINITY);
+                                play_note_sfx(res, note);
+                                self.judgements.borrow_mut().push((t, line_id as _, id, Err(dt <= limit_perfect)));
+                                note.judge = JudgeStatus::Hold(dt <= limit_perfect, t, t, false, f32::INFINITY);
                             }
                             _ => unreachable!(),
                         };
@@ -581,14 +836,14 @@ impl Judge {
             {
                 let note = &mut chart.lines[line_id].notes[id as usize];
                 let dt = (t - note.time).abs() / spd;
-                if dt <= if matches!(note.kind, NoteKind::Click) { LIMIT_BAD } else { LIMIT_GOOD } {
+                if dt <= if matches!(note.kind, NoteKind::Click) { limit_bad } else { limit_good } {
                     match note.kind {
                         NoteKind::Click => {
                             note.judge = JudgeStatus::Judged;
                             judgements.push((
-                                if dt <= LIMIT_PERFECT {
+                                if dt <= limit_perfect {
                                     Judgement::Perfect
-                                } else if dt <= LIMIT_GOOD {
+                                } else if dt <= limit_good {
                                     Judgement::Good
                                 } else {
                                     Judgement::Bad
@@ -599,9 +854,9 @@ impl Judge {
                             ));
                         }
                         NoteKind::Hold { .. } => {
-                            play_sfx(&mut res.sfx_click, &res.config);
-                            self.judgements.borrow_mut().push((t, line_id as _, id, Err(dt <= LIMIT_PERFECT)));
-                            note.judge = JudgeStatus::Hold(dt <= LIMIT_PERFECT, t, (t - note.time) / spd, false, f32::INFINITY);
+                            play_note_sfx(res, note);
+                            self.judgements.borrow_mut().push((t, line_id as _, id, Err(dt <= limit_perfect)));
+                            note.judge = JudgeStatus::Hold(dt <= limit_perfect, t, (t - note.time) / spd, false, f32::INFINITY);
                         }
                         _ => unreachable!(),
                     };
@@ -610,21 +865,82 @@ impl Judge {
                 break;
             }
         }
+        for KeyEvent { id, zone, pressed } in keys {
+            if !pressed {
+                self.held_keys.remove(&id);
+                continue;
+            }
+            self.held_keys.insert(id, zone);
+            // the earliest pending note in the key's zone; drags are only caught by holding keys
+            let mut target: Option<(usize, u32, f32)> = None;
+            for (line_id, (line, (idx, st))) in chart.lines.iter_mut().zip(self.notes.iter()).enumerate() {
+                for id in &idx[*st..] {
+                    let note = &mut line.notes[*id as usize];
+                    if target.map_or(false, |it| it.2 <= note.time) || (note.time - t) / spd > limit_bad {
+                        break;
+                    }
+                    if !matches!(note.judge, JudgeStatus::NotJudged) || matches!(note.kind, NoteKind::Drag) || (t - note.time) / spd > limit_bad {
+                        continue;
+                    }
+                    let x = &mut note.object.translation.0;
+                    x.set_time(t);
+                    if zone_contains(zone, x.now()) {
+                        target = Some((line_id, *id, note.time));
+                        break;
+                    }
+                }
+            }
+            let Some((line_id, id, _)) = target else {
+                continue;
+            };
+            let note = &mut chart.lines[line_id].notes[id as usize];
+            let dt = (t - note.time).abs() / spd;
+            match note.kind {
+                NoteKind::Click => {
+                    note.judge = JudgeStatus::Judged;
+                    judgements.push((
+                        if dt <= limit_perfect {
+                            Judgement::Perfect
+                        } else if dt <= limit_good {
+                            Judgement::Good
+                        } else {
+                            Judgement::Bad
+                        },
+                        line_id,
+                        id,
+                        None,
+                    ));
+                }
+                NoteKind::Hold { .. } if dt <= limit_good => {
+                    play_note_sfx(res, note);
+                    self.judgements.borrow_mut().push((t, line_id as _, id, Err(dt <= limit_perfect)));
+                    note.judge = JudgeStatus::Hold(dt <= limit_perfect, t, t, false, f32::INFINITY);
+                }
+                // pressing a key over a flick counts as flicking it
+                NoteKind::Flick if dt <= limit_good => {
+                    note.judge = JudgeStatus::PreJudge;
+                }
+                _ => {}
+            }
+        }
         for (line_id, ((line, pos), (idx, st))) in chart.lines.iter_mut().zip(pos.iter()).zip(self.notes.iter()).enumerate() {
             line.object.set_time(t);
             for id in &idx[*st..] {
                 let note = &mut line.notes[*id as usize];
                 if let NoteKind::Hold { end_time, .. } = &note.kind {
                     if let JudgeStatus::Hold(.., ref mut pre_judge, ref mut up_time) = note.judge {
-                        if (*end_time - t) / spd <= LIMIT_BAD {
+                        if (*end_time - t) / spd <= limit_bad {
                             *pre_judge = true;
                             continue;
                         }
                         let x = &mut note.object.translation.0;
                         x.set_time(t);
                         let x = x.now();
-                        if self.key_down_count == 0 && !pos.iter().any(|it| it.map_or(false, |it| (it.x - x).abs() <= X_DIFF_MAX)) {
-                            if t > *up_time + UP_TOLERANCE {
+                        if self.key_down_count == 0
+                            && !self.key_held_at(x)
+                            && !pos.iter().any(|it| it.map_or(false, |it| judge_dist(it.x - x, note.judge_scale) <= X_DIFF_MAX))
+                        {
+                            if t > *up_time + up_tolerance {
                                 note.judge = JudgeStatus::Judged;
                                 judgements.push((Judgement::Miss, line_id, *id, None));
                             } else if up_time.is_infinite() {
@@ -641,26 +957,28 @@ impl Judge {
                 }
                 // process miss
                 let dt = (t - note.time) / spd;
-                if dt > LIMIT_BAD {
+                if dt > limit_bad {
                     note.judge = JudgeStatus::Judged;
                     judgements.push((Judgement::Miss, line_id, *id, None));
                     continue;
                 }
-                if -dt > LIMIT_BAD {
+                if -dt > limit_bad {
                     break;
                 }
-                if !matches!(note.kind, NoteKind::Drag) && (self.key_down_count == 0 || !matches!(note.kind, NoteKind::Flick)) {
-                    continue;
-                }
-                let dt = dt.abs();
                 let x = &mut note.object.translation.0;
                 x.set_time(t);
                 let x = x.now();
-                if self.key_down_count != 0
+                // held keys catch drags and flicks passing through them
+                let key_held = self.key_down_count != 0 || self.key_held_at(x);
+                if !matches!(note.kind, NoteKind::Drag) && (!key_held || !matches!(note.kind, NoteKind::Flick)) {
+                    continue;
+                }
+                let dt = dt.abs();
+                if key_held
                     || pos.iter().any(|it| {
                         it.map_or(false, |it| {
-                            let dx = (it.x - x).abs();
-                            dx <= X_DIFF_MAX && dt <= (LIMIT_BAD - LIMIT_PERFECT * (dx - 0.9).max(0.))
+                            let dx = judge_dist(it.x - x, note.judge_scale);
+                            dx <= X_DIFF_MAX && dt <= (limit_bad - limit_perfect * (dx - 0.9).max(0.))
                         })
                     })
                 {
@@ -683,7 +1001,7 @@ impl Judge {
                     }
                 }
                 // TODO adjust
-                let ghost_t = t + LIMIT_GOOD;
+                let ghost_t = t + limit_good;
                 if matches!(note.kind, NoteKind::Click) {
                     if ghost_t < note.time {
                         break;
@@ -712,29 +1030,33 @@ impl Judge {
             let line = &chart.lines[line_id];
             let note = &line.notes[id as usize];
             let line_tr = line.now_transform(res, &chart.lines);
+            let offset = if matches!(judgement, Judgement::Miss) || matches!(note.kind, NoteKind::Drag | NoteKind::Flick) {
+                None
+            } else {
+                Some((diff.unwrap_or(t) - note.time) / spd)
+            };
             self.commit(
                 t,
                 judgement,
                 line_id as _,
                 id,
-                if matches!(judgement, Judgement::Miss) {
-                    0.25
-                } else if matches!(note.kind, NoteKind::Drag | NoteKind::Flick) {
-                    0.
-                } else {
-                    (diff.unwrap_or(t) - note.time) / spd
-                },
+                if matches!(judgement, Judgement::Miss) { 0.25 } else { offset.unwrap_or_default() },
             );
+            self.push_timeline(line_id as _, id, note, judgement, offset);
             if matches!(note.kind, NoteKind::Hold { .. }) {
                 continue;
             }
             if match judgement {
                 Judgement::Perfect => {
-                    res.with_model(line_tr * note.object.now(res), |res| res.emit_at_origin(note.rotation(line), res.res_pack.info.fx_perfect()));
+                    res.with_model(line_tr * note.object.now(res), |res| {
+                        res.emit_at_origin(note.rotation(line), note.fx_color(res.res_pack.info.fx_perfect()), false)
+                    });
                     true
                 }
                 Judgement::Good => {
-                    res.with_model(line_tr * note.object.now(res), |res| res.emit_at_origin(note.rotation(line), res.res_pack.info.fx_good()));
+                    res.with_model(line_tr * note.object.now(res), |res| {
+                        res.emit_at_origin(note.rotation(line), note.fx_color(res.res_pack.info.fx_good()), true)
+                    });
                     true
                 }
                 Judgement::Bad => {
@@ -762,14 +1084,7 @@ impl Judge {
                 }
                 _ => false,
             } {
-                if let Some(sfx) = match note.kind {
-                    NoteKind::Click => Some(&mut res.sfx_click),
-                    NoteKind::Drag => Some(&mut res.sfx_drag),
-                    NoteKind::Flick => Some(&mut res.sfx_flick),
-                    _ => None,
-                } {
-                    play_sfx(sfx, &res.config);
-                }
+                play_note_sfx(res, note);
             }
         }
         for (line, (idx, st)) in chart.lines.iter().zip(self.notes.iter_mut()) {
@@ -786,7 +1101,7 @@ impl Judge {
     fn auto_play_update(&mut self, res: &mut Resource, chart: &mut Chart) {
         let t = res.time;
         let (judge_type, judge_time, fx_color) = if res.config.all_good {
-            (Judgement::Good, LIMIT_GOOD, res.res_pack.info.fx_good())
+            (Judgement::Good, self.profile.good, res.res_pack.info.fx_good())
         } else {
             (Judgement::Perfect, 0., res.res_pack.info.fx_perfect())
         };
@@ -811,7 +1126,7 @@ impl Judge {
                     break;
                 }
                 note.judge = if matches!(note.kind, NoteKind::Hold { .. }) {
-                    play_sfx(&mut res.sfx_click, &res.config);
+                    play_note_sfx(res, note);
                     self.judgements.borrow_mut().push((t, line_id as _, *id, Err(true)));
                     //println!("{}\t{}\t{}", t, note.time, t - note.time);
                     // 都是AutoPlay了为什么还要输出判定时间差
@@ -839,39 +1154,43 @@ impl Judge {
                 (note.object.now(res), note.kind.clone())
             };
             let line = &chart.lines[line_id];
+            let note = &line.notes[id as usize];
             match note_kind {
                 NoteKind::Click => {
                     self.commit(t, judge_type, line_id as _, id, 0.);
+                    self.push_timeline(line_id as _, id, note, judge_type, Some(0.));
                     res.with_model(line.now_transform(res, &chart.lines) * note_transform, |res| {
-                        res.emit_at_origin(line.notes[id as usize].rotation(line), fx_color)
+                        res.emit_at_origin(line.notes[id as usize].rotation(line), note.fx_color(fx_color), matches!(judge_type, Judgement::Good))
         
                     });
                 }
                 NoteKind::Hold { .. } => {
                     self.commit(t, judge_type, line_id as _, id, 0.);
+                    self.push_timeline(line_id as _, id, note, judge_type, Some(0.));
                 }
                 _ => {
                     self.commit(t, Judgement::Perfect, line_id as _, id, 0.);
+                    self.push_timeline(line_id as _, id, note, Judgement::Perfect, None);
                     res.with_model(line.now_transform(res, &chart.lines) * note_transform, |res| {
-                        res.emit_at_origin(line.notes[id as usize].rotation(line), res.res_pack.info.fx_perfect())
+                        res.emit_at_origin(line.notes[id as usize].rotation(line), note.fx_color(res.res_pack.info.fx_perfect()), false)
         
                     });
                 },
             };
-            if let Some(sfx) = match note_kind {
-                NoteKind::Click => Some(&mut res.sfx_click),
-                NoteKind::Drag => Some(&mut res.sfx_drag),
-                NoteKind::Flick => Some(&mut res.sfx_flick),
-                _ => None,
-            } {
-                play_sfx(sfx, &res.config);
+            if !matches!(note_kind, NoteKind::Hold { .. }) {
+                play_note_sfx(res, note);
             }
         }
     }
 
     #[inline]
     pub fn result(&self) -> PlayResult {
-        self.inner.result()
+        PlayResult {
+            replay: self.recording.clone(),
+            judge_profile: self.profile,
+            timeline: self.timeline.clone(),
+            ..self.inner.result()
+        }
     }
 
     #[inline]
@@ -885,7 +1204,11 @@ impl Judge {
     }
 }
 
-struct Handler(Vec<Touch>, i32, u32);
+fn zone_contains(zone: Option<(f32, f32)>, x: f32) -> bool {
+    zone.map_or(true, |(start, end)| start <= x && x < end)
+}
+
+struct Handler(Vec<Touch>, i32, u32, Vec<(KeyCode, bool)>);
 impl Handler {
     fn finalize(&mut self) {
         if is_mouse_button_down(MouseButton::Left) {
@@ -939,15 +1262,17 @@ impl EventHandler for Handler {
         });
     }
 
-    fn key_down_event(&mut self, _ctx: &mut miniquad::Context, _keycode: KeyCode, _keymods: miniquad::KeyMods, repeat: bool) {
+    fn key_down_event(&mut self, _ctx: &mut miniquad::Context, keycode: KeyCode, _keymods: miniquad::KeyMods, repeat: bool) {
         if !repeat {
             self.1 += 1;
             self.2 += 1;
+            self.3.push((keycode, true));
         }
     }
 
-    fn key_up_event(&mut self, _ctx: &mut miniquad::Context, _keycode: KeyCode, _keymods: miniquad::KeyMods) {
+    fn key_up_event(&mut self, _ctx: &mut miniquad::Context, keycode: KeyCode, _keymods: miniquad::KeyMods) {
         self.1 -= 1;
+        self.3.push((keycode, false));
     }
 }
 
@@ -961,6 +1286,34 @@ pub struct PlayResult {
     pub early: u32,
     pub late: u32,
     pub std: f32,
+    pub replay: Option<Replay>,
+    pub judge_profile: JudgeProfile,
+    pub timeline: Vec<TimelineEntry>,
+}
+
+impl PlayResult {
+    pub fn timeline_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self.timeline)?)
+    }
+
+    /// Writes the timeline as CSV with a header row. Offsets are empty for misses, drags and flicks.
+    pub fn write_timeline_csv(&self, mut w: impl Write) -> Result<()> {
+        writeln!(w, "line_id,note_id,note_time,offset,judgement,kind")?;
+        for entry in &self.timeline {
+            writeln!(
+                w,
+                "{},{},{},{},{:?},{}",
+                entry.line_id,
+                entry.note_id,
+                entry.note_time,
+                entry.offset.map(|it| it.to_string()).unwrap_or_default(),
+                entry.judgement,
+                kind_name(&entry.kind),
+            )?;
+        }
+        w.flush()?;
+        Ok(())
+    }
 }
 
 pub fn icon_index(score: u32, full_combo: bool) -> usize {
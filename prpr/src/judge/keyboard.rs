@@ -0,0 +1,108 @@
+use crate::{config::Config, replay::KeyEvent};
+use macroquad::prelude::KeyCode;
+use tracing::warn;
+
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    use KeyCode::*;
+    Some(match name.to_ascii_lowercase().as_str() {
+        "a" => A,
+        "b" => B,
+        "c" => C,
+        "d" => D,
+        "e" => E,
+        "f" => F,
+        "g" => G,
+        "h" => H,
+        "i" => I,
+        "j" => J,
+        "k" => K,
+        "l" => L,
+        "m" => M,
+        "n" => N,
+        "o" => O,
+        "p" => P,
+        "q" => Q,
+        "r" => R,
+        "s" => S,
+        "t" => T,
+        "u" => U,
+        "v" => V,
+        "w" => W,
+        "x" => X,
+        "y" => Y,
+        "z" => Z,
+        "0" => Key0,
+        "1" => Key1,
+        "2" => Key2,
+        "3" => Key3,
+        "4" => Key4,
+        "5" => Key5,
+        "6" => Key6,
+        "7" => Key7,
+        "8" => Key8,
+        "9" => Key9,
+        "space" => Space,
+        "enter" => Enter,
+        "tab" => Tab,
+        "up" => Up,
+        "down" => Down,
+        "left" => Left,
+        "right" => Right,
+        "," | "comma" => Comma,
+        "." | "period" => Period,
+        "/" | "slash" => Slash,
+        ";" | "semicolon" => Semicolon,
+        "'" | "apostrophe" => Apostrophe,
+        "[" | "leftbracket" => LeftBracket,
+        "]" | "rightbracket" => RightBracket,
+        "\\" | "backslash" => Backslash,
+        "-" | "minus" => Minus,
+        "=" | "equal" => Equal,
+        "leftshift" => LeftShift,
+        "rightshift" => RightShift,
+        _ => return None,
+    })
+}
+
+/// Keys used in [`KeyMode::Mapped`](crate::config::KeyMode::Mapped), resolved from [`Config`].
+///
+/// Lane keys split every judge line into equal horizontal zones from left to right, measured in
+/// line-local coordinates (the line spans `-1..1` when it's unrotated and centered). The outermost
+/// zones extend to infinity so that notes beyond the edges are still reachable. Nearest keys hit
+/// the earliest pending note regardless of its position.
+pub struct KeyMap {
+    keys: Vec<(KeyCode, Option<(f32, f32)>)>,
+}
+
+impl KeyMap {
+    pub fn new(config: &Config) -> Self {
+        let parse = |name: &String| {
+            let key = key_from_name(name);
+            if key.is_none() {
+                warn!("unknown key name: {name}");
+            }
+            key
+        };
+        let lanes: Vec<_> = config.key_lanes.iter().filter_map(parse).collect();
+        let n = lanes.len();
+        let mut keys: Vec<_> = lanes
+            .into_iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let start = if i == 0 { f32::NEG_INFINITY } else { i as f32 / n as f32 * 2. - 1. };
+                let end = if i + 1 == n { f32::INFINITY } else { (i + 1) as f32 / n as f32 * 2. - 1. };
+                (key, Some((start, end)))
+            })
+            .collect();
+        keys.extend(config.key_nearest.iter().filter_map(parse).map(|key| (key, None)));
+        Self { keys }
+    }
+
+    pub fn event(&self, key: KeyCode, pressed: bool) -> Option<KeyEvent> {
+        self.keys.iter().position(|it| it.0 == key).map(|id| KeyEvent {
+            id: id as u32,
+            zone: self.keys[id].1,
+            pressed,
+        })
+    }
+}
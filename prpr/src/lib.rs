@@ -1,3 +1,4 @@
+pub mod analysis;
 pub mod bin;
 pub mod config;
 pub mod core;
@@ -7,12 +8,19 @@ pub mod fs;
 pub mod info;
 pub mod judge;
 pub mod l10n;
+pub mod mixdown;
+pub mod offline;
+pub mod overlay;
 pub mod parse;
 pub mod particle;
+pub mod practice;
+pub mod remote;
+pub mod replay;
 pub mod scene;
 pub mod task;
 pub mod time;
 pub mod ui;
+pub mod watch;
 
 #[cfg(feature = "log")]
 pub mod log;
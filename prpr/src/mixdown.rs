@@ -0,0 +1,151 @@
+use crate::{
+    config::Config,
+    core::{Chart, Note, NoteKind, ResourcePack},
+    info::ChartInfo,
+};
+use anyhow::{Context, Result};
+use byteorder::{LittleEndian as LE, WriteBytesExt};
+use sasa::{AudioClip, Frame};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+pub struct HitSounds<'a> {
+    pub click: &'a AudioClip,
+    pub drag: &'a AudioClip,
+    pub flick: &'a AudioClip,
+}
+
+impl<'a> HitSounds<'a> {
+    pub fn from_pack(pack: &'a ResourcePack) -> Self {
+        Self {
+            click: &pack.sfx_click,
+            drag: &pack.sfx_drag,
+            flick: &pack.sfx_flick,
+        }
+    }
+
+    /// The clip played for `note`, which is its custom hit sound in `chart` if it has one.
+    fn get<'b>(&self, chart: &'b Chart, note: &Note) -> &'b AudioClip
+    where
+        'a: 'b,
+    {
+        if let Some(clip) = note.hitsound.as_ref().and_then(|it| chart.hitsounds.get(it)) {
+            return clip;
+        }
+        match note.kind {
+            NoteKind::Click | NoteKind::Hold { .. } => self.click,
+            NoteKind::Drag => self.drag,
+            NoteKind::Flick => self.flick,
+        }
+    }
+}
+
+/// Chart-time positions of the hit sounds autoplay plays along with the notes playing them: one for
+/// every real note, with holds sounding like clicks at their heads.
+pub fn hit_times(chart: &Chart) -> Vec<(f32, &Note)> {
+    let mut hits: Vec<_> = chart
+        .lines
+        .iter()
+        .flat_map(|line| line.notes.iter())
+        .filter(|note| !note.fake)
+        .map(|note| (note.time, note))
+        .collect();
+    hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+    hits
+}
+
+/// Mixes the music with hit sounds at every note, as interleaved stereo samples at the music's sample rate.
+///
+/// The music starts at the first sample and plays at `config.speed`, which also shifts the pitch, just
+/// like in game. Neither the chart nor the clips are played, so no audio device is needed.
+pub fn mixdown(chart: &Chart, info: &ChartInfo, config: &Config, music: &AudioClip, sounds: &HitSounds) -> (Vec<f32>, u32) {
+    let sample_rate = music.sample_rate();
+    let speed = config.speed as f64;
+    let offset = (chart.offset + config.offset + info.offset) as f64;
+    let hits: Vec<_> = hit_times(chart)
+        .into_iter()
+        .map(|(time, note)| ((time as f64 + offset) / speed, sounds.get(chart, note)))
+        .collect();
+
+    let music_length = music.frames().len() as f64 / sample_rate as f64 / speed;
+    let length = hits
+        .iter()
+        .map(|(at, clip)| at + clip.frames().len() as f64 / clip.sample_rate() as f64)
+        .fold(music_length, f64::max);
+    let mut out = vec![0.; (length * sample_rate as f64).ceil() as usize * 2];
+
+    mix_clip(&mut out, sample_rate, music, 0., speed, config.volume_music);
+    if config.volume_sfx > 1e-2 {
+        for (at, clip) in hits {
+            mix_clip(&mut out, sample_rate, clip, at, 1., config.volume_sfx);
+        }
+    }
+    (out, sample_rate)
+}
+
+pub fn write_mixdown(w: impl Write, chart: &Chart, info: &ChartInfo, config: &Config, music: &AudioClip, sounds: &HitSounds) -> Result<()> {
+    let (samples, sample_rate) = mixdown(chart, info, config, music, sounds);
+    write_wav(w, &samples, sample_rate)
+}
+
+pub fn write_mixdown_to(path: &Path, chart: &Chart, info: &ChartInfo, config: &Config, music: &AudioClip, sounds: &HitSounds) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    write_mixdown(BufWriter::new(file), chart, info, config, music, sounds)
+}
+
+/// Linearly interpolated frame at a fractional position, `None` past either end.
+pub(crate) fn sample_at(frames: &[Frame], pos: f64) -> Option<Frame> {
+    if pos < 0. {
+        return None;
+    }
+    let index = pos as usize;
+    let a = frames.get(index)?;
+    let Some(b) = frames.get(index + 1) else {
+        return Some(a.clone());
+    };
+    let t = (pos - index as f64) as f32;
+    Some(Frame(a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t))
+}
+
+/// Adds `clip`, played at `speed` and starting at `at` seconds, onto interleaved stereo `out`.
+pub(crate) fn mix_clip(out: &mut [f32], sample_rate: u32, clip: &AudioClip, at: f64, speed: f64, amplifier: f32) {
+    let frames = clip.frames();
+    let ratio = clip.sample_rate() as f64 / sample_rate as f64 * speed;
+    let start = (at * sample_rate as f64).round().max(0.) as usize;
+    let skip = (-at).max(0.) * speed * clip.sample_rate() as f64;
+    for (i, sample) in out.chunks_exact_mut(2).skip(start).enumerate() {
+        let Some(frame) = sample_at(frames, skip + i as f64 * ratio) else {
+            break;
+        };
+        sample[0] += frame.0 * amplifier;
+        sample[1] += frame.1 * amplifier;
+    }
+}
+
+/// Writes interleaved stereo samples as a 32-bit float WAV file.
+pub fn write_wav(mut w: impl Write, samples: &[f32], sample_rate: u32) -> Result<()> {
+    const CHANNELS: u16 = 2;
+    const BITS: u16 = 32;
+    let data_len = (samples.len() * 4) as u32;
+    w.write_all(b"RIFF")?;
+    w.write_u32::<LE>(36 + data_len)?;
+    w.write_all(b"WAVE")?;
+    w.write_all(b"fmt ")?;
+    w.write_u32::<LE>(16)?;
+    w.write_u16::<LE>(3)?; // IEEE float
+    w.write_u16::<LE>(CHANNELS)?;
+    w.write_u32::<LE>(sample_rate)?;
+    w.write_u32::<LE>(sample_rate * (CHANNELS * BITS / 8) as u32)?;
+    w.write_u16::<LE>(CHANNELS * BITS / 8)?;
+    w.write_u16::<LE>(BITS)?;
+    w.write_all(b"data")?;
+    w.write_u32::<LE>(data_len)?;
+    for sample in samples {
+        w.write_f32::<LE>(sample.clamp(-1., 1.))?;
+    }
+    w.flush()?;
+    Ok(())
+}
@@ -0,0 +1,246 @@
+use crate::{
+    config::{Config, Mods},
+    core::{Note, NoteKind},
+    ext::SafeTexture,
+    fs::FileSystem,
+    info::ChartInfo,
+    mixdown::{mix_clip, write_wav},
+    scene::{GameMode, GameScene, NextScene, Scene},
+    time::TimeManager,
+    ui::{TextPainter, Ui},
+};
+use anyhow::{Context, Result};
+use macroquad::prelude::*;
+use std::{
+    cell::Cell,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    rc::Rc,
+};
+
+pub struct OfflineOptions {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+}
+
+impl Default for OfflineOptions {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            fps: 60,
+        }
+    }
+}
+
+/// Where rendered frames go. Frames are raw RGBA8, top row first.
+pub enum FrameSink {
+    /// One `{index:06}.rgba` file per frame in the given directory.
+    Sequence(PathBuf),
+    /// All frames concatenated, e.g. to the stdin of an encoder.
+    Pipe(Box<dyn Write>),
+}
+
+impl FrameSink {
+    pub fn write_frame(&mut self, index: u64, data: &[u8]) -> Result<()> {
+        match self {
+            Self::Sequence(dir) => {
+                std::fs::create_dir_all(dir.as_path())?;
+                std::fs::write(dir.join(format!("{index:06}.rgba")), data)?;
+            }
+            Self::Pipe(w) => {
+                w.write_all(data)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        if let Self::Pipe(w) = self {
+            w.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders a chart in autoplay at a fixed frame rate into an offscreen target.
+///
+/// Time is driven by a manual clock, so rendering is independent of how fast frames are produced.
+/// The live audio output is muted; use [`OfflineRenderer::write_audio`] to get the mixed track
+/// after rendering.
+pub struct OfflineRenderer {
+    game: GameScene,
+    tm: TimeManager,
+    clock: Rc<Cell<f64>>,
+    target: RenderTarget,
+    options: OfflineOptions,
+
+    volume_music: f32,
+    volume_sfx: f32,
+    speed: f32,
+
+    frame: u64,
+    // (clock time, music time) when the chart started
+    music_start: Option<(f64, f64)>,
+    finished: bool,
+}
+
+impl OfflineRenderer {
+    pub async fn new(
+        info: ChartInfo,
+        mut config: Config,
+        fs: Box<dyn FileSystem>,
+        background: SafeTexture,
+        illustration: SafeTexture,
+        options: OfflineOptions,
+    ) -> Result<Self> {
+        config.mods.insert(Mods::AUTOPLAY);
+        config.interactive = false;
+        let volume_music = std::mem::replace(&mut config.volume_music, 0.);
+        let volume_sfx = std::mem::replace(&mut config.volume_sfx, 0.);
+        let speed = config.speed;
+
+        let mut game = GameScene::new(GameMode::Normal, info, config, fs, None, background, illustration, None, None).await?;
+        let clock = Rc::new(Cell::new(0.));
+        let mut tm = TimeManager::manual(Box::new({
+            let clock = Rc::clone(&clock);
+            move || clock.get()
+        }));
+        let target = render_target(options.width, options.height);
+        game.enter(&mut tm, Some(target))?;
+        Ok(Self {
+            game,
+            tm,
+            clock,
+            target,
+            options,
+
+            volume_music,
+            volume_sfx,
+            speed,
+
+            frame: 0,
+            music_start: None,
+            finished: false,
+        })
+    }
+
+    #[inline]
+    pub fn game(&self) -> &GameScene {
+        &self.game
+    }
+
+    #[inline]
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    #[inline]
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Steps the chart by one frame and returns its pixels, or `None` once the chart has ended.
+    pub fn render_frame(&mut self, painter: &mut TextPainter) -> Result<Option<Image>> {
+        if self.finished {
+            return Ok(None);
+        }
+        self.clock.set(self.frame as f64 / self.options.fps as f64);
+        self.game.update(&mut self.tm)?;
+        if !matches!(self.game.next_scene(&mut self.tm), NextScene::None) {
+            self.finished = true;
+            return Ok(None);
+        }
+        if self.music_start.is_none() && !self.game.is_starting() {
+            self.music_start = Some((self.clock.get(), self.tm.now()));
+        }
+        let vp = (0, 0, self.options.width as i32, self.options.height as i32);
+        let mut ui = Ui::new(painter, Some(vp));
+        ui.set_touches(Vec::new());
+        ui.scope(|ui| self.game.render(&mut self.tm, ui))?;
+        self.game.gl.flush();
+        self.frame += 1;
+        Ok(Some(self.target.texture.get_texture_data()))
+    }
+
+    /// Renders the whole chart into `sink`, returning the number of frames written.
+    pub async fn run(&mut self, painter: &mut TextPainter, sink: &mut FrameSink) -> Result<u64> {
+        while let Some(image) = self.render_frame(painter)? {
+            sink.write_frame(self.frame - 1, &image.bytes)?;
+            next_frame().await;
+        }
+        sink.finish()?;
+        Ok(self.frame)
+    }
+
+    fn offset(&self) -> f32 {
+        self.game.chart.offset + self.game.res.config.offset + self.game.res.info.offset
+    }
+
+    /// Music-time positions of every hit sound played so far, along with the notes playing them.
+    pub fn hits(&self) -> Vec<(f32, &Note)> {
+        let offset = self.offset();
+        let chart = &self.game.chart;
+        let mut hits: Vec<_> = self
+            .game
+            .judge
+            .judgements
+            .borrow()
+            .iter()
+            .filter_map(|(_, line_id, note_id, what)| {
+                let note = &chart.lines[*line_id as usize].notes[*note_id as usize];
+                match (what, &note.kind) {
+                    // hold heads only push a pending judgement, and that's when the sound plays
+                    (Ok(_), NoteKind::Hold { .. }) => None,
+                    _ => Some((note.time + offset, note)),
+                }
+            })
+            .collect();
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        hits
+    }
+
+    /// Mixes music and hit sounds aligned to the rendered frames, as interleaved stereo samples.
+    pub fn mix_audio(&self) -> (Vec<f32>, u32) {
+        let music = &self.game.res.music;
+        let sample_rate = music.sample_rate();
+        let length = self.frame as f64 / self.options.fps as f64;
+        let mut out = vec![0.; (length * sample_rate as f64).ceil() as usize * 2];
+        let Some((c0, m0)) = self.music_start else {
+            return (out, sample_rate);
+        };
+        let speed = self.speed as f64;
+        let to_clock = |m: f64| c0 + (m - m0) / speed;
+
+        mix_clip(&mut out, sample_rate, music, to_clock(0.), speed, self.volume_music);
+
+        if self.volume_sfx > 1e-2 {
+            let pack = &self.game.res.res_pack;
+            let chart = &self.game.chart;
+            for (time, note) in self.hits() {
+                let clip = match (note.hitsound.as_ref().and_then(|it| chart.hitsounds.get(it)), &note.kind) {
+                    (Some(clip), _) => clip,
+                    (None, NoteKind::Click | NoteKind::Hold { .. }) => &pack.sfx_click,
+                    (None, NoteKind::Drag) => &pack.sfx_drag,
+                    (None, NoteKind::Flick) => &pack.sfx_flick,
+                };
+                mix_clip(&mut out, sample_rate, clip, to_clock(time as f64), 1., self.volume_sfx);
+            }
+        }
+        (out, sample_rate)
+    }
+
+    /// Writes the mixed audio track as a 32-bit float stereo WAV file.
+    pub fn write_audio(&self, w: impl Write) -> Result<()> {
+        let (samples, sample_rate) = self.mix_audio();
+        write_wav(w, &samples, sample_rate)
+    }
+
+    pub fn write_audio_to(&self, path: impl Into<PathBuf>) -> Result<()> {
+        let path = path.into();
+        let file = File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+        self.write_audio(BufWriter::new(file))
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{
+    info::ChartInfo,
+    judge::{PlayResult, TimelineEntry},
+    practice::IterationResult,
+};
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver, SyncSender, TrySendError},
+};
+use tracing::warn;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SongInfo<'a> {
+    pub name: &'a str,
+    pub level: &'a str,
+    pub difficulty: f32,
+    pub composer: &'a str,
+    pub charter: &'a str,
+    pub illustrator: &'a str,
+    /// Length of the music, in seconds.
+    pub length: f32,
+    pub note_count: u32,
+}
+
+impl<'a> SongInfo<'a> {
+    pub fn new(info: &'a ChartInfo, length: f32, note_count: u32) -> Self {
+        Self {
+            name: &info.name,
+            level: &info.level,
+            difficulty: info.difficulty,
+            composer: &info.composer,
+            charter: &info.charter,
+            illustrator: &info.illustrator,
+            length,
+            note_count,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameState {
+    /// Chart time, in seconds.
+    pub time: f32,
+    /// Fraction of the music played, from 0 to 1.
+    pub progress: f32,
+    pub paused: bool,
+    pub score: u32,
+    pub combo: u32,
+    pub accuracy: f64,
+    pub counts: [u32; 4],
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum OverlayEvent<'a> {
+    /// Sent whenever a play (re)starts.
+    Start(SongInfo<'a>),
+    Frame(FrameState),
+    Judgement(&'a TimelineEntry),
+    /// Sent when an iteration of a practice loop is finished.
+    PracticeIteration(&'a IterationResult),
+    #[serde(rename_all = "camelCase")]
+    End {
+        score: u32,
+        accuracy: f64,
+        max_combo: u32,
+        counts: [u32; 4],
+        early: u32,
+        late: u32,
+    },
+}
+
+impl<'a> OverlayEvent<'a> {
+    pub fn end(result: &PlayResult) -> Self {
+        Self::End {
+            score: result.score,
+            accuracy: result.accuracy,
+            max_combo: result.max_combo,
+            counts: result.counts,
+            early: result.early,
+            late: result.late,
+        }
+    }
+}
+
+/// Receives live play events, e.g. to feed a stream overlay.
+pub trait OverlaySink {
+    /// A sink that returns an error is dropped.
+    fn send(&mut self, event: &OverlayEvent) -> Result<()>;
+}
+
+/// Events queued for a sink before new ones are dropped.
+const QUEUE_SIZE: usize = 256;
+
+/// Writes every event as one line of JSON.
+///
+/// Writing happens on a separate thread so that a slow reader never stalls the game; events that
+/// don't fit in the queue are dropped.
+pub struct NdjsonSink {
+    sender: SyncSender<Vec<u8>>,
+    dropping: bool,
+}
+
+impl NdjsonSink {
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+        std::thread::spawn(move || {
+            if let Err(err) = Self::run_writer(writer, receiver) {
+                warn!("failed to write overlay events: {err:?}");
+            }
+        });
+        Self { sender, dropping: false }
+    }
+
+    fn run_writer(mut writer: impl Write, receiver: Receiver<Vec<u8>>) -> Result<()> {
+        while let Ok(line) = receiver.recv() {
+            writer.write_all(&line)?;
+            for line in receiver.try_iter() {
+                writer.write_all(&line)?;
+            }
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn create(path: &str) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Failed to create {path}"))?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+
+    /// Connects to `addr`, which must resolve to a loopback address unless `allow_remote` is set.
+    pub fn connect(addr: &str, allow_remote: bool) -> Result<Self> {
+        let addrs: Vec<_> = addr.to_socket_addrs().with_context(|| format!("Failed to resolve {addr}"))?.collect();
+        if !allow_remote && !addrs.iter().all(|it| it.ip().is_loopback()) {
+            bail!("{addr} is not a loopback address, set `overlayAllowRemote` to send events to it");
+        }
+        let stream = TcpStream::connect(&addrs[..]).with_context(|| format!("Failed to connect to {addr}"))?;
+        stream.set_nodelay(true)?;
+        Ok(Self::new(stream))
+    }
+}
+
+impl OverlaySink for NdjsonSink {
+    fn send(&mut self, event: &OverlayEvent) -> Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        match self.sender.try_send(line) {
+            Ok(()) => self.dropping = false,
+            Err(TrySendError::Full(_)) => {
+                if !self.dropping {
+                    self.dropping = true;
+                    warn!("overlay sink can't keep up, dropping events");
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => bail!("overlay writer stopped"),
+        }
+        Ok(())
+    }
+}
+
+/// Creates a sink from a target such as `tcp://127.0.0.1:9000`, `unix:///tmp/prpr.sock` (Unix only)
+/// or a plain file path.
+///
+/// `tcp://` targets must be on loopback unless `allow_remote` is set, see [`NdjsonSink::connect`].
+pub fn sink_from_target(target: &str, allow_remote: bool) -> Result<Box<dyn OverlaySink>> {
+    if let Some(addr) = target.strip_prefix("tcp://") {
+        return Ok(Box::new(NdjsonSink::connect(addr, allow_remote)?));
+    }
+    #[cfg(unix)]
+    if let Some(path) = target.strip_prefix("unix://") {
+        let stream = std::os::unix::net::UnixStream::connect(path).with_context(|| format!("Failed to connect to {path}"))?;
+        return Ok(Box::new(NdjsonSink::new(stream)));
+    }
+    Ok(Box::new(NdjsonSink::create(target)?))
+}
+
+pub fn broadcast(sinks: &mut Vec<Box<dyn OverlaySink>>, event: &OverlayEvent) {
+    sinks.retain_mut(|sink| match sink.send(event) {
+        Ok(()) => true,
+        Err(err) => {
+            warn!("overlay sink failed, dropping it: {err:?}");
+            false
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io,
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    };
+
+    fn frame() -> OverlayEvent<'static> {
+        OverlayEvent::Frame(FrameState {
+            time: 1.5,
+            progress: 0.1,
+            paused: false,
+            score: 0,
+            combo: 0,
+            accuracy: 1.,
+            counts: [0; 4],
+        })
+    }
+
+    /// Blocks every write until a token is sent.
+    struct Gate(Receiver<()>, Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Gate {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.recv().map_err(|_| io::ErrorKind::BrokenPipe)?;
+            self.1.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn slow_reader() {
+        let (open, gate) = mpsc::channel();
+        let output = Arc::default();
+        let mut sink = NdjsonSink::new(Gate(gate, Arc::clone(&output)));
+        // the writer is stuck, so these must not block
+        for _ in 0..QUEUE_SIZE * 4 {
+            sink.send(&frame()).unwrap();
+        }
+        assert!(sink.dropping);
+        drop(open);
+        // the writer fails once the gate is gone and the sink reports it
+        while sink.send(&frame()).is_ok() {
+            std::thread::yield_now();
+        }
+        assert!(output.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn loopback_only() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(sink_from_target(&format!("tcp://127.0.0.1:{port}"), false).is_ok());
+        // refused before connecting
+        assert!(sink_from_target("tcp://192.0.2.1:9000", false).is_err());
+    }
+}
@@ -9,7 +9,10 @@ pub use pgr::parse_phigros;
 pub use pgr::parse_phigros_fv1;
 
 mod rpe;
-pub use rpe::{parse_rpe, RPE_HEIGHT, RPE_WIDTH};
+pub use rpe::{export_rpe, parse_rpe, RPE_HEIGHT, RPE_WIDTH};
+
+mod validate;
+pub use validate::{validate, validate_chart, Diagnostic, Severity};
 
 pub(crate) fn process_lines(v: &mut [crate::core::JudgeLine]) {
     use crate::ext::NotNanExt;
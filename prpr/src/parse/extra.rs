@@ -2,11 +2,14 @@ crate::tl_file!("parser" ptl);
 
 use super::RPE_TWEEN_MAP;
 use crate::{
-    core::{Anim, BpmList, ChartExtra, ClampedTween, Effect, Keyframe, StaticTween, Triple, Tweenable, Uniform, Video, EPS},
+    core::{
+        Anim, BezierTween, BpmList, ChartExtra, ClampedTween, Effect, HitSoundMapping, Keyframe, ParamType, PiecewiseTween, RangedTween, SpringTween,
+        StaticTween, StepsTween, Triple, TweenFunction, TweenRegistry, Tweenable, Uniform, Video, EPS,
+    },
     ext::ScaleType,
     fs::FileSystem,
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use macroquad::prelude::{Color, Vec2};
 use serde::Deserialize;
 use std::{collections::HashMap, rc::Rc};
@@ -27,13 +30,78 @@ struct ExtKeyframe<T> {
     easing_left: f32,
     #[serde(default = "f32_one")]
     easing_right: f32,
-    easing_type: i32,
+    easing_type: EasingRef,
     start: T,
     end: T,
     start_time: Triple,
     end_time: Triple,
 }
 
+/// Either an RPE easing id or the name of an easing declared in `easings`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EasingRef {
+    Id(i32),
+    Name(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum EasingDef {
+    CubicBezier {
+        points: [f32; 4],
+    },
+    #[serde(rename_all = "camelCase")]
+    Steps {
+        steps: u32,
+        #[serde(default)]
+        jump_start: bool,
+    },
+    Spring {
+        damping: f32,
+        frequency: f32,
+    },
+    Piecewise {
+        points: Vec<(f32, f32)>,
+    },
+}
+
+impl EasingDef {
+    fn build(self) -> Result<Rc<dyn TweenFunction>> {
+        Ok(match self {
+            EasingDef::CubicBezier { points: [x1, y1, x2, y2] } => {
+                if !(0.0..=1.0).contains(&x1) || !(0.0..=1.0).contains(&x2) {
+                    bail!("bezier x coordinates must be within [0, 1]");
+                }
+                Rc::new(BezierTween::new((x1, y1), (x2, y2)))
+            }
+            EasingDef::Steps { steps, jump_start } => {
+                if steps == 0 {
+                    bail!("steps must be positive");
+                }
+                Rc::new(StepsTween { steps, jump_start })
+            }
+            EasingDef::Spring { damping, frequency } => Rc::new(SpringTween::new(damping, frequency)),
+            EasingDef::Piecewise { points } => {
+                if points.len() < 2 {
+                    bail!("at least two points are required");
+                }
+                Rc::new(PiecewiseTween::new(points))
+            }
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct ExtEasing {
+    name: String,
+    /// Lets RPE events use this easing through their `easingType`.
+    #[serde(default)]
+    id: Option<i32>,
+    #[serde(flatten)]
+    def: EasingDef,
+}
+
 #[derive(Default, Deserialize)]
 #[serde(untagged)]
 enum ExtAnim<V> {
@@ -44,11 +112,20 @@ enum ExtAnim<V> {
 }
 
 impl<V> ExtAnim<V> {
-    fn into<T: Tweenable>(self, r: &mut BpmList, default: Option<T>) -> Anim<T>
+    /// Every value this can take on, ignoring easings.
+    fn values(&self) -> Vec<&V> {
+        match self {
+            ExtAnim::Default => Vec::new(),
+            ExtAnim::Fixed(value) => vec![value],
+            ExtAnim::Keyframes(events) => events.iter().flat_map(|e| [&e.start, &e.end]).collect(),
+        }
+    }
+
+    fn into<T: Tweenable>(self, r: &mut BpmList, default: Option<T>, tweens: &TweenRegistry) -> Result<Anim<T>>
     where
         V: Into<T>,
     {
-        match self {
+        Ok(match self {
             ExtAnim::Default => Anim::default(),
             ExtAnim::Fixed(value) => Anim::fixed(value.into()),
             ExtAnim::Keyframes(events) => {
@@ -59,12 +136,25 @@ impl<V> ExtAnim<V> {
                     }
                 }
                 for e in events {
+                    let full = e.easing_left.abs() < EPS && (e.easing_right - 1.0).abs() < EPS;
+                    // built-in ids take precedence, unknown ones fall back to linear
+                    let (builtin, custom) = match &e.easing_type {
+                        EasingRef::Id(id) if (1..RPE_TWEEN_MAP.len() as i32).contains(id) => (*id, None),
+                        EasingRef::Id(id) => (1, tweens.get_id(*id)),
+                        EasingRef::Name(name) => (1, Some(tweens.get(name).ok_or_else(|| ptl!(err "easing-not-found", "name" => name.clone()))?)),
+                    };
                     kfs.push(Keyframe {
                         time: r.time(&e.start_time),
                         value: e.start.into(),
-                        tween: {
-                            let tween = RPE_TWEEN_MAP.get(e.easing_type.max(1) as usize).copied().unwrap_or(RPE_TWEEN_MAP[0]);
-                            if e.easing_left.abs() < EPS && (e.easing_right - 1.0).abs() < EPS {
+                        tween: if let Some(custom) = custom {
+                            if full {
+                                custom
+                            } else {
+                                Rc::new(RangedTween::new(custom, e.easing_left..e.easing_right))
+                            }
+                        } else {
+                            let tween = RPE_TWEEN_MAP[builtin as usize];
+                            if full {
                                 StaticTween::get_rc(tween)
                             } else {
                                 Rc::new(ClampedTween::new(tween, e.easing_left..e.easing_right))
@@ -75,7 +165,7 @@ impl<V> ExtAnim<V> {
                 }
                 Anim::new(kfs)
             }
-        }
+        })
     }
 }
 
@@ -110,14 +200,25 @@ enum Variable {
     Color(ExtAnim<[u8; 4]>),
 }
 
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ShaderForm {
+    Single(String),
+    Passes(Vec<String>),
+}
+
 #[derive(Deserialize)]
 struct ExtEffect {
     start: Triple,
     end: Triple,
-    shader: String,
+    shader: ShaderForm,
     #[serde(default)]
     vars: HashMap<String, Variable>,
     #[serde(default)]
+    textures: HashMap<String, String>,
+    #[serde(default)]
+    line: Option<usize>,
+    #[serde(default)]
     global: bool,
 }
 
@@ -134,51 +235,111 @@ struct ExtVideo {
     dim: ExtAnim<f32>,
 }
 
+#[derive(Deserialize)]
+struct ExtHitSound {
+    #[serde(default)]
+    line: Option<usize>,
+    time: Triple,
+    sound: String,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Extra {
     bpm: BpmForm,
     #[serde(default)]
+    easings: Vec<ExtEasing>,
+    #[serde(default)]
     effects: Vec<ExtEffect>,
     #[serde(default)]
     videos: Vec<ExtVideo>,
+    #[serde(default)]
+    hitsounds: Vec<ExtHitSound>,
 }
 
-async fn parse_effect(r: &mut BpmList, rpe: ExtEffect, fs: &mut dyn FileSystem) -> Result<Effect> {
+/// Checks `vars` against the schemas of the preset shaders, so that typos don't go unnoticed.
+fn check_vars(shaders: &[String], vars: &HashMap<String, Variable>) -> Result<()> {
+    // custom shaders can declare anything
+    if shaders.iter().any(|it| it.starts_with('/')) {
+        return Ok(());
+    }
+    let presets: Vec<_> = shaders.iter().filter_map(|it| Effect::preset(it)).collect();
+    for (name, var) in vars {
+        let Some(param) = presets.iter().find_map(|it| it.param(name)) else {
+            let available = presets.iter().flat_map(|it| it.params.iter().map(|it| it.name.as_str())).collect::<Vec<_>>().join(", ");
+            ptl!(bail "param-not-found", "name" => name.clone(), "shader" => shaders.join(", "), "available" => available);
+        };
+        let values: Vec<f32> = match (var, param.kind) {
+            (Variable::Float(anim), ParamType::Float | ParamType::Int) => anim.values().into_iter().copied().collect(),
+            (Variable::Vec2(_), ParamType::Vec2) | (Variable::Color(_), ParamType::Color) => Vec::new(),
+            _ => ptl!(bail "param-type-mismatch", "name" => name.clone(), "expected" => param.kind.name()),
+        };
+        for value in values {
+            if param.kind == ParamType::Int && value.fract() != 0. {
+                ptl!(bail "param-not-integer", "name" => name.clone(), "value" => value);
+            }
+            if let Some((min, max)) = param.range {
+                if !(min..=max).contains(&value) {
+                    ptl!(bail "param-out-of-range", "name" => name.clone(), "value" => value, "min" => min, "max" => max);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn parse_effect(r: &mut BpmList, rpe: ExtEffect, fs: &mut dyn FileSystem, tweens: &TweenRegistry) -> Result<Effect> {
     let range = r.time(&rpe.start)..r.time(&rpe.end);
+    let shaders = match rpe.shader {
+        ShaderForm::Single(shader) => vec![shader],
+        ShaderForm::Passes(shaders) => shaders,
+    };
+    let mut sources = Vec::with_capacity(shaders.len());
+    for shader in &shaders {
+        sources.push(if let Some(path) = shader.strip_prefix('/') {
+            String::from_utf8(fs.load_file(path).await?).with_context(|| ptl!("shader-load-failed", "path" => path))?
+        } else {
+            Effect::get_preset(shader)
+                .ok_or_else(|| ptl!(err "shader-not-found", "shader" => shader.clone()))?
+                .to_owned()
+        });
+    }
+    check_vars(&shaders, &rpe.vars)?;
     let vars = rpe
         .vars
         .into_iter()
         .map(|(name, var)| -> Result<Box<dyn Uniform>> {
             Ok(match var {
-                Variable::Float(events) => Box::new((name, events.into::<f32>(r, None))),
-                Variable::Vec2(events) => Box::new((name, events.into::<Vec2>(r, None))),
-                Variable::Color(events) => Box::new((name, events.into::<Color>(r, None))),
+                Variable::Float(events) => Box::new((name, events.into::<f32>(r, None, tweens)?)),
+                Variable::Vec2(events) => Box::new((name, events.into::<Vec2>(r, None, tweens)?)),
+                Variable::Color(events) => Box::new((name, events.into::<Color>(r, None, tweens)?)),
             })
         })
         .collect::<Result<_>>()?;
-    let string;
-    Effect::new(
-        range,
-        if let Some(path) = rpe.shader.strip_prefix('/') {
-            string = String::from_utf8(fs.load_file(path).await?).with_context(|| ptl!("shader-load-failed", "path" => path))?;
-            &string
-        } else {
-            Effect::get_preset(&rpe.shader).ok_or_else(|| ptl!(err "shader-not-found", "shader" => rpe.shader))?
-        },
-        vars,
-        rpe.global,
-    )
+    let mut textures = Vec::with_capacity(rpe.textures.len());
+    for (name, path) in rpe.textures {
+        let bytes = fs.load_file(&path).await.with_context(|| ptl!("texture-load-failed", "path" => path.clone()))?;
+        textures.push((name, image::load_from_memory(&bytes).with_context(|| ptl!("texture-load-failed", "path" => path))?.into()));
+    }
+    let mut effect = Effect::multi_pass(range, &sources.iter().map(String::as_str).collect::<Vec<_>>(), vars, textures, rpe.global)?;
+    effect.line = rpe.line;
+    Ok(effect)
 }
 
 pub async fn parse_extra(source: &str, fs: &mut dyn FileSystem) -> Result<ChartExtra> {
     let ext: Extra = serde_json::from_str(source).with_context(|| ptl!("json-parse-failed"))?;
     let mut r: BpmList = ext.bpm.into();
+    let mut tweens = TweenRegistry::default();
+    for easing in ext.easings {
+        let name = easing.name;
+        let tween = easing.def.build().with_context(|| ptl!("easing-invalid", "name" => name.clone()))?;
+        tweens.register(name, easing.id, tween);
+    }
     let mut effects = Vec::new();
     let mut global_effects = Vec::new();
     for (id, effect) in ext.effects.into_iter().enumerate() {
         (if effect.global { &mut global_effects } else { &mut effects }).push(
-            parse_effect(&mut r, effect, fs)
+            parse_effect(&mut r, effect, fs, &tweens)
                 .await
                 .with_context(|| ptl!("effect-location", "id" => id))?,
         );
@@ -187,20 +348,33 @@ pub async fn parse_extra(source: &str, fs: &mut dyn FileSystem) -> Result<ChartE
     for video in ext.videos {
         videos.push(
             Video::new(
-                fs.load_file(&video.path)
+                fs.open_reader(&video.path)
                     .await
                     .with_context(|| ptl!("video-load-failed", "path" => video.path.clone()))?,
                 r.time(&video.time),
                 video.scale,
-                video.alpha.into(&mut r, Some(1.)),
-                video.dim.into(&mut r, Some(0.)),
+                video.alpha.into(&mut r, Some(1.), &tweens)?,
+                video.dim.into(&mut r, Some(0.), &tweens)?,
             )
             .with_context(|| ptl!("video-load-failed", "path" => video.path))?,
         );
     }
+    let hitsounds = ext
+        .hitsounds
+        .into_iter()
+        .map(|it| HitSoundMapping {
+            line: it.line,
+            beat: it.time.beats(),
+            time: r.time(&it.time),
+            path: it.sound,
+        })
+        .collect();
     Ok(ChartExtra {
         effects,
         global_effects,
         videos,
+        tweens,
+        hitsounds,
+        source: Some(source.to_owned()),
     })
 }
@@ -10,6 +10,7 @@ use crate::{
     judge::JudgeStatus,
 };
 use anyhow::{bail, Context, Result};
+use macroquad::prelude::WHITE;
 use std::cell::RefCell;
 use tracing::warn;
 
@@ -175,6 +176,8 @@ fn parse_judge_line(mut pec: PECJudgeLine, id: usize, max_time: f32) -> Result<J
         z_index: 0,
         show_below: false,
         attach_ui: None,
+        group: 0,
+        link_groups: Vec::new(),
 
         cache,
     })
@@ -274,6 +277,11 @@ pub fn parse_pec(source: &str, extra: ChartExtra) -> Result<Chart> {
                         fake,
                         judge: JudgeStatus::NotJudged,
                         format: false,
+
+                        tint: WHITE,
+                        hit_fx_color: None,
+                        judge_scale: 1.,
+                        hitsound: None,
                     });
                     if it.next() == Some("#") {
                         last_note!().speed = it.take_f32()?;
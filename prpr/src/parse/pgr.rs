@@ -10,6 +10,7 @@ use crate::{
     judge::JudgeStatus,
 };
 use anyhow::{Context, Result};
+use macroquad::prelude::WHITE;
 use serde::Deserialize;
 use std::cell::RefCell;
 use tracing::warn;
@@ -241,6 +242,11 @@ fn parse_notes(r: f32, mut pgr: Vec<PgrNote>, speed: &mut AnimFloat, height: &mu
                 fake: false,
                 judge: JudgeStatus::NotJudged,
                 format: true,
+
+                tint: WHITE,
+                hit_fx_color: None,
+                judge_scale: 1.,
+                hitsound: None,
             })
         })
         .collect()
@@ -271,6 +277,8 @@ fn parse_judge_line(pgr: PgrJudgeLine, max_time: f32) -> Result<JudgeLine> {
         z_index: 0,
         show_below: false,
         attach_ui: None,
+        group: 0,
+        link_groups: Vec::new(),
 
         cache,
     })
@@ -301,6 +309,8 @@ fn parse_judge_line_fv1(pgr: PgrJudgeLine, max_time: f32) -> Result<JudgeLine> {
         z_index: 0,
         show_below: false,
         attach_ui: None,
+        group: 0,
+        link_groups: Vec::new(),
 
         cache,
     })
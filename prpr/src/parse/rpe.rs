@@ -1,25 +1,28 @@
 crate::tl_file!("parser" ptl);
 
-use super::{process_lines, RPE_TWEEN_MAP};
+use super::{process_lines, Diagnostic, Severity, RPE_TWEEN_MAP};
 use crate::{
     core::{
-        Anim, AnimFloat, AnimVector, BezierTween, BpmList, Chart, ChartExtra, ChartSettings, ClampedTween, CtrlObject, JudgeLine, JudgeLineCache,
-        JudgeLineKind, Keyframe, Note, NoteKind, Object, StaticTween, Triple, TweenFunction, Tweenable, UIElement, EPS, HEIGHT_RATIO,
+        Anim, AnimFloat, AnimVector, BezierTween, BpmList, Chart, ChartExtra, ChartSettings, ClampedTween, CtrlObject, EventKind, JudgeLine,
+        JudgeLineCache, JudgeLineKind, Keyframe, LinkedEvent, Note, NoteKind, Object, RangedTween, StaticTween, Triple, TweenFunction, TweenId,
+        TweenRegistry, Tweenable, UIElement, EPS, HEIGHT_RATIO,
     },
     ext::NotNanExt,
     fs::FileSystem,
+    info::ChartInfo,
     judge::JudgeStatus,
 };
 use anyhow::{Context, Result};
 use macroquad::prelude::{Color, WHITE};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use tracing::warn;
 
 pub const RPE_WIDTH: f32 = 1350.;
 pub const RPE_HEIGHT: f32 = 900.;
 const SPEED_RATIO: f32 = 10. / 45. / HEIGHT_RATIO;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct RPEBpmItem {
     bpm: f32,
@@ -35,10 +38,16 @@ fn f32_one() -> f32 {
     1.
 }
 
-#[derive(Deserialize)]
+fn is_zero(v: &i32) -> bool {
+    *v == 0
+}
+
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct RPEEvent<T = f32> {
-    // TODO linkgroup
+    /// Events sharing a link group are edited together in RPE. Playback ignores it, see [`LinkedEvent`].
+    #[serde(default, skip_serializing_if = "is_zero")]
+    linkgroup: i32,
     #[serde(default = "f32_zero")]
     easing_left: f32,
     #[serde(default = "f32_one")]
@@ -54,7 +63,7 @@ struct RPEEvent<T = f32> {
     end_time: Triple,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct RPECtrlEvent {
     easing: u8,
@@ -63,27 +72,33 @@ struct RPECtrlEvent {
     value: HashMap<String, f32>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct RPESpeedEvent {
-    // TODO linkgroup
+    #[serde(default, skip_serializing_if = "is_zero")]
+    linkgroup: i32,
     start_time: Triple,
     end_time: Triple,
     start: f32,
     end: f32,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct RPEEventLayer {
+    #[serde(skip_serializing_if = "Option::is_none")]
     alpha_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     move_x_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     move_y_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     rotate_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     speed_events: Option<Vec<RPESpeedEvent>>,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 struct RGBColor(u8, u8, u8);
 impl From<RGBColor> for Color {
     fn from(RGBColor(r, g, b): RGBColor) -> Self {
@@ -91,18 +106,24 @@ impl From<RGBColor> for Color {
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct RPEExtendedEvents {
+    #[serde(skip_serializing_if = "Option::is_none")]
     color_events: Option<Vec<RPEEvent<RGBColor>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     text_events: Option<Vec<RPEEvent<String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     scale_x_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     scale_y_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     incline_events: Option<Vec<RPEEvent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     paint_events: Option<Vec<RPEEvent>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct RPENote {
     // TODO above == 0? what does that even mean?
@@ -118,13 +139,25 @@ struct RPENote {
     speed: f32,
     is_fake: u8,
     visible_time: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tint: Option<RGBColor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tint_hit_effects: Option<RGBColor>,
+    #[serde(default = "f32_one")]
+    judge_area: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hitsound: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct RPEJudgeLine {
-    // TODO group
-    // TODO bpmfactor
+    /// Index into [`RPEChart::judge_line_group`].
+    #[serde(rename = "Group", default)]
+    group: usize,
+    /// The line runs at the chart's BPM divided by this.
+    #[serde(rename = "bpmfactor", default = "f32_one")]
+    bpm_factor: f32,
     #[serde(rename = "Name")]
     name: String,
     #[serde(rename = "Texture")]
@@ -132,12 +165,13 @@ struct RPEJudgeLine {
     #[serde(rename = "father")]
     parent: Option<isize>,
     event_layers: Vec<Option<RPEEventLayer>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     extended: Option<RPEExtendedEvents>,
     notes: Option<Vec<RPENote>>,
     is_cover: u8,
     #[serde(default)]
     z_order: i32,
-    #[serde(rename = "attachUI")]
+    #[serde(rename = "attachUI", skip_serializing_if = "Option::is_none")]
     attach_ui: Option<UIElement>,
 
     #[serde(default)]
@@ -150,24 +184,48 @@ struct RPEJudgeLine {
     y_control: Vec<RPECtrlEvent>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct RPEMetadata {
     offset: i32,
+
+    // only used when exporting
+    #[serde(rename = "RPEVersion", default)]
+    rpe_version: i32,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    song: String,
+    #[serde(default)]
+    background: String,
+    #[serde(default)]
+    charter: String,
+    #[serde(default)]
+    composer: String,
+    #[serde(default)]
+    level: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct RPEChart {
     #[serde(rename = "META")]
     meta: RPEMetadata,
     #[serde(rename = "BPMList")]
     bpm_list: Vec<RPEBpmItem>,
+    #[serde(default)]
+    judge_line_group: Vec<String>,
     judge_line_list: Vec<RPEJudgeLine>,
 }
 
 type BezierMap = HashMap<(u16, i16, i16), Rc<dyn TweenFunction>>;
 
+struct Tweens<'a> {
+    bezier: BezierMap,
+    /// Easings declared in `extra.json`, used for ids RPE doesn't have.
+    custom: &'a TweenRegistry,
+}
+
 fn bezier_key<T>(event: &RPEEvent<T>) -> (u16, i16, i16) {
     let p = &event.bezier_points;
     let int = |p: f32| (p * 100.).round() as i16;
@@ -178,7 +236,7 @@ fn parse_events<T: Tweenable, V: Clone + Into<T>>(
     r: &mut BpmList,
     rpe: &[RPEEvent<V>],
     default: Option<T>,
-    bezier_map: &BezierMap,
+    tweens: &Tweens,
 ) -> Result<Anim<T>> {
     let mut kfs = Vec::new();
     if rpe.len() > 0 {
@@ -194,9 +252,21 @@ fn parse_events<T: Tweenable, V: Clone + Into<T>>(
             value: e.start.clone().into(),
             tween: {
                 let tween = RPE_TWEEN_MAP.get(e.easing_type.max(1) as usize).copied().unwrap_or(RPE_TWEEN_MAP[0]);
+                let full = e.easing_left.abs() < EPS && (e.easing_right - 1.0).abs() < EPS;
+                let custom = if (1..RPE_TWEEN_MAP.len() as i32).contains(&e.easing_type) {
+                    None
+                } else {
+                    tweens.custom.get_id(e.easing_type)
+                };
                 if e.bezier != 0 {
-                    Rc::clone(&bezier_map[&bezier_key(e)])
-                } else if e.easing_left.abs() < EPS && (e.easing_right - 1.0).abs() < EPS {
+                    Rc::clone(&tweens.bezier[&bezier_key(e)])
+                } else if let Some(custom) = custom {
+                    if full {
+                        custom
+                    } else {
+                        Rc::new(RangedTween::new(custom, e.easing_left..e.easing_right))
+                    }
+                } else if full {
                     StaticTween::get_rc(tween)
                 } else {
                     Rc::new(ClampedTween::new(tween, e.easing_left..e.easing_right))
@@ -335,6 +405,11 @@ fn parse_notes(r: &mut BpmList, rpe: Vec<RPENote>, height: &mut AnimFloat) -> Re
                 fake: note.is_fake != 0,
                 judge: JudgeStatus::NotJudged,
                 format: false,
+
+                tint: note.tint.map_or(WHITE, Color::from),
+                hit_fx_color: note.tint_hit_effects.map(Color::from),
+                judge_scale: if note.judge_area.is_finite() && note.judge_area > 0. { note.judge_area } else { 1. },
+                hitsound: note.hitsound,
             })
         })
         .collect()
@@ -353,7 +428,40 @@ fn parse_ctrl_events(rpe: &[RPECtrlEvent], key: &str) -> AnimFloat {
     )
 }
 
-async fn parse_judge_line(r: &mut BpmList, rpe: RPEJudgeLine, max_time: f32, fs: &mut dyn FileSystem, bezier_map: &BezierMap) -> Result<JudgeLine> {
+fn parse_link_groups<T>(r: &mut BpmList, kind: EventKind, events: Option<&Vec<RPEEvent<T>>>, out: &mut Vec<LinkedEvent>) {
+    out.extend(events.into_iter().flatten().filter(|e| e.linkgroup != 0).map(|e| LinkedEvent {
+        kind,
+        time: r.time(&e.start_time),
+        group: e.linkgroup,
+    }));
+}
+
+fn parse_line_link_groups(r: &mut BpmList, rpe: &RPEJudgeLine) -> Vec<LinkedEvent> {
+    let mut res = Vec::new();
+    for layer in rpe.event_layers.iter().flatten() {
+        parse_link_groups(r, EventKind::Alpha, layer.alpha_events.as_ref(), &mut res);
+        parse_link_groups(r, EventKind::MoveX, layer.move_x_events.as_ref(), &mut res);
+        parse_link_groups(r, EventKind::MoveY, layer.move_y_events.as_ref(), &mut res);
+        parse_link_groups(r, EventKind::Rotate, layer.rotate_events.as_ref(), &mut res);
+        res.extend(layer.speed_events.iter().flatten().filter(|e| e.linkgroup != 0).map(|e| LinkedEvent {
+            kind: EventKind::Speed,
+            time: r.time(&e.start_time),
+            group: e.linkgroup,
+        }));
+    }
+    if let Some(e) = &rpe.extended {
+        parse_link_groups(r, EventKind::Color, e.color_events.as_ref(), &mut res);
+        parse_link_groups(r, EventKind::Text, e.text_events.as_ref(), &mut res);
+        parse_link_groups(r, EventKind::ScaleX, e.scale_x_events.as_ref(), &mut res);
+        parse_link_groups(r, EventKind::ScaleY, e.scale_y_events.as_ref(), &mut res);
+        parse_link_groups(r, EventKind::Incline, e.incline_events.as_ref(), &mut res);
+        parse_link_groups(r, EventKind::Paint, e.paint_events.as_ref(), &mut res);
+    }
+    res
+}
+
+async fn parse_judge_line(r: &mut BpmList, rpe: RPEJudgeLine, max_time: f32, fs: &mut dyn FileSystem, tweens: &Tweens) -> Result<JudgeLine> {
+    let link_groups = parse_line_link_groups(r, &rpe);
     let event_layers: Vec<_> = rpe.event_layers.into_iter().flatten().collect();
     fn events_with_factor(
         r: &mut BpmList,
@@ -361,11 +469,11 @@ async fn parse_judge_line(r: &mut BpmList, rpe: RPEJudgeLine, max_time: f32, fs:
         get: impl Fn(&RPEEventLayer) -> &Option<Vec<RPEEvent>>,
         factor: f32,
         desc: &str,
-        bezier_map: &BezierMap,
+        tweens: &Tweens,
     ) -> Result<AnimFloat> {
         let anis: Vec<_> = event_layers
             .iter()
-            .filter_map(|it| get(it).as_ref().map(|es| parse_events(r, es, None, bezier_map)))
+            .filter_map(|it| get(it).as_ref().map(|es| parse_events(r, es, None, tweens)))
             .collect::<Result<_>>()
             .with_context(|| ptl!("type-events-parse-failed", "type" => desc))?;
         let mut res = AnimFloat::chain(anis);
@@ -377,17 +485,17 @@ async fn parse_judge_line(r: &mut BpmList, rpe: RPEJudgeLine, max_time: f32, fs:
     let cache = JudgeLineCache::new(&mut notes);
     Ok(JudgeLine {
         object: Object {
-            alpha: events_with_factor(r, &event_layers, |it| &it.alpha_events, 1. / 255., "alpha", bezier_map)?,
-            rotation: events_with_factor(r, &event_layers, |it| &it.rotate_events, -1., "rotate", bezier_map)?,
+            alpha: events_with_factor(r, &event_layers, |it| &it.alpha_events, 1. / 255., "alpha", tweens)?,
+            rotation: events_with_factor(r, &event_layers, |it| &it.rotate_events, -1., "rotate", tweens)?,
             translation: AnimVector(
-                events_with_factor(r, &event_layers, |it| &it.move_x_events, 2. / RPE_WIDTH, "move X", bezier_map)?,
-                events_with_factor(r, &event_layers, |it| &it.move_y_events, 2. / RPE_HEIGHT, "move Y", bezier_map)?,
+                events_with_factor(r, &event_layers, |it| &it.move_x_events, 2. / RPE_WIDTH, "move X", tweens)?,
+                events_with_factor(r, &event_layers, |it| &it.move_y_events, 2. / RPE_HEIGHT, "move Y", tweens)?,
             ),
             scale: {
-                fn parse(r: &mut BpmList, opt: &Option<Vec<RPEEvent>>, factor: f32, bezier_map: &BezierMap) -> Result<AnimFloat> {
+                fn parse(r: &mut BpmList, opt: &Option<Vec<RPEEvent>>, factor: f32, tweens: &Tweens) -> Result<AnimFloat> {
                     let mut res = opt
                         .as_ref()
-                        .map(|it| parse_events(r, it, None, bezier_map))
+                        .map(|it| parse_events(r, it, None, tweens))
                         .transpose()?
                         .unwrap_or_default();
                     res.map_value(|v| v * factor);
@@ -417,9 +525,9 @@ async fn parse_judge_line(r: &mut BpmList, rpe: RPEJudgeLine, max_time: f32, fs:
                                     } else {
                                         1.
                                     },
-                                bezier_map,
+                                tweens,
                             )?,
-                            parse(r, &e.scale_y_events, factor, bezier_map)?,
+                            parse(r, &e.scale_y_events, factor, tweens)?,
                         ))
                     })
                     .transpose()?
@@ -434,7 +542,7 @@ async fn parse_judge_line(r: &mut BpmList, rpe: RPEJudgeLine, max_time: f32, fs:
         }),
         height,
         incline: if let Some(events) = rpe.extended.as_ref().and_then(|e| e.incline_events.as_ref()) {
-            parse_events(r, events, Some(0.), bezier_map).with_context(|| ptl!("incline-events-parse-failed"))?
+            parse_events(r, events, Some(0.), tweens).with_context(|| ptl!("incline-events-parse-failed"))?
         } else {
             AnimFloat::default()
         },
@@ -442,11 +550,11 @@ async fn parse_judge_line(r: &mut BpmList, rpe: RPEJudgeLine, max_time: f32, fs:
         kind: if rpe.texture == "line.png" {
             if let Some(events) = rpe.extended.as_ref().and_then(|e| e.paint_events.as_ref()) {
                 JudgeLineKind::Paint(
-                    parse_events(r, events, Some(-1.), bezier_map).with_context(|| ptl!("paint-events-parse-failed"))?,
+                    parse_events(r, events, Some(-1.), tweens).with_context(|| ptl!("paint-events-parse-failed"))?,
                     RefCell::default(),
                 )
             } else if let Some(events) = rpe.extended.as_ref().and_then(|e| e.text_events.as_ref()) {
-                JudgeLineKind::Text(parse_events(r, events, Some(String::new()), bezier_map).with_context(|| ptl!("text-events-parse-failed"))?)
+                JudgeLineKind::Text(parse_events(r, events, Some(String::new()), tweens).with_context(|| ptl!("text-events-parse-failed"))?)
             } else {
                 JudgeLineKind::Normal
             }
@@ -462,7 +570,7 @@ async fn parse_judge_line(r: &mut BpmList, rpe: RPEJudgeLine, max_time: f32, fs:
             )
         },
         color: if let Some(events) = rpe.extended.as_ref().and_then(|e| e.color_events.as_ref()) {
-            parse_events(r, events, Some(WHITE), bezier_map).with_context(|| ptl!("color-events-parse-failed"))?
+            parse_events(r, events, Some(WHITE), tweens).with_context(|| ptl!("color-events-parse-failed"))?
         } else {
             Anim::default()
         },
@@ -477,6 +585,8 @@ async fn parse_judge_line(r: &mut BpmList, rpe: RPEJudgeLine, max_time: f32, fs:
         z_index: rpe.z_order,
         show_below: rpe.is_cover != 1,
         attach_ui: rpe.attach_ui,
+        group: rpe.group,
+        link_groups,
 
         cache,
     })
@@ -512,8 +622,26 @@ fn get_bezier_map(rpe: &RPEChart) -> BezierMap {
 
 pub async fn parse_rpe(source: &str, fs: &mut dyn FileSystem, extra: ChartExtra) -> Result<Chart> {
     let rpe: RPEChart = serde_json::from_str(source).with_context(|| ptl!("json-parse-failed"))?;
-    let bezier_map = get_bezier_map(&rpe);
-    let mut r = BpmList::new(rpe.bpm_list.into_iter().map(|it| (it.start_time.beats(), it.bpm)).collect());
+    let tweens = Tweens {
+        bezier: get_bezier_map(&rpe),
+        custom: &extra.tweens,
+    };
+    let ranges: Vec<_> = rpe.bpm_list.iter().map(|it| (it.start_time.beats(), it.bpm)).collect();
+    let r = BpmList::new(ranges.clone());
+    // every line keeps its own BPM list so that bpmfactor scales all of its times
+    let mut line_bpm: Vec<_> = rpe
+        .judge_line_list
+        .iter()
+        .map(|line| {
+            let factor = line.bpm_factor;
+            if !factor.is_finite() || factor <= 0. {
+                warn!("invalid bpmfactor {factor}, using 1");
+                BpmList::new(ranges.clone())
+            } else {
+                BpmList::new(ranges.iter().map(|(beats, bpm)| (*beats, bpm / factor)).collect())
+            }
+        })
+        .collect();
     fn vec<T>(v: &Option<Vec<T>>) -> impl Iterator<Item = &T> {
         v.iter().flat_map(|it| it.iter())
     }
@@ -521,7 +649,8 @@ pub async fn parse_rpe(source: &str, fs: &mut dyn FileSystem, extra: ChartExtra)
     let max_time = *rpe
         .judge_line_list
         .iter()
-        .map(|line| {
+        .zip(line_bpm.iter_mut())
+        .map(|(line, r)| {
             line.notes.as_ref().map(|notes| {
                 notes
                     .iter()
@@ -550,14 +679,572 @@ pub async fn parse_rpe(source: &str, fs: &mut dyn FileSystem, extra: ChartExtra)
         .max().unwrap_or_default() + 1.;
     // don't want to add a whole crate for a mere join_all...
     let mut lines = Vec::new();
-    for (id, rpe) in rpe.judge_line_list.into_iter().enumerate() {
+    for (id, (rpe, bpm)) in rpe.judge_line_list.into_iter().zip(line_bpm.iter_mut()).enumerate() {
         let name = rpe.name.clone();
         lines.push(
-            parse_judge_line(&mut r, rpe, max_time, fs, &bezier_map)
+            parse_judge_line(bpm, rpe, max_time, fs, &tweens)
                 .await
                 .with_context(move || ptl!("judge-line-location-name", "jlid" => id, "name" => name))?,
         );
     }
     process_lines(&mut lines);
-    Ok(Chart::new(rpe.meta.offset as f32 / 1000.0, lines, r, ChartSettings::default(), extra))
+    let mut chart = Chart::new(rpe.meta.offset as f32 / 1000.0, lines, r, ChartSettings::default(), extra);
+    chart.line_groups = rpe.judge_line_group;
+    chart.map_hitsounds(Some(&mut line_bpm[..]));
+    Ok(chart)
+}
+
+fn export_time(r: &mut BpmList, time: f32) -> Triple {
+    Triple::from_beats(r.beat(time))
+}
+
+/// Linear pieces a tween RPE has no easing for is baked into.
+const BAKED_SEGMENTS: usize = 16;
+
+/// Returns `None` if RPE has no easing for `tween`.
+fn export_tween(tween: &Rc<dyn TweenFunction>) -> Option<(i32, f32, f32, u8, [f32; 4])> {
+    let easing = |id: TweenId| RPE_TWEEN_MAP.iter().skip(1).position(|it| *it == id).map(|it| it as i32 + 1);
+    let tween = tween.as_any();
+    if let Some(t) = tween.downcast_ref::<StaticTween>() {
+        Some((easing(t.0)?, 0., 1., 0, [0.; 4]))
+    } else if let Some(t) = tween.downcast_ref::<ClampedTween>() {
+        Some((easing(t.0)?, t.1.start, t.1.end, 0, [0.; 4]))
+    } else if let Some(t) = tween.downcast_ref::<BezierTween>() {
+        Some((1, 0., 1., 1, [t.p1.0, t.p1.1, t.p2.0, t.p2.1]))
+    } else {
+        None
+    }
+}
+
+/// Links of this kind of event, used to restore their link groups.
+type Links<'a> = (EventKind, &'a [LinkedEvent]);
+
+fn export_link_group((kind, links): Links, time: f32) -> i32 {
+    links
+        .iter()
+        .find(|it| it.kind == kind && (it.time - time).abs() < 1e-3)
+        .map_or(0, |it| it.group)
+}
+
+fn export_events<T: Tweenable, V: Clone>(r: &mut BpmList, anim: &Anim<T>, links: Links, f: impl Fn(&T) -> V) -> Vec<RPEEvent<V>> {
+    let kfs = &anim.keyframes;
+    let event = |start_time: Triple, end_time: Triple, start: V, end: V| RPEEvent {
+        linkgroup: 0,
+        easing_left: 0.,
+        easing_right: 1.,
+        bezier: 0,
+        bezier_points: [0.; 4],
+        easing_type: 1,
+        start,
+        end,
+        start_time,
+        end_time,
+    };
+    if kfs.len() == 1 {
+        let beats = r.beat(kfs[0].time);
+        let value = f(&kfs[0].value);
+        return vec![RPEEvent {
+            linkgroup: export_link_group(links, kfs[0].time),
+            ..event(Triple::from_beats(beats), Triple::from_beats(beats + 1.), value.clone(), value)
+        }];
+    }
+    kfs.windows(2)
+        .flat_map(|w| {
+            let (a, b) = (&w[0], &w[1]);
+            let start_time = export_time(r, a.time);
+            let end_time = export_time(r, b.time);
+            // hold tweens: 0 keeps the start value, 1 jumps to the end value. These only fill gaps between events, so they aren't linked
+            match a.tween.as_any().downcast_ref::<StaticTween>().map(|it| it.0) {
+                Some(0) => return vec![event(start_time, end_time, f(&a.value), f(&a.value))],
+                Some(1) => return vec![event(start_time, end_time, f(&b.value), f(&b.value))],
+                _ => {}
+            }
+            let Some((easing_type, easing_left, easing_right, bezier, bezier_points)) = export_tween(&a.tween) else {
+                let time = |p: f32| f32::tween(&a.time, &b.time, p);
+                let value = |p: f32| f(&T::tween(&a.value, &b.value, a.tween.y(p)));
+                return (0..BAKED_SEGMENTS)
+                    .map(|i| {
+                        let (p, q) = (i as f32 / BAKED_SEGMENTS as f32, (i + 1) as f32 / BAKED_SEGMENTS as f32);
+                        RPEEvent {
+                            linkgroup: if i == 0 { export_link_group(links, a.time) } else { 0 },
+                            ..event(export_time(r, time(p)), export_time(r, time(q)), value(p), value(q))
+                        }
+                    })
+                    .collect();
+            };
+            vec![RPEEvent {
+                linkgroup: export_link_group(links, a.time),
+                easing_left,
+                easing_right,
+                bezier,
+                bezier_points,
+                easing_type,
+                ..event(start_time, end_time, f(&a.value), f(&b.value))
+            }]
+        })
+        .collect()
+}
+
+/// Splits chained animations into one event list per layer.
+fn export_layers<T: Tweenable, V: Clone>(r: &mut BpmList, anim: &Anim<T>, links: Links, f: impl Fn(&T) -> V) -> Vec<Vec<RPEEvent<V>>> {
+    let mut layers = Vec::new();
+    let mut cur = Some(anim);
+    while let Some(anim) = cur {
+        if !anim.keyframes.is_empty() {
+            layers.push(export_events(r, anim, links, &f));
+        }
+        cur = anim.next.as_deref();
+    }
+    layers
+}
+
+fn export_optional<T: Tweenable, V: Clone>(r: &mut BpmList, anim: &Anim<T>, links: Links, f: impl Fn(&T) -> V) -> Option<Vec<RPEEvent<V>>> {
+    let events: Vec<_> = export_layers(r, anim, links, f).into_iter().flatten().collect();
+    if events.is_empty() {
+        None
+    } else {
+        Some(events)
+    }
+}
+
+fn export_speed_events(r: &mut BpmList, height: &AnimFloat, links: &[LinkedEvent]) -> Vec<RPESpeedEvent> {
+    let times: Vec<_> = height.keyframes.iter().map(|it| it.time).collect();
+    let mut height = height.clone();
+    let mut at = |t: f32| {
+        height.set_time(t);
+        height.now()
+    };
+    times
+        .windows(2)
+        .filter(|w| w[1] > w[0])
+        .map(|w| {
+            let (start, end) = (w[0], w[1]);
+            let eps = ((end - start) / 4.).min(1e-3);
+            RPESpeedEvent {
+                linkgroup: export_link_group((EventKind::Speed, links), start),
+                start_time: export_time(r, start),
+                end_time: export_time(r, end),
+                start: (at(start + eps) - at(start)) / eps / SPEED_RATIO,
+                end: (at(end - 1e-4) - at(end - 1e-4 - eps)) / eps / SPEED_RATIO,
+            }
+        })
+        .collect()
+}
+
+fn export_ctrl_events(anim: &AnimFloat, key: &str) -> Vec<RPECtrlEvent> {
+    let event = |x: f32, easing: u8, value: f32| RPECtrlEvent {
+        easing,
+        x,
+        value: [(key.to_owned(), value)].into_iter().collect(),
+    };
+    let kfs = &anim.keyframes;
+    let mut events = Vec::new();
+    for (i, kf) in kfs.iter().enumerate() {
+        match (export_tween(&kf.tween), kfs.get(i + 1)) {
+            (None, Some(next)) => events.extend((0..BAKED_SEGMENTS).map(|j| {
+                let p = j as f32 / BAKED_SEGMENTS as f32;
+                event(f32::tween(&kf.time, &next.time, p), 1, f32::tween(&kf.value, &next.value, kf.tween.y(p)))
+            })),
+            // the last keyframe's easing doesn't matter
+            (tween, _) => events.push(event(kf.time, tween.map_or(1, |it| it.0 as u8), kf.value)),
+        }
+    }
+    events
+}
+
+fn rgb(c: Color) -> RGBColor {
+    let f = |v: f32| (v * 255.).round().clamp(0., 255.) as u8;
+    RGBColor(f(c.r), f(c.g), f(c.b))
+}
+
+fn export_notes(r: &mut BpmList, notes: &[Note]) -> Vec<RPENote> {
+    let initial = |anim: &AnimFloat, default: f32| anim.keyframes.first().map_or(default, |it| it.value);
+    notes
+        .iter()
+        .map(|note| {
+            let (alpha, visible_time) = match &note.object.alpha.keyframes[..] {
+                [] => (1., 999999.),
+                [kf] => (kf.value, 999999.),
+                [.., kf] => (kf.value, note.time - kf.time),
+            };
+            RPENote {
+                kind: match note.kind {
+                    NoteKind::Click => 1,
+                    NoteKind::Hold { .. } => 2,
+                    NoteKind::Flick => 3,
+                    NoteKind::Drag => 4,
+                },
+                above: note.above as u8,
+                start_time: export_time(r, note.time),
+                end_time: export_time(
+                    r,
+                    match note.kind {
+                        NoteKind::Hold { end_time, .. } => end_time,
+                        _ => note.time,
+                    },
+                ),
+                position_x: initial(&note.object.translation.0, 0.) * (RPE_WIDTH / 2.),
+                y_offset: if note.speed.abs() < EPS {
+                    0.
+                } else {
+                    initial(&note.object.translation.1, 0.) / (2. / RPE_HEIGHT * note.speed)
+                },
+                alpha: (alpha * 255.).round().clamp(0., 255.) as u16,
+                size: initial(&note.object.scale.0, 1.),
+                speed: note.speed,
+                is_fake: note.fake as u8,
+                visible_time,
+                tint: (note.tint != WHITE).then(|| rgb(note.tint)),
+                tint_hit_effects: note.hit_fx_color.map(rgb),
+                judge_area: note.judge_scale,
+                hitsound: note.hitsound.clone(),
+            }
+        })
+        .collect()
+}
+
+fn export_judge_line(r: &mut BpmList, line: &JudgeLine) -> RPEJudgeLine {
+    let texture = match &line.kind {
+        JudgeLineKind::Texture(_, path) => path.clone(),
+        _ => "line.png".to_owned(),
+    };
+    let is_line = texture == "line.png";
+    let scale_factor = if is_line { 1. } else { 2.57 / RPE_WIDTH };
+    let scale_x_factor = scale_factor * if is_line && !matches!(line.kind, JudgeLineKind::Text(_)) && line.attach_ui.is_none() { 0.5 } else { 1. };
+
+    let obj = &line.object;
+    let links = &line.link_groups[..];
+    let mut alpha = export_layers(r, &obj.alpha, (EventKind::Alpha, links), |v| v * 255.).into_iter();
+    let mut rotate = export_layers(r, &obj.rotation, (EventKind::Rotate, links), |v| -v).into_iter();
+    let mut move_x = export_layers(r, &obj.translation.0, (EventKind::MoveX, links), |v| v * (RPE_WIDTH / 2.)).into_iter();
+    let mut move_y = export_layers(r, &obj.translation.1, (EventKind::MoveY, links), |v| v * (RPE_HEIGHT / 2.)).into_iter();
+    let mut speed = Some(export_speed_events(r, &line.height, links)).filter(|it| !it.is_empty());
+    let mut event_layers = Vec::new();
+    loop {
+        let layer = RPEEventLayer {
+            alpha_events: alpha.next(),
+            move_x_events: move_x.next(),
+            move_y_events: move_y.next(),
+            rotate_events: rotate.next(),
+            speed_events: speed.take(),
+        };
+        if layer.alpha_events.is_none()
+            && layer.move_x_events.is_none()
+            && layer.move_y_events.is_none()
+            && layer.rotate_events.is_none()
+            && layer.speed_events.is_none()
+        {
+            break;
+        }
+        event_layers.push(Some(layer));
+    }
+
+    let extended = RPEExtendedEvents {
+        color_events: export_optional(r, &line.color, (EventKind::Color, links), |c| rgb(*c)),
+        text_events: if let JudgeLineKind::Text(anim) = &line.kind {
+            export_optional(r, anim, (EventKind::Text, links), String::clone)
+        } else {
+            None
+        },
+        scale_x_events: export_optional(r, &obj.scale.0, (EventKind::ScaleX, links), |v| v / scale_x_factor),
+        scale_y_events: export_optional(r, &obj.scale.1, (EventKind::ScaleY, links), |v| v / scale_factor),
+        incline_events: export_optional(r, &line.incline, (EventKind::Incline, links), |v| *v),
+        paint_events: if let JudgeLineKind::Paint(anim, _) = &line.kind {
+            export_optional(r, anim, (EventKind::Paint, links), |v| *v)
+        } else {
+            None
+        },
+    };
+    let has_extended = extended.color_events.is_some()
+        || extended.text_events.is_some()
+        || extended.scale_x_events.is_some()
+        || extended.scale_y_events.is_some()
+        || extended.incline_events.is_some()
+        || extended.paint_events.is_some();
+
+    let ctrl = line.ctrl_obj.borrow();
+    RPEJudgeLine {
+        group: line.group,
+        // times are already scaled by the line's bpmfactor
+        bpm_factor: 1.,
+        name: "Untitled".to_owned(),
+        texture,
+        parent: Some(line.parent.map_or(-1, |it| it as isize)),
+        event_layers,
+        extended: if has_extended { Some(extended) } else { None },
+        notes: Some(export_notes(r, &line.notes)),
+        is_cover: if line.show_below { 0 } else { 1 },
+        z_order: line.z_index,
+        attach_ui: line.attach_ui,
+
+        pos_control: export_ctrl_events(&ctrl.pos, "pos"),
+        size_control: export_ctrl_events(&ctrl.size, "size"),
+        alpha_control: export_ctrl_events(&ctrl.alpha, "alpha"),
+        y_control: export_ctrl_events(&ctrl.y, "y"),
+    }
+}
+
+/// Converts a chart back into RPE's `chart.json`, with metadata taken from `info`.
+///
+/// Tweens RPE has no easing for (steps, springs, custom easings...) are baked into linear events.
+pub fn export_rpe(chart: &Chart, info: &ChartInfo) -> Result<String> {
+    let mut r = chart.bpm_list.borrow_mut();
+    let rpe = RPEChart {
+        meta: RPEMetadata {
+            offset: (chart.offset * 1000.).round() as i32,
+
+            rpe_version: 140,
+            name: info.name.clone(),
+            song: info.music.clone(),
+            background: info.illustration.clone(),
+            charter: info.charter.clone(),
+            composer: info.composer.clone(),
+            level: info.level.clone(),
+        },
+        bpm_list: r
+            .ranges()
+            .into_iter()
+            .map(|(beats, bpm)| RPEBpmItem {
+                bpm,
+                start_time: Triple::from_beats(beats),
+            })
+            .collect(),
+        judge_line_group: if chart.line_groups.is_empty() {
+            vec!["Default".to_owned()]
+        } else {
+            chart.line_groups.clone()
+        },
+        judge_line_list: chart.lines.iter().map(|line| export_judge_line(&mut r, line)).collect(),
+    };
+    Ok(serde_json::to_string(&rpe)?)
+}
+
+fn check_easings<T>(events: Option<&Vec<RPEEvent<T>>>, line: usize, custom: &TweenRegistry, out: &mut Vec<Diagnostic>) {
+    for e in events.into_iter().flatten() {
+        if e.bezier == 0 && !(1..RPE_TWEEN_MAP.len() as i32).contains(&e.easing_type) && custom.get_id(e.easing_type).is_none() {
+            out.push(
+                Diagnostic::new(Severity::Warning, ptl!("validate-unknown-easing", "easing" => e.easing_type))
+                    .at(line, None, Some(e.start_time.beats())),
+            );
+        }
+    }
+}
+
+/// Reports problems that are lost once the chart is parsed: unknown easings and missing textures or hit sounds.
+///
+/// `custom` holds the easings of `extra.json`, which events can use through their ids.
+pub(super) async fn check_rpe(source: &str, fs: &mut dyn FileSystem, custom: &TweenRegistry, out: &mut Vec<Diagnostic>) {
+    let Ok(rpe) = serde_json::from_str::<RPEChart>(source) else {
+        // reported by the parser
+        return;
+    };
+    for (id, line) in rpe.judge_line_list.iter().enumerate() {
+        if !line.bpm_factor.is_finite() || line.bpm_factor <= 0. {
+            out.push(Diagnostic::new(Severity::Warning, ptl!("validate-invalid-bpm-factor", "factor" => line.bpm_factor)).at(id, None, None));
+        }
+        if !rpe.judge_line_group.is_empty() && line.group >= rpe.judge_line_group.len() {
+            out.push(Diagnostic::new(Severity::Warning, ptl!("validate-unknown-line-group", "group" => line.group)).at(id, None, None));
+        }
+        for layer in line.event_layers.iter().flatten() {
+            check_easings(layer.alpha_events.as_ref(), id, custom, out);
+            check_easings(layer.move_x_events.as_ref(), id, custom, out);
+            check_easings(layer.move_y_events.as_ref(), id, custom, out);
+            check_easings(layer.rotate_events.as_ref(), id, custom, out);
+        }
+        if let Some(e) = &line.extended {
+            check_easings(e.color_events.as_ref(), id, custom, out);
+            check_easings(e.text_events.as_ref(), id, custom, out);
+            check_easings(e.scale_x_events.as_ref(), id, custom, out);
+            check_easings(e.scale_y_events.as_ref(), id, custom, out);
+            check_easings(e.incline_events.as_ref(), id, custom, out);
+            check_easings(e.paint_events.as_ref(), id, custom, out);
+        }
+        for e in [&line.pos_control, &line.size_control, &line.alpha_control, &line.y_control].into_iter().flatten() {
+            if !(1..RPE_TWEEN_MAP.len()).contains(&(e.easing as usize)) {
+                out.push(Diagnostic::new(Severity::Warning, ptl!("validate-unknown-easing", "easing" => e.easing)).at(id, None, None));
+            }
+        }
+        if line.texture != "line.png" && fs.load_file(&line.texture).await.is_err() {
+            out.push(Diagnostic::new(Severity::Error, ptl!("validate-texture-missing", "path" => line.texture.clone())).at(id, None, None));
+        }
+        for note in line.notes.iter().flatten() {
+            let beat = Some(note.start_time.beats());
+            if !note.judge_area.is_finite() || note.judge_area <= 0. {
+                out.push(Diagnostic::new(Severity::Warning, ptl!("validate-invalid-judge-area", "area" => note.judge_area)).at(id, None, beat));
+            }
+            if let Some(path) = &note.hitsound {
+                if fs.load_file(path).await.is_err() {
+                    out.push(Diagnostic::new(Severity::Error, ptl!("validate-hitsound-missing", "path" => path.clone())).at(id, None, beat));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{HitSoundMapping, PiecewiseTween},
+        fs::MemoryFileSystem,
+    };
+
+    const GROUPS: &str = include_str!("../../tests/fixtures/groups.json");
+
+    fn load(source: &str) -> Chart {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(parse_rpe(source, &mut MemoryFileSystem::default(), ChartExtra::default()))
+            .unwrap()
+    }
+
+    #[track_caller]
+    fn assert_times<T: Tweenable>(anim: &Anim<T>, expected: &[f32]) {
+        let times: Vec<_> = anim.keyframes.iter().map(|it| it.time).collect();
+        assert_eq!(times.len(), expected.len(), "{times:?}");
+        for (time, expected) in times.iter().zip(expected) {
+            assert!((time - expected).abs() < 1e-4, "{times:?} != {expected:?}");
+        }
+    }
+
+    fn links(line: &JudgeLine) -> Vec<(u8, i32, i32)> {
+        let mut res: Vec<_> = line
+            .link_groups
+            .iter()
+            .map(|it| (it.kind as u8, (it.time * 1000.).round() as i32, it.group))
+            .collect();
+        res.sort();
+        res
+    }
+
+    #[test]
+    fn bpm_factor() {
+        let chart = load(GROUPS);
+        // 120 BPM, then 240 from beat 4. bpmfactor 2 halves both
+        let slow = &chart.lines[0];
+        assert_times(&slow.object.translation.0, &[0., 2.]);
+        assert_times(&slow.object.rotation, &[1., 3.]);
+        assert_times(&slow.object.alpha, &[2., 5.]);
+        assert_eq!(slow.notes[0].time, 4.);
+        // and bpmfactor 0.5 doubles them
+        let fast = &chart.lines[1];
+        assert_times(&fast.object.translation.0, &[0., 0.5]);
+        assert_times(&fast.object.translation.1, &[1., 1.5]);
+        assert_times(&fast.color, &[0., 0.5, 1.]);
+        assert_eq!(fast.notes[0].time, 1.);
+    }
+
+    #[test]
+    fn groups() {
+        let chart = load(GROUPS);
+        assert_eq!(chart.line_groups, ["Default", "Upper"]);
+        assert_eq!(chart.lines[0].group, 0);
+        assert_eq!(chart.lines[1].group, 1);
+        let (alpha, move_x, move_y, rotate, speed, color) = (
+            EventKind::Alpha as u8,
+            EventKind::MoveX as u8,
+            EventKind::MoveY as u8,
+            EventKind::Rotate as u8,
+            EventKind::Speed as u8,
+            EventKind::Color as u8,
+        );
+        assert_eq!(links(&chart.lines[0]), [(move_x, 0, 3), (rotate, 1000, 3), (speed, 0, 5)]);
+        assert_eq!(links(&chart.lines[1]), [(move_y, 1000, 3), (color, 500, 7)]);
+        assert!(!links(&chart.lines[0]).iter().any(|it| it.0 == alpha));
+    }
+
+    #[test]
+    fn hitsounds() {
+        let mapping = |line, beat: f32, path: &str| HitSoundMapping {
+            line,
+            beat,
+            // what the BPM list of extra.json gives, which is off for both lines
+            time: beat / 2.,
+            path: path.to_owned(),
+        };
+        let extra = ChartExtra {
+            hitsounds: vec![mapping(None, 4., "all.wav"), mapping(Some(1), 4., "fast.wav"), mapping(Some(1), 3., "none.wav")],
+            ..Default::default()
+        };
+        let mut fs = MemoryFileSystem::default();
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let mut chart = rt.block_on(parse_rpe(GROUPS, &mut fs, extra)).unwrap();
+        // both notes are on beat 4, despite their bpmfactor
+        assert_eq!(chart.lines[0].notes[0].hitsound.as_deref(), Some("all.wav"));
+        assert_eq!(chart.lines[1].notes[0].hitsound.as_deref(), Some("fast.wav"));
+        // the files don't exist, so the notes go back to the default sound
+        rt.block_on(chart.load_hitsounds(&mut fs));
+        assert!(chart.hitsounds.is_empty());
+        assert!(chart.lines.iter().flat_map(|it| it.notes.iter()).all(|it| it.hitsound.is_none()));
+    }
+
+    #[test]
+    fn custom_easing_ids() {
+        let mut rpe: serde_json::Value = serde_json::from_str(GROUPS).unwrap();
+        let layer = &mut rpe["judgeLineList"][0]["eventLayers"][0];
+        // starts at beat 0, registered below
+        layer["moveXEvents"][0]["easingType"] = 100.into();
+        // starts at beat 1, unknown
+        layer["rotateEvents"][0]["easingType"] = 200.into();
+        let mut extra = ChartExtra::default();
+        extra.tweens.register("custom", Some(100), StaticTween::get_rc(2));
+        let res = tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(crate::parse::validate(
+            rpe.to_string().as_bytes(),
+            crate::info::ChartFormat::Rpe,
+            &mut MemoryFileSystem::default(),
+            extra,
+            None,
+        ));
+        let at = |beat: f32| res.iter().filter(|it| it.line == Some(0) && it.beat == Some(beat)).count();
+        assert_eq!(at(0.), 0, "{res:?}");
+        assert_eq!(at(1.), 1, "{res:?}");
+    }
+
+    #[test]
+    fn export_groups() {
+        let chart = load(GROUPS);
+        let exported = export_rpe(&chart, &ChartInfo::default()).unwrap();
+        let rpe: RPEChart = serde_json::from_str(&exported).unwrap();
+        assert_eq!(rpe.judge_line_group, ["Default", "Upper"]);
+        assert_eq!(rpe.judge_line_list[1].group, 1);
+        // exported times are already scaled, so the keyframes and groups come back unchanged
+        let reloaded = load(&exported);
+        assert_eq!(reloaded.line_groups, chart.line_groups);
+        for (a, b) in chart.lines.iter().zip(reloaded.lines.iter()) {
+            assert_eq!(a.group, b.group);
+            assert_eq!(links(a), links(b));
+            let times = |anim: &AnimFloat| anim.keyframes.iter().map(|it| it.time).collect::<Vec<_>>();
+            assert_times(&b.object.translation.0, &times(&a.object.translation.0));
+            assert_times(&b.object.rotation, &times(&a.object.rotation));
+        }
+    }
+
+    #[test]
+    fn export_baked() {
+        let mut chart = load(GROUPS);
+        // reaches the end value halfway, which no RPE easing does
+        let tween: Rc<dyn TweenFunction> = Rc::new(PiecewiseTween::new(vec![(0., 0.), (0.5, 1.), (1., 1.)]));
+        chart.lines[0].object.translation.0.keyframes[0].tween = Rc::clone(&tween);
+        let mut size = Keyframe::new(0., 1., 0);
+        size.tween = tween;
+        chart.lines[0].ctrl_obj.get_mut().size = AnimFloat::new(vec![size, Keyframe::new(4., 2., 0)]);
+        let reloaded = load(&export_rpe(&chart, &ChartInfo::default()).unwrap());
+        let (mut a, mut b) = (chart.lines[0].object.translation.0.clone(), reloaded.lines[0].object.translation.0.clone());
+        for i in 0..=40 {
+            let t = i as f32 * 0.05;
+            a.set_time(t);
+            b.set_time(t);
+            assert!((a.now() - b.now()).abs() < 1e-3, "{t}: {} != {}", a.now(), b.now());
+        }
+        // the link group stays on the first piece only
+        assert_eq!(links(&chart.lines[0]), links(&reloaded.lines[0]));
+        let (mut a, mut b) = (chart.lines[0].ctrl_obj.borrow().size.clone(), reloaded.lines[0].ctrl_obj.borrow().size.clone());
+        for i in 0..=40 {
+            let x = i as f32 * 0.1;
+            a.set_time(x);
+            b.set_time(x);
+            assert!((a.now() - b.now()).abs() < 1e-3, "{x}: {} != {}", a.now(), b.now());
+        }
+    }
 }
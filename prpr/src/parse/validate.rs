@@ -0,0 +1,149 @@
+crate::tl_file!("parser" ptl);
+
+use super::{parse_pec, parse_phigros, parse_phigros_fv1, parse_rpe, rpe::check_rpe};
+use crate::{
+    bin::read_pbc,
+    core::{Chart, ChartExtra, NoteKind},
+    fs::FileSystem,
+    info::ChartFormat,
+};
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The chart loads, but probably not the way the charter intended.
+    Warning,
+    /// The chart fails to load or can't be played correctly.
+    Error,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: Option<usize>,
+    pub note: Option<usize>,
+    pub beat: Option<f32>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            line: None,
+            note: None,
+            beat: None,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn at(mut self, line: usize, note: Option<usize>, beat: Option<f32>) -> Self {
+        self.line = Some(line);
+        self.note = note;
+        self.beat = beat;
+        self
+    }
+}
+
+/// Checks a chart file without rendering it.
+///
+/// `extra` is the chart's parsed `extra.json` (or the default), whose easings RPE events may use.
+/// `track_length` is the length of the music in seconds; notes beyond it are reported when given.
+/// Diagnostics are sorted by line, then by beat.
+pub async fn validate(source: &[u8], format: ChartFormat, fs: &mut dyn FileSystem, extra: ChartExtra, track_length: Option<f32>) -> Vec<Diagnostic> {
+    let mut res = Vec::new();
+    let text = || String::from_utf8_lossy(source);
+    if matches!(format, ChartFormat::Rpe) {
+        check_rpe(&text(), fs, &extra.tweens, &mut res).await;
+        // the parser would only fail on the first of these, with less context
+        if res.iter().any(|it| it.severity == Severity::Error) {
+            sort(&mut res);
+            return res;
+        }
+    }
+    let chart = match format {
+        ChartFormat::Rpe => parse_rpe(&text(), fs, extra).await,
+        ChartFormat::Pgr => parse_phigros(&text(), extra),
+        ChartFormat::Pgr1 => parse_phigros_fv1(&text(), extra),
+        ChartFormat::Pec => parse_pec(&text(), extra),
+        ChartFormat::Pbc => read_pbc(source),
+    };
+    match chart {
+        Ok(chart) => res.extend(validate_chart(&chart, track_length)),
+        Err(err) => res.push(Diagnostic::new(Severity::Error, ptl!("validate-parse-failed", "error" => format!("{err:?}")))),
+    }
+    sort(&mut res);
+    res
+}
+
+/// Checks an already parsed chart.
+pub fn validate_chart(chart: &Chart, track_length: Option<f32>) -> Vec<Diagnostic> {
+    let mut res = Vec::new();
+    let mut bpm_list = chart.bpm_list.borrow_mut();
+    for (line_id, line) in chart.lines.iter().enumerate() {
+        for (note_id, note) in line.notes.iter().enumerate() {
+            let mut report = |severity, message: String| {
+                let beat = note.time.is_finite().then(|| bpm_list.beat(note.time));
+                res.push(Diagnostic::new(severity, message).at(line_id, Some(note_id), beat));
+            };
+            if !note.time.is_finite() {
+                report(Severity::Error, ptl!("validate-invalid-time").to_string());
+                continue;
+            }
+            if note.time < 0. {
+                report(Severity::Warning, ptl!("validate-note-before-start").to_string());
+            }
+            let end_time = match note.kind {
+                NoteKind::Hold { end_time, .. } => {
+                    if !end_time.is_finite() {
+                        report(Severity::Error, ptl!("validate-invalid-time").to_string());
+                        continue;
+                    }
+                    if end_time < note.time {
+                        report(Severity::Error, ptl!("validate-hold-end-before-start").to_string());
+                    }
+                    end_time.max(note.time)
+                }
+                _ => note.time,
+            };
+            if let Some(length) = track_length {
+                if end_time > length {
+                    report(
+                        Severity::Error,
+                        ptl!("validate-note-after-end", "time" => format!("{end_time:.3}"), "length" => format!("{length:.3}")),
+                    );
+                }
+            }
+        }
+
+        let Some(parent) = line.parent else {
+            continue;
+        };
+        if parent >= chart.lines.len() {
+            res.push(Diagnostic::new(Severity::Error, ptl!("validate-parent-out-of-range", "parent" => parent)).at(line_id, None, None));
+            continue;
+        }
+        // walk up the parent chain; a cycle is only reported on the lines that are part of it
+        let mut cur = parent;
+        for _ in 0..chart.lines.len() {
+            if cur == line_id {
+                res.push(Diagnostic::new(Severity::Error, ptl!("validate-parent-cycle")).at(line_id, None, None));
+                break;
+            }
+            match chart.lines[cur].parent {
+                Some(next) if next < chart.lines.len() => cur = next,
+                _ => break,
+            }
+        }
+    }
+    res
+}
+
+fn sort(res: &mut [Diagnostic]) {
+    res.sort_by(|a, b| {
+        a.line
+            .cmp(&b.line)
+            .then_with(|| a.beat.unwrap_or(f32::NEG_INFINITY).total_cmp(&b.beat.unwrap_or(f32::NEG_INFINITY)))
+    });
+}
@@ -0,0 +1,247 @@
+use crate::{
+    core::BpmList,
+    judge::{Judge, Judgement},
+    mixdown::sample_at,
+};
+use sasa::Frame;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// A named A–B section, in beats.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PracticeLoop {
+    pub name: String,
+    pub start_beat: f32,
+    pub end_beat: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PracticePlan {
+    pub loops: Vec<PracticeLoop>,
+    /// Speed of each step, e.g. `[0.7, 0.8, 0.9, 1.0]`. The last step is repeated once reached.
+    ///
+    /// Unlike the speed setting, the music of the loop keeps its pitch, see [`compensate_pitch`].
+    pub speeds: Vec<f32>,
+    /// Iterations played at each speed before moving on to the next step.
+    pub iterations_per_step: u32,
+    /// Only move on when an iteration reaches this accuracy.
+    pub min_accuracy: f64,
+    /// Seconds of lead-in (in real time) before the loop starts, counted down on screen.
+    pub countdown: f32,
+}
+
+impl Default for PracticePlan {
+    fn default() -> Self {
+        Self {
+            loops: Vec::new(),
+            speeds: vec![0.7, 0.8, 0.9, 1.0],
+            iterations_per_step: 2,
+            min_accuracy: 0.,
+            countdown: 3.,
+        }
+    }
+}
+
+/// Grain length of the time stretch, in frames.
+const GRAIN: usize = 1024;
+const HOP: usize = GRAIN / 2;
+/// How far (in frames) a grain may move to line up with the previous one.
+const SEEK: usize = 128;
+
+fn frame_at(frames: &[Frame], i: usize) -> Frame {
+    frames.get(i).cloned().unwrap_or(Frame(0., 0.))
+}
+
+/// Changes the tempo of `input` by `speed` without changing its pitch, using WSOLA: Hann-windowed
+/// grains are overlapped at half a grain apart, each one taken near where `speed` says it should come
+/// from, shifted to best continue the previous grain.
+fn stretch(input: &[Frame], speed: f32) -> Vec<Frame> {
+    let len = (input.len() as f32 / speed) as usize;
+    let window: Vec<f32> = (0..GRAIN).map(|i| (std::f32::consts::PI * i as f32 / GRAIN as f32).sin().powi(2)).collect();
+    let mono = |i: usize| {
+        let frame = frame_at(input, i);
+        frame.0 + frame.1
+    };
+    let mut out = vec![Frame(0., 0.); len + GRAIN];
+    let mut prev = 0;
+    for start in (0..len).step_by(HOP) {
+        let nominal = (start as f32 * speed) as usize;
+        let pos = if start == 0 {
+            0
+        } else {
+            // what would follow the previous grain seamlessly
+            let natural = prev + HOP;
+            let score = |cand: usize| (0..HOP).step_by(4).map(|i| mono(cand + i) * mono(natural + i)).sum::<f32>();
+            (nominal.saturating_sub(SEEK)..=nominal + SEEK)
+                .step_by(2)
+                .max_by(|a, b| score(*a).total_cmp(&score(*b)))
+                .unwrap()
+        };
+        for (i, &w) in window.iter().enumerate() {
+            // nothing overlaps the first half of the first grain, so don't fade it in
+            let w = if start == 0 && i < HOP { 1. } else { w };
+            let frame = frame_at(input, pos + i);
+            out[start + i].0 += frame.0 * w;
+            out[start + i].1 += frame.1 * w;
+        }
+        prev = pos;
+    }
+    out.truncate(len);
+    out
+}
+
+/// Raises the pitch of `frames[range]` so that it sounds at its original pitch when played at
+/// `speed`. Frames outside `range` are copied as is, so positions in the clip stay the same.
+pub fn compensate_pitch(frames: &[Frame], speed: f32, range: Range<usize>) -> Vec<Frame> {
+    let range = range.start.min(frames.len())..range.end.min(frames.len());
+    let stretched = stretch(&frames[range.clone()], speed);
+    let mut out = frames.to_vec();
+    // squeeze the stretched part back into its original length
+    for (i, frame) in out[range].iter_mut().enumerate() {
+        *frame = sample_at(&stretched, i as f64 / speed as f64)
+            .or_else(|| stretched.last().cloned())
+            .unwrap_or(Frame(0., 0.));
+    }
+    out
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IterationResult {
+    pub loop_index: usize,
+    pub iteration: u32,
+    pub speed: f32,
+    pub accuracy: f64,
+    pub counts: [u32; 4],
+}
+
+/// Progress through a [`PracticePlan`].
+pub struct Practice {
+    pub plan: PracticePlan,
+    current: usize,
+    step: usize,
+    // iterations played at the current step
+    step_iterations: u32,
+    iteration: u32,
+    results: Vec<IterationResult>,
+}
+
+impl Practice {
+    pub fn new(plan: PracticePlan) -> Self {
+        Self {
+            plan,
+            current: 0,
+            step: 0,
+            step_iterations: 0,
+            iteration: 0,
+            results: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn current(&self) -> Option<&PracticeLoop> {
+        self.plan.loops.get(self.current)
+    }
+
+    /// Switches to another loop, starting again from the first speed step.
+    pub fn select(&mut self, index: usize) {
+        self.current = index.min(self.plan.loops.len().saturating_sub(1));
+        self.step = 0;
+        self.step_iterations = 0;
+        self.iteration = 0;
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.plan.speeds.get(self.step).or(self.plan.speeds.last()).copied().unwrap_or(1.)
+    }
+
+    /// Chart-time range of the current loop.
+    pub fn range(&self, bpm_list: &mut BpmList) -> Option<Range<f32>> {
+        let lp = self.current()?;
+        Some(bpm_list.time_beats(lp.start_beat)..bpm_list.time_beats(lp.end_beat))
+    }
+
+    #[inline]
+    pub fn results(&self) -> &[IterationResult] {
+        &self.results
+    }
+
+    /// Records the iteration that just ended, judging only notes inside `range` (chart time), and
+    /// advances the speed step if due.
+    pub fn finish_iteration(&mut self, judge: &Judge, range: Range<f32>) -> &IterationResult {
+        let mut counts = [0; 4];
+        for entry in judge.timeline() {
+            if range.contains(&entry.note_time) {
+                counts[entry.judgement as usize] += 1;
+            }
+        }
+        let total: u32 = counts.iter().sum();
+        let accuracy = if total == 0 {
+            1.
+        } else {
+            (counts[Judgement::Perfect as usize] as f64 + counts[Judgement::Good as usize] as f64 * 0.65) / total as f64
+        };
+        self.results.push(IterationResult {
+            loop_index: self.current,
+            iteration: self.iteration,
+            speed: self.speed(),
+            accuracy,
+            counts,
+        });
+        self.iteration += 1;
+        if accuracy >= self.plan.min_accuracy {
+            self.step_iterations += 1;
+            if self.step_iterations >= self.plan.iterations_per_step && self.step + 1 < self.plan.speeds.len() {
+                self.step += 1;
+                self.step_iterations = 0;
+            }
+        }
+        self.results.last().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Period of the strongest frequency in `frames`, in frames, found by autocorrelation.
+    fn period(frames: &[Frame]) -> usize {
+        let score = |lag: usize| frames.iter().zip(&frames[lag..]).map(|(a, b)| a.0 * b.0).sum::<f32>();
+        (20..400).max_by(|a, b| score(*a).total_cmp(&score(*b))).unwrap()
+    }
+
+    #[test]
+    fn stretch_keeps_pitch() {
+        // 441 Hz at 44.1 kHz, so a period of 100 frames
+        let tone: Vec<_> = (0..44100)
+            .map(|i| {
+                let v = (i as f32 * std::f32::consts::TAU / 100.).sin() * 0.5;
+                Frame(v, v)
+            })
+            .collect();
+        let stretched = stretch(&tone, 0.7);
+        assert_eq!(stretched.len(), (44100. / 0.7) as usize);
+        assert_eq!(period(&stretched[GRAIN..GRAIN + 4000]), 100);
+        let peak = stretched[GRAIN..stretched.len() - GRAIN].iter().map(|it| it.0.abs()).fold(0., f32::max);
+        assert!((peak - 0.5).abs() < 0.05, "{peak}");
+    }
+
+    #[test]
+    fn compensate() {
+        let tone: Vec<_> = (0..44100)
+            .map(|i| {
+                let v = (i as f32 * std::f32::consts::TAU / 100.).sin();
+                Frame(v, -v)
+            })
+            .collect();
+        let out = compensate_pitch(&tone, 0.5, 10000..30000);
+        assert_eq!(out.len(), tone.len());
+        let same = |a: &[Frame], b: &[Frame]| a.iter().zip(b).all(|(a, b)| a.0 == b.0 && a.1 == b.1);
+        assert!(same(&out[..10000], &tone[..10000]));
+        assert!(same(&out[30000..], &tone[30000..]));
+        // an octave up, to be played at half speed
+        assert_eq!(period(&out[12000..16000]), 50);
+    }
+}
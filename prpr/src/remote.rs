@@ -0,0 +1,401 @@
+//! Local HTTP control surface for [`GameScene`](crate::scene::GameScene), enabled with
+//! [`Config::remote_port`](crate::config::Config::remote_port).
+//!
+//! The server only listens on `127.0.0.1`. Since web pages can reach that too, every request must
+//! carry [`Config::remote_token`](crate::config::Config::remote_token) in an `X-Prpr-Token` header
+//! and a `Host` of `127.0.0.1:<port>` or `localhost:<port>`; requests with an `Origin` (i.e. from a
+//! browser) are refused, and POST bodies must be `application/json`. Endpoints:
+//!
+//! - `POST /load` with `{"path": "..."}`: load a chart folder or archive, replacing the current one
+//! - `POST /play`, `POST /pause`
+//! - `POST /seek` with `{"time": 12.5}` (seconds of chart time) or `{"beat": 32}`
+//! - `POST /speed` with `{"speed": 0.8}`
+//! - `POST /mods` with `{"mods": 1}` (bits of [`Mods`])
+//! - `GET /state`: the latest [`RemoteState`] as JSON
+//! - `GET /events`: [`RemoteState`] as server-sent events, pushed whenever it changes
+
+use crate::{config::Mods, judge::Judgement};
+use anyhow::{bail, Context, Result};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex,
+    },
+    time::Duration,
+};
+use tracing::warn;
+
+const MAX_BODY_SIZE: usize = 64 * 1024;
+const EVENT_KEEP_ALIVE: Duration = Duration::from_secs(15);
+const TOKEN_HEADER: &str = "x-prpr-token";
+
+static SERVER: OnceCell<RemoteServer> = OnceCell::new();
+
+#[derive(Clone, Copy, Debug)]
+pub enum SeekTarget {
+    Time(f32),
+    Beat(f32),
+}
+
+#[derive(Clone, Debug)]
+pub enum RemoteCommand {
+    Load(PathBuf),
+    Play,
+    Pause,
+    Seek(SeekTarget),
+    Speed(f32),
+    Mods(Mods),
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteState {
+    pub chart: String,
+    /// Chart time, in seconds.
+    pub time: f32,
+    pub beat: f32,
+    pub paused: bool,
+    pub speed: f32,
+    pub mods: i32,
+    pub score: u32,
+    pub combo: u32,
+    pub accuracy: f64,
+    pub counts: [u32; 4],
+    pub last_judgement: Option<Judgement>,
+}
+
+#[derive(Default)]
+struct Shared {
+    // bumped on every change so that event streams know when to push
+    version: u64,
+    state: String,
+}
+
+pub struct RemoteServer {
+    commands: Mutex<Receiver<RemoteCommand>>,
+    shared: Arc<(Mutex<Shared>, Condvar)>,
+}
+
+impl RemoteServer {
+    /// Returns the server, starting it on first use. Later calls ignore `port` and `token`.
+    pub fn global(port: u16, token: &str) -> Result<&'static Self> {
+        SERVER.get_or_try_init(|| Self::start(port, token))
+    }
+
+    fn start(port: u16, token: &str) -> Result<Self> {
+        if token.is_empty() {
+            bail!("remote control needs a token, see `remoteToken`");
+        }
+        let access = Arc::new(Access {
+            port,
+            token: token.to_owned(),
+        });
+        let listener =
+            TcpListener::bind((Ipv4Addr::LOCALHOST, port)).with_context(|| format!("failed to bind remote control server to port {port}"))?;
+        let (tx, rx) = mpsc::channel();
+        let shared: Arc<(Mutex<Shared>, Condvar)> = Arc::default();
+        {
+            let shared = Arc::clone(&shared);
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            warn!("remote control connection failed: {err:?}");
+                            continue;
+                        }
+                    };
+                    let tx = tx.clone();
+                    let shared = Arc::clone(&shared);
+                    let access = Arc::clone(&access);
+                    std::thread::spawn(move || {
+                        if let Err(err) = handle(stream, &tx, &shared, &access) {
+                            warn!("remote control request failed: {err:?}");
+                        }
+                    });
+                }
+            });
+        }
+        Ok(Self {
+            commands: Mutex::new(rx),
+            shared,
+        })
+    }
+
+    /// Commands received since the last call, in order.
+    pub fn commands(&self) -> Vec<RemoteCommand> {
+        self.commands.lock().unwrap().try_iter().collect()
+    }
+
+    pub fn publish(&self, state: &RemoteState) {
+        let state = serde_json::to_string(state).unwrap();
+        let (lock, cvar) = &*self.shared;
+        let mut shared = lock.lock().unwrap();
+        if shared.state == state {
+            return;
+        }
+        shared.version += 1;
+        shared.state = state;
+        cvar.notify_all();
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    // names are lowercase
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|it| it.0 == name).map(|it| it.1.as_str())
+    }
+}
+
+/// What a request needs to be let through.
+struct Access {
+    port: u16,
+    token: String,
+}
+
+impl Access {
+    /// Returns the status to reject `req` with, if any.
+    fn check(&self, req: &Request) -> Option<&'static str> {
+        // browsers always send an Origin on cross-origin requests, other clients have no reason to
+        if req.header("origin").is_some() {
+            return Some("403 Forbidden");
+        }
+        // guards against DNS rebinding
+        let host = req.header("host").unwrap_or_default();
+        if host != format!("127.0.0.1:{}", self.port) && host != format!("localhost:{}", self.port) {
+            return Some("403 Forbidden");
+        }
+        if req.header(TOKEN_HEADER) != Some(self.token.as_str()) {
+            return Some("401 Unauthorized");
+        }
+        if req.method == "POST" && req.header("content-type").and_then(|it| it.split(';').next()).map(str::trim) != Some("application/json") {
+            return Some("415 Unsupported Media Type");
+        }
+        None
+    }
+}
+
+fn read_request(stream: &mut BufReader<TcpStream>) -> Result<Request> {
+    let mut line = String::new();
+    stream.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        bail!("malformed request line");
+    };
+    let (method, path) = (method.to_owned(), path.split('?').next().unwrap().to_owned());
+    let mut length = 0;
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        stream.read_line(&mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let (name, value) = (name.trim().to_ascii_lowercase(), value.trim());
+            if name == "content-length" {
+                length = value.parse().context("invalid content length")?;
+            }
+            headers.push((name, value.to_owned()));
+        }
+    }
+    if length > MAX_BODY_SIZE {
+        bail!("request body too large");
+    }
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body)?;
+    Ok(Request {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+fn parse_command(req: &Request) -> Result<RemoteCommand> {
+    #[derive(Deserialize)]
+    struct Load {
+        path: PathBuf,
+    }
+    #[derive(Deserialize)]
+    struct Seek {
+        time: Option<f32>,
+        beat: Option<f32>,
+    }
+    #[derive(Deserialize)]
+    struct Speed {
+        speed: f32,
+    }
+    #[derive(Deserialize)]
+    struct SetMods {
+        mods: Mods,
+    }
+    Ok(match req.path.as_str() {
+        "/load" => RemoteCommand::Load(serde_json::from_slice::<Load>(&req.body)?.path),
+        "/play" => RemoteCommand::Play,
+        "/pause" => RemoteCommand::Pause,
+        "/seek" => {
+            let seek: Seek = serde_json::from_slice(&req.body)?;
+            RemoteCommand::Seek(match (seek.time, seek.beat) {
+                (Some(time), None) => SeekTarget::Time(time),
+                (None, Some(beat)) => SeekTarget::Beat(beat),
+                _ => bail!("expected exactly one of `time` and `beat`"),
+            })
+        }
+        "/speed" => {
+            let speed = serde_json::from_slice::<Speed>(&req.body)?.speed;
+            if !(0.1..=4.).contains(&speed) {
+                bail!("speed out of range");
+            }
+            RemoteCommand::Speed(speed)
+        }
+        "/mods" => RemoteCommand::Mods(serde_json::from_slice::<SetMods>(&req.body)?.mods),
+        _ => bail!("unknown endpoint"),
+    })
+}
+
+fn handle(stream: TcpStream, tx: &Sender<RemoteCommand>, shared: &(Mutex<Shared>, Condvar), access: &Access) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+    let req = match read_request(&mut reader) {
+        Ok(req) => req,
+        Err(err) => return respond(&mut stream, "400 Bad Request", &error_body(&err)),
+    };
+    if let Some(status) = access.check(&req) {
+        return respond(&mut stream, status, r#"{"error":"forbidden"}"#);
+    }
+    match (req.method.as_str(), req.path.as_str()) {
+        ("GET", "/state") => {
+            let state = shared.0.lock().unwrap().state.clone();
+            respond(&mut stream, "200 OK", if state.is_empty() { "null" } else { state.as_str() })
+        }
+        ("GET", "/events") => stream_events(stream, shared),
+        ("POST", _) => match parse_command(&req) {
+            Ok(cmd) => {
+                if tx.send(cmd).is_err() {
+                    return respond(&mut stream, "503 Service Unavailable", r#"{"error":"player is gone"}"#);
+                }
+                respond(&mut stream, "202 Accepted", r#"{"ok":true}"#)
+            }
+            Err(err) => respond(&mut stream, "400 Bad Request", &error_body(&err)),
+        },
+        _ => respond(&mut stream, "404 Not Found", r#"{"error":"not found"}"#),
+    }
+}
+
+fn error_body(err: &anyhow::Error) -> String {
+    serde_json::json!({ "error": format!("{err:#}") }).to_string()
+}
+
+fn stream_events(mut stream: TcpStream, shared: &(Mutex<Shared>, Condvar)) -> Result<()> {
+    stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n")?;
+    let (lock, cvar) = shared;
+    let mut seen = 0;
+    loop {
+        let message = {
+            let guard = cvar
+                .wait_timeout_while(lock.lock().unwrap(), EVENT_KEEP_ALIVE, |it| it.version == seen)
+                .unwrap()
+                .0;
+            if guard.version == seen {
+                None
+            } else {
+                seen = guard.version;
+                Some(guard.state.clone())
+            }
+        };
+        // a comment line doubles as a keep-alive and notices closed connections
+        match message {
+            Some(state) => write!(stream, "data: {state}\n\n")?,
+            None => stream.write_all(b":\n\n")?,
+        }
+        stream.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "secret";
+
+    /// Sends `request` (with `{port}` substituted) to a fresh handler and returns the status line.
+    fn send(request: &str) -> (String, Vec<RemoteCommand>) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut client = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        client.write_all(request.replace("{port}", &port.to_string()).as_bytes()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let access = Access {
+            port,
+            token: TOKEN.to_owned(),
+        };
+        handle(stream, &tx, &Default::default(), &access).unwrap();
+        let mut status = String::new();
+        BufReader::new(client).read_line(&mut status).unwrap();
+        (status.trim_end().to_owned(), rx.try_iter().collect())
+    }
+
+    fn post(headers: &str) -> (String, Vec<RemoteCommand>) {
+        send(&format!("POST /play HTTP/1.1\r\n{headers}Content-Length: 2\r\n\r\n{{}}"))
+    }
+
+    #[test]
+    fn accepts_local_clients() {
+        let (status, commands) = post("Host: 127.0.0.1:{port}\r\nX-Prpr-Token: secret\r\nContent-Type: application/json\r\n");
+        assert_eq!(status, "HTTP/1.1 202 Accepted");
+        assert!(matches!(commands[..], [RemoteCommand::Play]));
+        let (status, _) = send("GET /state HTTP/1.1\r\nHost: localhost:{port}\r\nx-prpr-token: secret\r\n\r\n");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+    }
+
+    #[test]
+    fn rejects_browsers() {
+        let (status, commands) = post(
+            "Host: 127.0.0.1:{port}\r\nOrigin: https://example.com\r\nX-Prpr-Token: secret\r\nContent-Type: application/json\r\n",
+        );
+        assert_eq!(status, "HTTP/1.1 403 Forbidden");
+        assert!(commands.is_empty());
+        // DNS rebinding
+        let (status, _) = send("GET /state HTTP/1.1\r\nHost: evil.example:{port}\r\nX-Prpr-Token: secret\r\n\r\n");
+        assert_eq!(status, "HTTP/1.1 403 Forbidden");
+        // simple requests can't set a JSON content type
+        let (status, commands) = post("Host: 127.0.0.1:{port}\r\nX-Prpr-Token: secret\r\nContent-Type: text/plain\r\n");
+        assert_eq!(status, "HTTP/1.1 415 Unsupported Media Type");
+        assert!(commands.is_empty());
+    }
+
+    #[test]
+    fn requires_token() {
+        let (status, commands) = post("Host: 127.0.0.1:{port}\r\nContent-Type: application/json\r\n");
+        assert_eq!(status, "HTTP/1.1 401 Unauthorized");
+        assert!(commands.is_empty());
+        let (status, _) = post("Host: 127.0.0.1:{port}\r\nX-Prpr-Token: guess\r\nContent-Type: application/json\r\n");
+        assert_eq!(status, "HTTP/1.1 401 Unauthorized");
+        assert!(RemoteServer::start(0, "").is_err());
+    }
+}
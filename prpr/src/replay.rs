@@ -0,0 +1,262 @@
+use crate::{
+    bin::{BinaryData, BinaryReader, BinaryWriter},
+    judge::JudgeProfile,
+};
+use anyhow::{bail, Result};
+use macroquad::prelude::{vec2, Touch, TouchPhase};
+use std::io::{Read, Write};
+
+const MAGIC: &[u8; 4] = b"PRRP";
+const VERSION: u8 = 1;
+
+/// Input consumed by one call of [`Judge::update`](crate::judge::Judge::update).
+///
+/// Positions are already normalized and touch times are already in chart time, so the
+/// same input can be judged again without any knowledge of the original window or device.
+#[derive(Clone, Default)]
+pub struct JudgeInput {
+    pub touches: Vec<Touch>,
+    pub events: Vec<Touch>,
+    pub key_delta: i32,
+    pub keys_down: u32,
+    pub keys: Vec<KeyEvent>,
+}
+
+/// A press or release of a mapped key.
+#[derive(Clone, Copy)]
+pub struct KeyEvent {
+    pub id: u32,
+    /// Range of line-local x covered by the key, `None` for the whole line.
+    pub zone: Option<(f32, f32)>,
+    pub pressed: bool,
+}
+
+#[derive(Clone)]
+pub struct ReplayFrame {
+    pub time: f32,
+    pub input: JudgeInput,
+}
+
+#[derive(Clone, Default)]
+pub struct Replay {
+    pub speed: f32,
+    pub aspect_ratio: f32,
+    pub judge_profile: JudgeProfile,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn read(r: impl Read) -> Result<Self> {
+        let mut r = BinaryReader::new(r);
+        let mut magic = [0; 4];
+        r.0.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("not a replay file");
+        }
+        let version = r.read::<u8>()?;
+        if version != VERSION {
+            bail!("unsupported replay version: {version}");
+        }
+        Ok(Self {
+            speed: r.read()?,
+            aspect_ratio: r.read()?,
+            judge_profile: r.read()?,
+            frames: r.array()?,
+        })
+    }
+
+    pub fn write(&self, w: impl Write) -> Result<()> {
+        let mut w = BinaryWriter::new(w);
+        w.0.write_all(MAGIC)?;
+        w.write_val(VERSION)?;
+        w.write_val(self.speed)?;
+        w.write_val(self.aspect_ratio)?;
+        w.write(&self.judge_profile)?;
+        w.array(&self.frames)
+    }
+}
+
+impl BinaryData for Touch {
+    fn read_binary<R: Read>(r: &mut BinaryReader<R>) -> Result<Self> {
+        Ok(Self {
+            id: r.uleb()?,
+            phase: match r.read::<u8>()? {
+                0 => TouchPhase::Started,
+                1 => TouchPhase::Stationary,
+                2 => TouchPhase::Moved,
+                3 => TouchPhase::Ended,
+                4 => TouchPhase::Cancelled,
+                _ => bail!("invalid touch phase"),
+            },
+            position: vec2(r.read()?, r.read()?),
+            time: r.read()?,
+        })
+    }
+
+    fn write_binary<W: Write>(&self, w: &mut BinaryWriter<W>) -> Result<()> {
+        w.uleb(self.id)?;
+        w.write_val(match self.phase {
+            TouchPhase::Started => 0_u8,
+            TouchPhase::Stationary => 1,
+            TouchPhase::Moved => 2,
+            TouchPhase::Ended => 3,
+            TouchPhase::Cancelled => 4,
+        })?;
+        w.write_val(self.position.x)?;
+        w.write_val(self.position.y)?;
+        w.write_val(self.time)?;
+        Ok(())
+    }
+}
+
+impl BinaryData for ReplayFrame {
+    fn read_binary<R: Read>(r: &mut BinaryReader<R>) -> Result<Self> {
+        Ok(Self {
+            // stored verbatim: rounding frame times would change judgements
+            time: r.read()?,
+            input: JudgeInput {
+                touches: r.array()?,
+                events: r.array()?,
+                key_delta: r.read()?,
+                keys_down: r.uleb()? as _,
+                keys: r.array()?,
+            },
+        })
+    }
+
+    fn write_binary<W: Write>(&self, w: &mut BinaryWriter<W>) -> Result<()> {
+        w.write_val(self.time)?;
+        w.array(&self.input.touches)?;
+        w.array(&self.input.events)?;
+        w.write_val(self.input.key_delta)?;
+        w.uleb(self.input.keys_down as _)?;
+        w.array(&self.input.keys)?;
+        Ok(())
+    }
+}
+
+impl BinaryData for KeyEvent {
+    fn read_binary<R: Read>(r: &mut BinaryReader<R>) -> Result<Self> {
+        Ok(Self {
+            id: r.uleb()? as _,
+            zone: if r.read()? { Some((r.read()?, r.read()?)) } else { None },
+            pressed: r.read()?,
+        })
+    }
+
+    fn write_binary<W: Write>(&self, w: &mut BinaryWriter<W>) -> Result<()> {
+        w.uleb(self.id as _)?;
+        w.write_val(self.zone.is_some())?;
+        if let Some((start, end)) = self.zone {
+            w.write_val(start)?;
+            w.write_val(end)?;
+        }
+        w.write_val(self.pressed)?;
+        Ok(())
+    }
+}
+
+impl BinaryData for JudgeProfile {
+    fn read_binary<R: Read>(r: &mut BinaryReader<R>) -> Result<Self> {
+        Ok(Self {
+            perfect: r.read()?,
+            good: r.read()?,
+            bad: r.read()?,
+            flick_speed_threshold: r.read()?,
+            up_tolerance: r.read()?,
+        })
+    }
+
+    fn write_binary<W: Write>(&self, w: &mut BinaryWriter<W>) -> Result<()> {
+        w.write_val(self.perfect)?;
+        w.write_val(self.good)?;
+        w.write_val(self.bad)?;
+        w.write_val(self.flick_speed_threshold)?;
+        w.write_val(self.up_tolerance)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(id: u64, phase: TouchPhase, x: f32, y: f32, time: f64) -> Touch {
+        Touch {
+            id,
+            phase,
+            position: vec2(x, y),
+            time,
+        }
+    }
+
+    fn sample() -> Replay {
+        Replay {
+            speed: 1.25,
+            aspect_ratio: 16. / 9.,
+            judge_profile: JudgeProfile::STRICT,
+            frames: (0..100)
+                .map(|i| ReplayFrame {
+                    // odd times on purpose: any rounding would show up
+                    time: i as f32 / 61. + 0.1,
+                    input: JudgeInput {
+                        touches: vec![touch(i % 3, TouchPhase::Moved, 0.1 * i as f32 - 1., -0.3, f64::NEG_INFINITY)],
+                        events: vec![
+                            touch(i % 3, TouchPhase::Started, -0.5, 0.25, i as f64 / 61.),
+                            touch(7, TouchPhase::Cancelled, 0.75, 1. / 3., i as f64 / 59.),
+                        ],
+                        key_delta: i as i32 % 3 - 1,
+                        keys_down: i as u32 % 2,
+                        keys: vec![KeyEvent {
+                            id: i as u32,
+                            zone: (i % 2 == 0).then_some((-0.5, 0.5)),
+                            pressed: i % 4 < 2,
+                        }],
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    fn assert_touches(a: &[Touch], b: &[Touch]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.phase, b.phase);
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.time.to_bits(), b.time.to_bits());
+        }
+    }
+
+    /// Playback judges exactly what was recorded, so the file must keep every frame bit for bit.
+    #[test]
+    fn round_trip() {
+        let replay = sample();
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes).unwrap();
+        let read = Replay::read(&bytes[..]).unwrap();
+        assert_eq!(read.speed, replay.speed);
+        assert_eq!(read.aspect_ratio, replay.aspect_ratio);
+        assert_eq!(read.judge_profile, replay.judge_profile);
+        assert_eq!(read.frames.len(), replay.frames.len());
+        for (a, b) in replay.frames.iter().zip(&read.frames) {
+            assert_eq!(a.time.to_bits(), b.time.to_bits());
+            assert_touches(&a.input.touches, &b.input.touches);
+            assert_touches(&a.input.events, &b.input.events);
+            assert_eq!(a.input.key_delta, b.input.key_delta);
+            assert_eq!(a.input.keys_down, b.input.keys_down);
+            assert_eq!(a.input.keys.len(), b.input.keys.len());
+            for (a, b) in a.input.keys.iter().zip(&b.input.keys) {
+                assert_eq!((a.id, a.zone, a.pressed), (b.id, b.zone, b.pressed));
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(Replay::read(&b"PRPR"[..]).is_err());
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION + 1);
+        assert!(Replay::read(&bytes[..]).is_err());
+    }
+}
@@ -6,33 +6,38 @@ use chinese_number::{ChineseCase, ChineseCountMethod, ChineseVariant, NumberToCh
 use super::{
     draw_background,
     ending::RecordUpdateState,
-    loading::{BasicPlayer, UpdateFn, UploadFn},
+    loading::{BasicPlayer, LoadingScene, UpdateFn, UploadFn},
     request_input, return_input, show_message, take_input, EndingScene, NextScene, Scene,
 };
 use crate::{
-    bin::{BinaryReader, BinaryWriter},
+    bin::read_pbc,
     config::{Config, Mods},
     core::{copy_fbo, BadNote, Chart, ChartExtra, Effect, Point, Resource, UIElement, Vector},
-    ext::{parse_time, screen_aspect, semi_white, RectExt, SafeTexture},
-    fs::FileSystem,
+    ext::{parse_time, poll_future, screen_aspect, semi_white, LocalTask, RectExt, SafeTexture, ScaleType},
+    fs::{fs_from_file, load_info, ExternalFileSystem, FileSystem},
     info::{ChartFormat, ChartInfo},
-    judge::Judge,
+    judge::{Judge, JudgeProfile, JudgeStatus},
+    overlay::{broadcast, sink_from_target, FrameState, OverlayEvent, OverlaySink, SongInfo},
     parse::{parse_extra, parse_pec, parse_phigros, parse_phigros_fv1, parse_rpe},
+    practice::{compensate_pitch, Practice, PracticePlan},
+    remote::{RemoteCommand, RemoteServer, RemoteState, SeekTarget},
+    replay::Replay,
     task::Task,
     time::TimeManager,
     ui::{RectButton, Ui},
+    watch::ChartWatcher,
 };
 use anyhow::{bail, Context, Result};
 use concat_string::concat_string;
 use lyon::path::Path;
 use macroquad::{prelude::*, window::InternalGlContext};
-use sasa::{Music, MusicParams};
+use sasa::{AudioClip, Music, MusicParams};
 use serde::{Deserialize, Serialize};
 use std::{
     any::Any,
     cell::RefCell,
     fs::File,
-    io::{Cursor, ErrorKind},
+    io::ErrorKind,
     ops::{DerefMut, Range},
     path::PathBuf,
     process::{Command, Stdio},
@@ -89,21 +94,38 @@ fn fmt_time(t: f32) -> String {
     format!("{}{hrs:02}:{mins:02}:{secs:05.2}", if f { "-" } else { "" })
 }
 
+/// Marks notes before `time` as judged so that they are skipped instead of being missed all at once.
+fn skip_notes_before(chart: &mut Chart, time: f32) {
+    for note in chart.lines.iter_mut().flat_map(|it| it.notes.iter_mut()) {
+        if note.time < time {
+            note.judge = JudgeStatus::Judged;
+        }
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen::prelude::wasm_bindgen]
 extern "C" {
     fn on_game_start();
 }
 
-#[derive(PartialEq, Eq)]
 pub enum GameMode {
     Normal,
     TweakOffset,
     Exercise,
     NoRetry,
     View,
+    Replay(Arc<Replay>),
 }
 
+impl PartialEq for GameMode {
+    fn eq(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+impl Eq for GameMode {}
+
 #[derive(Clone)]
 enum State {
     Starting,
@@ -131,6 +153,22 @@ pub struct GameScene {
     exercise_range: Range<f32>,
     exercise_press: Option<(i8, u64)>,
     exercise_btns: (RectButton, RectButton),
+    practice: Option<Practice>,
+    // music of the current practice step, with the pitch of the loop kept: (speed, frames, clip)
+    practice_clip: Option<(f32, Range<usize>, AudioClip)>,
+
+    watcher: Option<(ChartWatcher, Box<dyn FileSystem>)>,
+    reload_task: LocalTask<Result<(Chart, Vec<u8>, ChartFormat)>>,
+
+    remote: Option<&'static RemoteServer>,
+    remote_load_task: LocalTask<Result<LoadingScene>>,
+    // seeking, changing speed or mods mid-run makes the result meaningless as a record
+    tampered: bool,
+
+    overlay_sinks: Vec<Box<dyn OverlaySink>>,
+    // judgements already sent to overlay sinks
+    overlay_cursor: usize,
+    overlay_started: bool,
 
     pub music: Music,
 
@@ -159,6 +197,9 @@ macro_rules! reset {
         $tm.reset();
         $self.last_update_time = $tm.now();
         $self.state = State::Starting;
+        $self.tampered = false;
+        $self.overlay_cursor = 0;
+        $self.overlay_started = false;
     }};
 }
 
@@ -281,7 +322,7 @@ impl GameScene {
 
     pub async fn load_chart(fs: &mut dyn FileSystem, info: &ChartInfo) -> Result<(Chart, Vec<u8>, ChartFormat)> {
         let extra = fs.load_file("extra.json").await.ok().map(String::from_utf8).transpose()?;
-        let extra = if let Some(extra) = extra {
+        let mut extra = if let Some(extra) = extra {
             parse_extra(&extra, fs).await.context("Failed to parse extra")?
         } else {
             ChartExtra::default()
@@ -312,11 +353,22 @@ impl GameScene {
             ChartFormat::Pgr1 => parse_phigros_fv1(&String::from_utf8_lossy(&bytes), extra),
             ChartFormat::Pec => parse_pec(&String::from_utf8_lossy(&bytes), extra),
             ChartFormat::Pbc => {
-                let mut r = BinaryReader::new(Cursor::new(&bytes));
-                r.read()
+                let mut chart = read_pbc(&bytes)?;
+                // prefer extra.json next to the chart over the one embedded in it
+                if extra.source.is_none() {
+                    if let Some(source) = chart.extra.source.take() {
+                        extra = parse_extra(&source, fs).await.context("Failed to parse extra")?;
+                    }
+                }
+                chart.extra = extra;
+                Ok(chart)
             }
         }?;
         chart.load_textures(fs).await?;
+        if !matches!(format, ChartFormat::Rpe) {
+            chart.map_hitsounds(None);
+        }
+        chart.load_hitsounds(fs).await;
         chart.settings.hold_partial_cover = info.hold_partial_cover;
         Ok((chart, bytes, format))
     }
@@ -339,6 +391,12 @@ impl GameScene {
             GameMode::Exercise => {
                 config.mods.remove(Mods::AUTOPLAY);
             }
+            GameMode::Replay(ref replay) => {
+                config.mods.remove(Mods::AUTOPLAY);
+                config.speed = replay.speed;
+                config.aspect_ratio = Some(replay.aspect_ratio);
+                config.judge_profile = replay.judge_profile;
+            }
             _ => {}
         }
         let (mut chart, chart_bytes, chart_format) = Self::load_chart(fs.deref_mut(), &info).await?;
@@ -350,6 +408,7 @@ impl GameScene {
                 .push(Effect::new(0.0..f32::INFINITY, include_str!("fxaa.glsl"), Vec::new(), false).unwrap());
         }
 
+        let watch_fs = (config.watch_chart && fs.as_any().is::<ExternalFileSystem>()).then(|| fs.clone_box());
         let info_offset = info.offset;
         let mut res = Resource::new(
             config,
@@ -362,12 +421,35 @@ impl GameScene {
         )
         .await
         .context("Failed to load resources")?;
+        res.load_hitsounds(&chart).context("Failed to load hit sounds")?;
         let exercise_range = (chart.offset + info_offset + res.config.offset)..res.track_length;
 
-        let judge = Judge::new(&chart);
+        let mut judge = Judge::new(&chart, &res.config);
+        if let GameMode::Replay(replay) = &mode {
+            judge.play_replay(Arc::clone(replay));
+        } else if !res.config.autoplay() {
+            judge.start_recording();
+        }
+
+        let remote = res.config.remote_port.and_then(|port| {
+            RemoteServer::global(port, &res.config.remote_token)
+                .map_err(|err| warn!("failed to start remote control server: {err:?}"))
+                .ok()
+        });
+
+        let overlay_sinks = res
+            .config
+            .overlay_targets
+            .iter()
+            .filter_map(|target| {
+                sink_from_target(target, res.config.overlay_allow_remote)
+                    .map_err(|err| warn!("failed to create overlay sink {target}: {err:?}"))
+                    .ok()
+            })
+            .collect();
 
         let music = Self::new_music(&mut res)?;
-        Ok(Self {
+        let mut scene = Self {
             should_exit: false,
             next_scene: None,
 
@@ -386,6 +468,19 @@ impl GameScene {
             exercise_range,
             exercise_press: None,
             exercise_btns: (RectButton::new(), RectButton::new()),
+            practice: None,
+            practice_clip: None,
+
+            watcher: None,
+            reload_task: None,
+
+            remote,
+            remote_load_task: None,
+            tampered: false,
+
+            overlay_sinks,
+            overlay_cursor: 0,
+            overlay_started: false,
 
             music,
 
@@ -400,7 +495,307 @@ impl GameScene {
             update_fn,
 
             touch_points: Vec::new(),
-        })
+        };
+        if let Some(fs) = watch_fs {
+            scene.watch(fs)?;
+        }
+        Ok(scene)
+    }
+
+    /// Reloads the chart whenever files in its folder change, keeping the current time and pause state.
+    ///
+    /// Done automatically for folder charts when [`Config::watch_chart`] is set.
+    ///
+    /// `fs` must be the [`ExternalFileSystem`] the chart was loaded from.
+    pub fn watch(&mut self, mut fs: Box<dyn FileSystem>) -> Result<()> {
+        let Some(ext) = fs.as_any().downcast_mut::<ExternalFileSystem>() else {
+            bail!("only folder-based charts can be watched");
+        };
+        let watcher = ChartWatcher::new(ext.0.path());
+        self.watcher = Some((watcher, fs));
+        Ok(())
+    }
+
+    fn poll_reload(&mut self) {
+        if let Some(task) = &mut self.reload_task {
+            if let Some(result) = poll_future(task.as_mut()) {
+                self.reload_task = None;
+                match result {
+                    Ok((chart, bytes, format)) => {
+                        self.swap_chart(chart, bytes, format);
+                        show_message(tl!("chart-reloaded")).ok();
+                    }
+                    Err(err) => {
+                        warn!("failed to reload chart: {err:?}");
+                        show_message(format!("{}: {err:#}", tl!("chart-reload-failed"))).error();
+                    }
+                }
+            }
+        } else if let Some((watcher, fs)) = &mut self.watcher {
+            if watcher.poll() {
+                let mut fs = fs.clone_box();
+                let info = self.res.info.clone();
+                self.reload_task = Some(Box::pin(async move { Self::load_chart(fs.deref_mut(), &info).await }));
+            }
+        }
+    }
+
+    fn swap_chart(&mut self, mut chart: Chart, bytes: Vec<u8>, format: ChartFormat) {
+        self.effects = std::mem::take(&mut chart.extra.global_effects);
+        if self.res.config.fxaa {
+            chart
+                .extra
+                .effects
+                .push(Effect::new(0.0..f32::INFINITY, include_str!("fxaa.glsl"), Vec::new(), false).unwrap());
+        }
+        self.res.set_no_effect(chart.extra.effects.is_empty() && self.effects.is_empty());
+        skip_notes_before(&mut chart, self.res.time);
+        if let Err(err) = self.res.load_hitsounds(&chart) {
+            warn!("failed to load hit sounds: {err:?}");
+        }
+        self.judge = Judge::new(&chart, &self.res.config);
+        if let GameMode::Replay(replay) = &self.mode {
+            self.judge.play_replay(Arc::clone(replay));
+        } else if !self.res.config.autoplay() {
+            self.judge.start_recording();
+        }
+        // the score so far is lost and the chart may differ from the one records are kept for
+        self.tampered = true;
+        self.chart = chart;
+        self.chart_bytes = bytes;
+        self.chart_format = format;
+        self.bad_notes.clear();
+    }
+
+    fn handle_remote(&mut self, tm: &mut TimeManager) -> Result<()> {
+        let Some(remote) = self.remote else {
+            return Ok(());
+        };
+        if let Some(task) = &mut self.remote_load_task {
+            if let Some(result) = poll_future(task.as_mut()) {
+                self.remote_load_task = None;
+                match result {
+                    Ok(scene) => self.next_scene = Some(NextScene::Replace(Box::new(scene))),
+                    Err(err) => {
+                        warn!("failed to load chart: {err:?}");
+                        show_message(format!("{}: {err:#}", tl!("remote-load-failed"))).error();
+                    }
+                }
+            }
+        }
+        for cmd in remote.commands() {
+            match cmd {
+                RemoteCommand::Load(path) => {
+                    let mode = match self.mode {
+                        GameMode::Exercise => GameMode::Exercise,
+                        GameMode::NoRetry => GameMode::NoRetry,
+                        GameMode::View => GameMode::View,
+                        _ => GameMode::Normal,
+                    };
+                    let config = self.res.config.clone();
+                    let player = self.player.as_ref().map(|it| BasicPlayer {
+                        avatar: it.avatar.clone(),
+                        id: it.id,
+                        rks: it.rks,
+                    });
+                    let upload_fn = self.upload_fn.clone();
+                    self.remote_load_task = Some(Box::pin(async move {
+                        let mut fs: Box<dyn FileSystem> = fs_from_file(&path)?;
+                        let info = load_info(fs.deref_mut()).await?;
+                        LoadingScene::new(mode, info, &config, fs, player, upload_fn, None).await
+                    }));
+                }
+                RemoteCommand::Play => {
+                    if tm.paused() {
+                        match self.state {
+                            State::Playing => {
+                                self.music.play()?;
+                                tm.resume();
+                            }
+                            // the music starts by itself once the time reaches zero
+                            State::BeforeMusic => tm.resume(),
+                            _ => {}
+                        }
+                    }
+                }
+                RemoteCommand::Pause => {
+                    if !tm.paused() && matches!(self.state, State::Playing | State::BeforeMusic) {
+                        if !self.music.paused() {
+                            self.music.pause()?;
+                        }
+                        tm.pause();
+                    }
+                }
+                RemoteCommand::Seek(target) => {
+                    if !matches!(self.state, State::Playing) {
+                        continue;
+                    }
+                    let time = match target {
+                        SeekTarget::Time(time) => time,
+                        SeekTarget::Beat(beat) => self.chart.bpm_list.borrow_mut().time_beats(beat),
+                    };
+                    let offset = self.offset();
+                    let dst = (time + offset).clamp(0., self.res.track_length);
+                    self.bad_notes.clear();
+                    self.judge.reset();
+                    self.chart.reset();
+                    skip_notes_before(&mut self.chart, dst - offset);
+                    self.music.seek_to(dst)?;
+                    tm.seek_to(dst as f64);
+                    self.res.time = dst - offset;
+                    self.tampered = true;
+                }
+                RemoteCommand::Speed(speed) => {
+                    self.res.config.speed = speed;
+                    let pos = self.music.position();
+                    self.music = Self::new_music(&mut self.res)?;
+                    if matches!(self.state, State::Playing) {
+                        self.music.seek_to(pos)?;
+                        if !tm.paused() {
+                            self.music.play()?;
+                        }
+                    }
+                    let now = tm.now();
+                    tm.speed = speed as _;
+                    tm.seek_to(now);
+                    self.tampered = true;
+                }
+                RemoteCommand::Mods(mods) => {
+                    self.res.config.mods = mods;
+                    self.tampered = true;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn remote_state(&self, tm: &TimeManager) -> RemoteState {
+        RemoteState {
+            chart: self.res.info.name.clone(),
+            time: self.res.time,
+            beat: self.chart.bpm_list.borrow_mut().beat(self.res.time),
+            paused: tm.paused(),
+            speed: self.res.config.speed,
+            mods: self.res.config.mods.bits(),
+            score: self.judge.score(),
+            combo: self.judge.combo(),
+            accuracy: self.judge.real_time_accuracy(),
+            counts: self.judge.counts(),
+            last_judgement: self.judge.timeline().last().map(|it| it.judgement),
+        }
+    }
+
+    /// Adds a sink that receives live play events, in addition to those from [`Config::overlay_targets`].
+    pub fn add_overlay_sink(&mut self, sink: Box<dyn OverlaySink>) {
+        self.overlay_sinks.push(sink);
+    }
+
+    fn feed_overlay(&mut self, tm: &TimeManager) {
+        if self.overlay_sinks.is_empty() {
+            return;
+        }
+        if !self.overlay_started {
+            self.overlay_started = true;
+            let note_count = self.chart.lines.iter().flat_map(|it| it.notes.iter()).filter(|it| !it.fake).count() as u32;
+            broadcast(
+                &mut self.overlay_sinks,
+                &OverlayEvent::Start(SongInfo::new(&self.res.info, self.res.track_length, note_count)),
+            );
+        }
+        let timeline = self.judge.timeline();
+        // the timeline is cleared on retries and seeks
+        if timeline.len() < self.overlay_cursor {
+            self.overlay_cursor = 0;
+        }
+        for entry in &timeline[self.overlay_cursor..] {
+            broadcast(&mut self.overlay_sinks, &OverlayEvent::Judgement(entry));
+        }
+        self.overlay_cursor = timeline.len();
+        let progress = if self.res.track_length > 0. {
+            ((self.res.time + self.offset()) / self.res.track_length).clamp(0., 1.)
+        } else {
+            0.
+        };
+        let frame = FrameState {
+            time: self.res.time,
+            progress,
+            paused: tm.paused(),
+            score: self.judge.score(),
+            combo: self.judge.combo(),
+            accuracy: self.judge.real_time_accuracy(),
+            counts: self.judge.counts(),
+        };
+        broadcast(&mut self.overlay_sinks, &OverlayEvent::Frame(frame));
+    }
+
+    /// Loops sections of the chart in [`GameMode::Exercise`], following the speed steps of `plan`.
+    pub fn set_practice(&mut self, plan: PracticePlan) -> Result<()> {
+        if self.mode != GameMode::Exercise {
+            bail!("practice is only available in exercise mode");
+        }
+        self.practice = Some(Practice::new(plan));
+        self.apply_practice()
+    }
+
+    /// Progress of the current practice, [`Practice::results`] holding the accuracy of every
+    /// iteration played so far. Each one is also sent to the overlay sinks when it's finished.
+    #[inline]
+    pub fn practice(&self) -> Option<&Practice> {
+        self.practice.as_ref()
+    }
+
+    pub fn select_practice_loop(&mut self, index: usize) -> Result<()> {
+        if let Some(practice) = &mut self.practice {
+            practice.select(index);
+        }
+        self.apply_practice()
+    }
+
+    fn apply_practice(&mut self) -> Result<()> {
+        let Some(practice) = &self.practice else {
+            return Ok(());
+        };
+        let speed = practice.speed();
+        if let Some(range) = practice.range(&mut self.chart.bpm_list.borrow_mut()) {
+            let offset = self.offset();
+            self.exercise_range = (range.start + offset)..(range.end + offset);
+        }
+        self.res.config.speed = speed;
+        let music = &self.res.music;
+        let rate = music.sample_rate() as f32;
+        // with a second of margin, so that the pitch doesn't jump right at the end of the loop
+        let frames = (self.exercise_start() * rate).max(0.) as usize..((self.exercise_range.end + 1.) * rate) as usize;
+        let key = ((speed - 1.).abs() > 1e-3).then_some((speed, frames));
+        let current = self.practice_clip.as_ref().map(|(speed, frames, _)| (*speed, frames.clone()));
+        if key != current {
+            self.practice_clip = key.map(|(speed, frames)| {
+                let clip = AudioClip::from_raw(compensate_pitch(music.frames(), speed, frames.clone()), music.sample_rate());
+                (speed, frames, clip)
+            });
+            self.music = self.create_music()?;
+        }
+        Ok(())
+    }
+
+    /// Like [`Self::new_music`], but plays the pitch-kept practice music if there is one.
+    fn create_music(&mut self) -> Result<Music> {
+        let Some((_, _, clip)) = &self.practice_clip else {
+            return Self::new_music(&mut self.res);
+        };
+        self.res.audio.create_music(
+            clip.clone(),
+            MusicParams {
+                amplifier: self.res.config.volume_music as _,
+                playback_rate: self.res.config.speed as _,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Where exercise mode (re)starts, including the practice lead-in.
+    fn exercise_start(&self) -> f32 {
+        let lead_in = self.practice.as_ref().map_or(0., |it| it.plan.countdown * self.res.config.speed);
+        self.exercise_range.start - lead_in
     }
 
     fn new_music(res: &mut Resource) -> Result<Music> {
@@ -508,9 +903,14 @@ impl GameScene {
             let ct = pause_center.coords;
             let c = Color { a: color.a * c.a, ..color };
             ui.with(scale.prepend_translation(&-ct).append_translation(&ct), |ui| {
-                ui.fill_rect(r, c);
-                r.x += pause_w * 2.;
-                ui.fill_rect(r, c);
+                if let Some(icon) = &res.icon_pause {
+                    let r = Rect::new(r.x, r.y, pause_w * 3., pause_h);
+                    ui.fill_rect(r, (**icon, r, ScaleType::Fit, c));
+                } else {
+                    ui.fill_rect(r, c);
+                    r.x += pause_w * 2.;
+                    ui.fill_rect(r, c);
+                }
             });
         });
         if self.judge.combo() >= 3 {
@@ -850,6 +1250,29 @@ impl GameScene {
                 ui.text(t.to_string()).anchor(0.5, 0.5).size(1.).color(c).draw();
             }
         }
+        if let Some(practice) = &self.practice {
+            let left = (self.exercise_range.start - tm.now() as f32) / self.res.config.speed;
+            if !tm.paused() && left > 0. {
+                ui.text((left.ceil() as i32).to_string()).anchor(0.5, 0.5).size(1.).color(c).draw();
+                if let Some(lp) = practice.current() {
+                    ui.text(format!("{} · {:.0}%", lp.name, self.res.config.speed * 100.))
+                        .pos(0., 0.12)
+                        .anchor(0.5, 0.)
+                        .size(0.6)
+                        .color(c)
+                        .draw();
+                }
+                if let Some(last) = practice.results().last() {
+                    let [perfect, good, bad, miss] = last.counts;
+                    ui.text(format!("#{} · {:.2}% · {perfect}/{good}/{bad}/{miss}", last.iteration + 1, last.accuracy * 100.))
+                        .pos(0., 0.22)
+                        .anchor(0.5, 0.)
+                        .size(0.45)
+                        .color(c)
+                        .draw();
+                }
+            }
+        }
         if self.res.config.touch_debug {
             for touch in Judge::get_touches() {
                 ui.fill_circle(touch.position.x, touch.position.y, 0.04, Color { a: 0.4, ..RED });
@@ -861,6 +1284,10 @@ impl GameScene {
         Ok(())
     }
 
+    pub fn is_starting(&self) -> bool {
+        matches!(self.state, State::Starting)
+    }
+
     fn interactive(res: &Resource, state: &State) -> bool {
         res.config.interactive && matches!(state, State::Playing)
     }
@@ -931,7 +1358,7 @@ impl Scene for GameScene {
     fn enter(&mut self, tm: &mut TimeManager, target: Option<RenderTarget>) -> Result<()> {
         #[cfg(target_arch = "wasm32")]
         on_game_start();
-        self.music = Self::new_music(&mut self.res)?;
+        self.music = self.create_music()?;
         self.res.camera.render_target = target;
         tm.speed = self.res.config.speed as _;
         tm.adjust_time = self.res.config.adjust_time;
@@ -959,16 +1386,35 @@ impl Scene for GameScene {
 
     fn update(&mut self, tm: &mut TimeManager) -> Result<()> {
         self.res.audio.recover_if_needed()?;
+        self.poll_reload();
+        self.handle_remote(tm)?;
         if matches!(self.state, State::Playing) {
             tm.update(self.music.position() as f64);
         }
         if self.mode == GameMode::Exercise && tm.now() > self.exercise_range.end as f64 && !tm.paused() {
             let state = self.state.clone();
+            if let Some(practice) = &mut self.practice {
+                let offset = self.chart.offset + self.res.config.offset + self.info_offset;
+                let result = practice.finish_iteration(&self.judge, (self.exercise_range.start - offset)..(self.exercise_range.end - offset));
+                broadcast(&mut self.overlay_sinks, &OverlayEvent::PracticeIteration(result));
+            }
+            self.apply_practice()?;
             reset!(self, self.res, tm);
             self.state = state;
-            tm.seek_to(self.exercise_range.start as f64);
-            tm.pause();
-            self.music.pause()?;
+            tm.seek_to(self.exercise_start() as f64);
+            if self.practice.is_some() {
+                // restart right away, the lead-in is counted down on screen
+                let start = tm.now() as f32;
+                if start < 0. {
+                    self.state = State::BeforeMusic;
+                } else {
+                    self.music.seek_to(start)?;
+                    self.music.play()?;
+                }
+            } else {
+                tm.pause();
+                self.music.pause()?;
+            }
         }
         let offset = self.offset();
         let time = tm.now() as f32;
@@ -979,12 +1425,12 @@ impl Scene for GameScene {
                     self.state = State::BeforeMusic;
                     tm.reset();
                     tm.seek_to(if self.mode == GameMode::Exercise {
-                        self.exercise_range.start as f64
+                        self.exercise_start() as f64
                     } else {
                         offset.min(0.) as f64
                     });
                     self.last_update_time = tm.real_time();
-                    if self.first_in && self.mode == GameMode::Exercise {
+                    if self.first_in && self.mode == GameMode::Exercise && self.practice.is_none() {
                         tm.pause();
                         self.first_in = false;
                     }
@@ -992,7 +1438,7 @@ impl Scene for GameScene {
                 } else {
                     self.res.alpha = 1. - (1. - time / Self::BEFORE_TIME).powi(3);
                     if self.mode == GameMode::Exercise {
-                        self.exercise_range.start
+                        self.exercise_start()
                     } else {
                         offset
                     }
@@ -1021,7 +1467,14 @@ impl Scene for GameScene {
                     // TODO strengthen the protection
                     #[cfg(feature = "closed")]
                     if let Some(upload_fn) = &self.upload_fn {
-                        if !self.res.config.offline_mode && !self.res.config.autoplay() && self.res.config.speed >= 1.0 - 1e-3 {
+                        if !self.res.config.offline_mode
+                            && !self.res.config.autoplay()
+                            && self.res.config.speed >= 1.0 - 1e-3
+                            && self.res.config.judge_profile == JudgeProfile::STANDARD
+                            && !matches!(self.mode, GameMode::Replay(_))
+                            && !self.tampered
+                            && self.watcher.is_none()
+                        {
                             if let Some(player) = &self.player {
                                 if let Some(chart) = &self.res.info.id {
                                     record_data = Some(encode_record(self, player.id, *chart));
@@ -1030,7 +1483,15 @@ impl Scene for GameScene {
                         }
                     }
                     let result = self.judge.result();
-                    let record = if self.res.config.autoplay() || self.res.config.speed < 1.0 - 1e-3 {
+                    broadcast(&mut self.overlay_sinks, &OverlayEvent::end(&result));
+                    // results judged with other windows are not comparable to normal records
+                    let record = if self.res.config.autoplay()
+                        || self.res.config.speed < 1.0 - 1e-3
+                        || result.judge_profile != JudgeProfile::STANDARD
+                        || matches!(self.mode, GameMode::Replay(_))
+                        || self.tampered
+                        || self.watcher.is_some()
+                    {
                         None
                     } else {
                         Some(SimpleRecord {
@@ -1040,7 +1501,7 @@ impl Scene for GameScene {
                         })
                     };
                     self.next_scene = match self.mode {
-                        GameMode::Normal | GameMode::NoRetry | GameMode::View => Some(NextScene::Overlay(Box::new(EndingScene::new(
+                        GameMode::Normal | GameMode::NoRetry | GameMode::View | GameMode::Replay(_) => Some(NextScene::Overlay(Box::new(EndingScene::new(
                             self.res.background.clone(),
                             self.res.illustration.clone(),
                             self.res.player.clone(),
@@ -1086,6 +1547,7 @@ impl Scene for GameScene {
             WHITE
         };
         self.res.judge_line_color.a *= self.res.alpha;
+        self.res.combo = self.judge.combo();
         self.chart.update(&mut self.res);
         let res = &mut self.res;
         if res.config.interactive && is_key_pressed(KeyCode::Space) {
@@ -1119,7 +1581,7 @@ impl Scene for GameScene {
             }
         }
         for e in &mut self.effects {
-            e.update(&self.res);
+            e.update(&self.res, &mut self.chart.bpm_list.borrow_mut(), &self.chart.lines);
         }
         if let Some((id, text)) = take_input() {
             let offset = self.offset().min(0.);
@@ -1151,6 +1613,10 @@ impl Scene for GameScene {
                 _ => return_input(id, text),
             }
         }
+        if let Some(remote) = self.remote {
+            remote.publish(&self.remote_state(tm));
+        }
+        self.feed_overlay(tm);
         Ok(())
     }
 
@@ -1291,7 +1757,7 @@ impl Scene for GameScene {
             tm.speed = 1.0;
             tm.adjust_time = false;
             match self.mode {
-                GameMode::Normal | GameMode::Exercise | GameMode::NoRetry | GameMode::View => NextScene::Pop,
+                GameMode::Normal | GameMode::Exercise | GameMode::NoRetry | GameMode::View | GameMode::Replay(_) => NextScene::Pop,
                 GameMode::TweakOffset => NextScene::PopWithResult(Box::new(None::<f32>)),
             }
         } else if let Some(next_scene) = self.next_scene.take() {
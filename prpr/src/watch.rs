@@ -0,0 +1,69 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Polls a chart folder for changes by comparing modification times and sizes.
+///
+/// Polling keeps this working on every platform without a file notification backend; chart
+/// folders are small enough for a rescan every [`POLL_INTERVAL`] to be cheap.
+pub struct ChartWatcher {
+    root: PathBuf,
+    snapshot: HashMap<PathBuf, (Option<SystemTime>, u64)>,
+    last_poll: Instant,
+}
+
+impl ChartWatcher {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        let snapshot = scan(&root);
+        Self {
+            root,
+            snapshot,
+            last_poll: Instant::now(),
+        }
+    }
+
+    #[inline]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns `true` once for every batch of changes seen since the last call.
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let snapshot = scan(&self.root);
+        if snapshot == self.snapshot {
+            return false;
+        }
+        self.snapshot = snapshot;
+        true
+    }
+}
+
+fn scan(root: &Path) -> HashMap<PathBuf, (Option<SystemTime>, u64)> {
+    fn walk(dir: &Path, out: &mut HashMap<PathBuf, (Option<SystemTime>, u64)>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                walk(&entry.path(), out);
+            } else {
+                out.insert(entry.path(), (meta.modified().ok(), meta.len()));
+            }
+        }
+    }
+    let mut res = HashMap::new();
+    walk(root, &mut res);
+    res
+}